@@ -0,0 +1,79 @@
+//! Compares the node layouts of `CompressedForest` and `SoaForest` on a find-root-heavy workload with large values.
+//! Queries repeat on nodes whose paths are already compressed, where `SoaForest` reads parents only.
+//!
+//! Run with `cargo bench --bench layout`.
+
+use std::convert::Infallible;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use elu::{AssociativeOperation, CompressedForest, NewRoot, SoaForest};
+
+type Value = [u64; 16];
+
+struct WrappingAdd;
+
+impl AssociativeOperation<Value> for WrappingAdd {
+    type Error = Infallible;
+
+    fn associate(lhs: &Value, rhs: &Value) -> Result<Value, Self::Error> {
+        let mut value = *lhs;
+        for (v, r) in value.iter_mut().zip(rhs) {
+            *v = v.wrapping_add(*r);
+        }
+        Ok(value)
+    }
+}
+
+const NODES: usize = 1 << 20;
+const QUERIES: usize = 1 << 22;
+
+fn random_pairs(count: usize, bound: usize) -> Vec<(usize, usize)> {
+    let mut state: usize = 11;
+    let mut next = || {
+        state = state
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (state >> 33) % bound
+    };
+    (0..count).map(|_| (next(), next())).collect()
+}
+
+fn run<F>(mut forest: F, links: &[(usize, usize)], queries: &[(usize, usize)]) -> Duration
+where
+    F: NewRoot<Value = Value, Operation = WrappingAdd, Error = Infallible>,
+    F::Id: Copy,
+{
+    let ids: Vec<_> = (0..NODES)
+        .map(|i| forest.new_root([i as u64; 16]))
+        .collect();
+    for &(a, b) in links {
+        let _ = forest.try_link(ids[a], ids[b]);
+    }
+
+    let start = Instant::now();
+    for &(a, _) in queries {
+        black_box(forest.find_root(ids[a]));
+    }
+    start.elapsed()
+}
+
+fn main() {
+    let links = random_pairs(NODES, NODES);
+    let queries = random_pairs(QUERIES, NODES);
+
+    let aos = run(
+        CompressedForest::<Value, WrappingAdd, u32>::with_capacity(NODES),
+        &links,
+        &queries,
+    );
+    let soa = run(
+        SoaForest::<Value, WrappingAdd, u32>::with_capacity(NODES),
+        &links,
+        &queries,
+    );
+
+    println!("find_root, {QUERIES} queries on {NODES} nodes");
+    println!("  CompressedForest: {aos:?}");
+    println!("  SoaForest:        {soa:?}");
+}
@@ -0,0 +1,68 @@
+//! Compares the checked and unchecked evaluations of `CompressedForest` on compressed paths, where bounds checks
+//! are a noticeable part of the work, and on a fresh forest, where path compression dominates.
+//!
+//! Run with `cargo bench --bench unchecked`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use elu::operation::CloneAdd;
+use elu::{CompressedForest, EvalLinkUpdate};
+
+type Forest = CompressedForest<u64, CloneAdd, u32>;
+
+const NODES: usize = 1 << 20;
+const QUERIES: usize = 1 << 24;
+
+fn random_keys(count: usize, bound: usize) -> Vec<usize> {
+    let mut state: usize = 11;
+    (0..count)
+        .map(|_| {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 33) % bound
+        })
+        .collect()
+}
+
+// Links every node below a random node of lower key, so that the forest is a single tree.
+fn forest() -> (Forest, Vec<<Forest as EvalLinkUpdate>::Id>) {
+    let mut forest: Forest = (0..NODES as u64).collect();
+    let ids: Vec<_> = forest.roots().collect();
+    for (key, parent) in random_keys(NODES, NODES).into_iter().enumerate().skip(1) {
+        forest.link(ids[parent % key], ids[key]);
+    }
+    (forest, ids)
+}
+
+fn run(unchecked: bool, compressed: bool, queries: &[usize]) -> Duration {
+    let (mut forest, ids) = forest();
+    if compressed {
+        forest.compress_all();
+    }
+
+    let start = Instant::now();
+    for &key in queries {
+        if unchecked {
+            // SAFETY: every id was returned by the forest
+            black_box(unsafe { forest.eval_unchecked(ids[key]) });
+        } else {
+            black_box(forest.eval(ids[key]));
+        }
+    }
+    start.elapsed()
+}
+
+fn main() {
+    let queries = random_keys(QUERIES, NODES);
+
+    println!("eval, {QUERIES} queries on {NODES} nodes");
+    for (compressed, name) in [(true, "compressed paths"), (false, "fresh forest")] {
+        let checked = run(false, compressed, &queries);
+        let unchecked = run(true, compressed, &queries);
+        println!("  {name}");
+        println!("    checked:   {checked:?}");
+        println!("    unchecked: {unchecked:?}");
+    }
+}
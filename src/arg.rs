@@ -0,0 +1,211 @@
+use std::marker::PhantomData;
+
+use crate::index::Index;
+use crate::operation::{AssociativeOperation, SelectionOperation};
+use crate::{CompressedForest, EvalLinkUpdate, ForestError, NewRoot};
+
+// Associates values tagged with the key of the node they come from, the tag of the selected operand is kept.
+#[derive(Debug)]
+struct Arg<O>(PhantomData<O>);
+
+impl<O> Clone for Arg<O> {
+    fn clone(&self) -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<V, O> AssociativeOperation<(usize, V)> for Arg<O>
+where
+    V: Clone,
+    O: SelectionOperation<V>,
+{
+    type Error = O::Error;
+
+    #[inline]
+    fn associate(lhs: &(usize, V), rhs: &(usize, V)) -> Result<(usize, V), Self::Error> {
+        if O::selects_lhs(&lhs.1, &rhs.1)? {
+            Ok(lhs.clone())
+        } else {
+            Ok(rhs.clone())
+        }
+    }
+}
+
+/// An EVAL-LINK-UPDATE forest structure with path compression that also finds the node achieving the evaluated value.
+///
+/// Evaluating with a [`SelectionOperation`] (e.g minimum or maximum) selects the value of one of the nodes of the path,
+/// [`try_eval_arg`](ArgForest::try_eval_arg) returns that node along with the value, as required by the
+/// Lengauer–Tarjan dominators algorithm.
+///
+/// `V` is the value type associated to nodes in the forest and `O` is the selection operation applied when evaluating.
+#[derive(Debug, Clone)]
+pub struct ArgForest<V, O>
+where
+    O: 'static,
+{
+    forest: CompressedForest<(usize, V), Arg<O>>,
+}
+
+impl<V, O> Default for ArgForest<V, O>
+where
+    V: Clone,
+    O: SelectionOperation<V>,
+{
+    #[inline]
+    fn default() -> Self {
+        Self {
+            forest: CompressedForest::new(),
+        }
+    }
+}
+
+impl<V, O> ArgForest<V, O>
+where
+    V: Clone,
+    O: SelectionOperation<V>,
+{
+    /// Creates a new empty forest.
+    #[inline]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new empty forest with a given capacity.
+    #[inline]
+    #[must_use]
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            forest: CompressedForest::with_capacity(capacity),
+        }
+    }
+
+    /// Reserve enough space for a given number of nodes.
+    #[inline]
+    pub fn reserve(&mut self, additional: usize) {
+        self.forest.reserve(additional);
+    }
+
+    /// Computes the value of the node identified by `id` and returns it with the id of the node of the path
+    /// whose value has been selected.
+    ///
+    /// When the value of a root has been replaced by [`try_update`](EvalLinkUpdate::try_update), the root
+    /// is reported as the node holding the new value.
+    ///
+    /// # Errors
+    /// Will return [`ForestError::InvalidId`] if the id does not belong to the forest
+    /// and [`ForestError::Operation`] if [`O::selects_lhs`](SelectionOperation::selects_lhs) fails.
+    pub fn try_eval_arg(
+        &mut self,
+        id: Index<Self>,
+    ) -> Result<(Index<Self>, V), ForestError<O::Error>> {
+        let (key, value) = self.forest.try_eval(Self::inner(id))?;
+        Ok((Index::new(key), value))
+    }
+
+    #[inline]
+    fn inner(id: Index<Self>) -> Index<CompressedForest<(usize, V), Arg<O>>> {
+        Index::new(id.into())
+    }
+}
+
+impl<V, O> ArgForest<V, O>
+where
+    V: Clone,
+    O: SelectionOperation<V, Error = std::convert::Infallible>,
+{
+    /// Infallible version of [`try_eval_arg`](ArgForest::try_eval_arg).
+    ///
+    /// # Panics
+    /// Panics if the id does not belong to the forest.
+    #[inline]
+    pub fn eval_arg(&mut self, id: Index<Self>) -> (Index<Self>, V) {
+        self.try_eval_arg(id).unwrap()
+    }
+}
+
+impl<V, O> EvalLinkUpdate for ArgForest<V, O>
+where
+    V: Clone,
+    O: 'static + SelectionOperation<V>,
+{
+    type Id = Index<Self>;
+    type Value = V;
+    type Operation = O;
+    type Error = O::Error;
+
+    #[inline]
+    fn node_count(&self) -> Option<usize> {
+        Some(self.forest.len())
+    }
+
+    fn try_find_root(&mut self, id: Index<Self>) -> Result<Index<Self>, ForestError<O::Error>> {
+        let root = self.forest.try_find_root(Self::inner(id))?;
+        Ok(Index::new(root.into()))
+    }
+
+    fn try_link(
+        &mut self,
+        id_a: Index<Self>,
+        id_b: Index<Self>,
+    ) -> Result<(), ForestError<O::Error>> {
+        self.forest.try_link(Self::inner(id_a), Self::inner(id_b))
+    }
+
+    fn try_update(&mut self, id: Index<Self>, value: V) -> Result<(), ForestError<O::Error>> {
+        // the new value of the root is tagged with the root itself
+        let root = self.forest.try_find_root(Self::inner(id))?;
+        self.forest.try_update(root, (root.into(), value))
+    }
+
+    fn try_eval(&mut self, id: Index<Self>) -> Result<V, ForestError<O::Error>> {
+        Ok(self.try_eval_arg(id)?.1)
+    }
+}
+
+impl<V, O> NewRoot for ArgForest<V, O>
+where
+    V: Clone,
+    O: 'static + SelectionOperation<V>,
+{
+    fn new_root(&mut self, value: V) -> Index<Self> {
+        let key = self.forest.len();
+        let root = self.forest.new_root((key, value));
+        Index::new(root.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::operation::CloneMin;
+
+    #[test]
+    fn min_forest() {
+        let mut forest: ArgForest<usize, CloneMin> = ArgForest::with_capacity(5);
+        let v0 = forest.new_root(4);
+        let v1 = forest.new_root(2);
+        let v2 = forest.new_root(5);
+        let v3 = forest.new_root(3);
+        let v4 = forest.new_root(1);
+
+        forest.link(v0, v1);
+        forest.link(v1, v2);
+        forest.link(v2, v3);
+
+        assert_eq!((v1, 2), forest.eval_arg(v3));
+        assert_eq!((v0, 4), forest.eval_arg(v0));
+        assert_eq!(2, forest.eval(v2));
+
+        forest.link(v4, v0);
+
+        assert_eq!((v4, 1), forest.eval_arg(v3));
+        assert_eq!(v4, forest.find_root(v2));
+
+        forest.update(v3, 6);
+
+        assert_eq!((v1, 2), forest.eval_arg(v3));
+        assert_eq!((v4, 6), forest.eval_arg(v4));
+        assert_eq!(Some(5), forest.node_count());
+    }
+}
@@ -0,0 +1,496 @@
+use std::convert::Infallible;
+use std::fmt;
+use std::marker::PhantomData;
+use std::sync::atomic::{fence, AtomicUsize, Ordering};
+use std::sync::{Mutex, PoisonError};
+
+use crate::index::Index;
+use crate::operation::CommutativeOperation;
+use crate::{EvalLinkUpdate, ForestError, NewRoot};
+
+// The parent of a root is encoded as `ROOT`.
+const ROOT: usize = usize::MAX;
+// Version of the nodes that have not been created yet, versions of created nodes start at 2.
+const UNINITIALIZED: usize = 1;
+
+// Parent and value are guarded by a sequence lock: the version is odd while a thread writes the node,
+// readers retry until they read both fields under the same even version.
+struct AtomicNode {
+    version: AtomicUsize,
+    parent: AtomicUsize,
+    value: AtomicUsize,
+}
+
+/// An EVAL-LINK-UPDATE forest structure with path compression for `usize` values that can be shared across threads.
+///
+/// Every operation takes `&self`. Unlike [`ConcurrentForest`](crate::ConcurrentForest), parents and values are stored
+/// in place in atomic integers, so the forest never allocates after its creation. Each node is guarded by a sequence lock:
+/// readers never block, and threads writing the same node (to compress a path or update a root) wait for each other
+/// only while the two fields are stored. Links are serialized so that cycles can be detected.
+///
+/// Evaluations running concurrently with links or updates of the same tree may or may not observe them.
+///
+/// The number of nodes is bounded by the capacity given at creation.
+///
+/// `O` is the associative operation applied when evaluating. It must be a [`CommutativeOperation`], so that the forest
+/// is free to reorder its operands when threads compress and link concurrently.
+pub struct AtomicForest<O>
+where
+    O: 'static,
+{
+    nodes: Box<[AtomicNode]>,
+    // number of reserved slots, may exceed the capacity
+    reserved: AtomicUsize,
+    link_lock: Mutex<()>,
+    _op: PhantomData<O>,
+}
+
+impl<O> fmt::Debug for AtomicForest<O>
+where
+    O: 'static,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AtomicForest")
+            .field("capacity", &self.nodes.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<O> AtomicForest<O>
+where
+    O: CommutativeOperation<usize>,
+{
+    /// Creates a new empty forest that can hold up to `capacity` nodes.
+    #[must_use]
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            nodes: (0..capacity)
+                .map(|_| AtomicNode {
+                    version: AtomicUsize::new(UNINITIALIZED),
+                    parent: AtomicUsize::new(ROOT),
+                    value: AtomicUsize::new(0),
+                })
+                .collect(),
+            reserved: AtomicUsize::new(0),
+            link_lock: Mutex::new(()),
+            _op: PhantomData,
+        }
+    }
+
+    /// Returns the maximum number of nodes the forest can hold.
+    #[inline]
+    #[must_use]
+    pub fn capacity(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Creates a new tree root in the forest with the given value.
+    ///
+    /// # Errors
+    /// Gives the value back if the forest is full.
+    pub fn try_new_root(&self, value: usize) -> Result<Index<Self>, usize> {
+        let key = self.reserved.fetch_add(1, Ordering::Relaxed);
+        if key >= self.capacity() {
+            return Err(value);
+        }
+        let node = &self.nodes[key];
+        node.value.store(value, Ordering::Relaxed);
+        node.version.store(UNINITIALIZED + 1, Ordering::Release);
+        Ok(Index::new(key))
+    }
+
+    /// Infallible version of [`try_new_root`](AtomicForest::try_new_root).
+    ///
+    /// # Panics
+    /// Panics if the forest is full.
+    #[inline]
+    #[must_use]
+    pub fn new_root(&self, value: usize) -> Index<Self> {
+        match self.try_new_root(value) {
+            Ok(id) => id,
+            Err(_) => panic!("the forest is full"),
+        }
+    }
+
+    /// Finds the root of the tree that contains the node identified by `id`.
+    ///
+    /// # Errors
+    /// Will return [`ForestError::InvalidId`] if the id does not belong to the forest
+    /// and [`ForestError::Operation`] if [`O::associate`](crate::AssociativeOperation::associate) fails.
+    pub fn try_find_root(&self, id: Index<Self>) -> Result<Index<Self>, ForestError<O::Error>> {
+        let key = self.check(id)?;
+        Ok(Index::new(self.find_root_key(key)?))
+    }
+
+    /// Evaluates the node identified by `id`.
+    ///
+    /// # Errors
+    /// Will return [`ForestError::InvalidId`] if the id does not belong to the forest
+    /// and [`ForestError::Operation`] if [`O::associate`](crate::AssociativeOperation::associate) fails.
+    pub fn try_eval(&self, id: Index<Self>) -> Result<usize, ForestError<O::Error>> {
+        let key = self.check(id)?;
+        self.find_root_key(key)?;
+
+        let (mut parent, mut value) = self.read(key);
+        while let Some(parent_key) = parent {
+            let parent_value;
+            (parent, parent_value) = self.read(parent_key);
+            value = O::associate(&parent_value, &value).map_err(ForestError::Operation)?;
+        }
+        Ok(value)
+    }
+
+    /// Links the root of the tree that contains the node identified by `id_b` to the node identified by `id_a`.
+    ///
+    /// # Errors
+    /// Will return [`ForestError::InvalidId`] if an id does not belong to the forest,
+    /// [`ForestError::WouldCreateCycle`] if both nodes belong to the same tree
+    /// and [`ForestError::Operation`] if [`O::associate`](crate::AssociativeOperation::associate) fails.
+    pub fn try_link(
+        &self,
+        id_a: Index<Self>,
+        id_b: Index<Self>,
+    ) -> Result<(), ForestError<O::Error>> {
+        let id_a = self.check(id_a)?;
+        let id_b = self.check(id_b)?;
+
+        // roots only stop being roots when linked, so they cannot change while the lock is held
+        let _guard = self
+            .link_lock
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let root_a_key = self.find_root_key(id_a)?;
+        let root_b_key = self.find_root_key(id_b)?;
+        if root_a_key == root_b_key {
+            return Err(ForestError::WouldCreateCycle);
+        }
+
+        // value of "node a" relative to its root
+        let relative_a = if root_a_key == id_a {
+            None
+        } else {
+            let (mut parent, mut value) = self.read(id_a);
+            while let Some(parent_key) = parent.filter(|&key| key != root_a_key) {
+                let parent_value;
+                (parent, parent_value) = self.read(parent_key);
+                value = O::associate(&parent_value, &value).map_err(ForestError::Operation)?;
+            }
+            Some(value)
+        };
+
+        // the root of "tree b" may be updated concurrently
+        loop {
+            let (_, value_b) = self.read(root_b_key);
+            let new_value = match relative_a {
+                Some(value_a) => {
+                    O::associate(&value_a, &value_b).map_err(ForestError::Operation)?
+                }
+                None => value_b,
+            };
+            if self.replace(root_b_key, (None, value_b), (Some(root_a_key), new_value)) {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Replaces the value of the root of the tree that contains the node identified by `id`.
+    ///
+    /// # Errors
+    /// Will return [`ForestError::InvalidId`] if the id does not belong to the forest
+    /// and [`ForestError::Operation`] if [`O::associate`](crate::AssociativeOperation::associate) fails.
+    pub fn try_update(&self, id: Index<Self>, value: usize) -> Result<(), ForestError<O::Error>> {
+        let key = self.check(id)?;
+        loop {
+            let root_key = self.find_root_key(key)?;
+            let (parent, current) = self.read(root_key);
+            if parent.is_none() && self.replace(root_key, (None, current), (None, value)) {
+                return Ok(());
+            }
+            // the root has been linked or updated concurrently
+        }
+    }
+
+    /// Checks if two nodes belong to the same tree.
+    ///
+    /// # Errors
+    /// Will return [`ForestError::InvalidId`] if an id does not belong to the forest
+    /// and [`ForestError::Operation`] if [`O::associate`](crate::AssociativeOperation::associate) fails.
+    pub fn try_connected(
+        &self,
+        id_a: Index<Self>,
+        id_b: Index<Self>,
+    ) -> Result<bool, ForestError<O::Error>> {
+        Ok(self.try_find_root(id_a)? == self.try_find_root(id_b)?)
+    }
+
+    fn check(&self, id: Index<Self>) -> Result<usize, ForestError<O::Error>> {
+        let key: usize = id.into();
+        match self.nodes.get(key) {
+            Some(node) if node.version.load(Ordering::Acquire) != UNINITIALIZED => Ok(key),
+            _ => Err(ForestError::InvalidId),
+        }
+    }
+
+    // Reads the parent and the value of a created node.
+    fn read(&self, key: usize) -> (Option<usize>, usize) {
+        let node = &self.nodes[key];
+        loop {
+            let version = node.version.load(Ordering::Acquire);
+            if version.is_multiple_of(2) {
+                let parent = node.parent.load(Ordering::Relaxed);
+                let value = node.value.load(Ordering::Relaxed);
+                fence(Ordering::Acquire);
+                if node.version.load(Ordering::Relaxed) == version {
+                    return ((parent != ROOT).then_some(parent), value);
+                }
+            }
+            std::hint::spin_loop();
+        }
+    }
+
+    // Writes the parent and the value of a node if they are still `current`. Associative operations are never
+    // applied while a node is locked, so that a panicking operation cannot leave it locked.
+    fn replace(
+        &self,
+        key: usize,
+        current: (Option<usize>, usize),
+        (parent, value): (Option<usize>, usize),
+    ) -> bool {
+        let node = &self.nodes[key];
+        // lock the node by making its version odd
+        let version = loop {
+            let version = node.version.load(Ordering::Relaxed);
+            if version.is_multiple_of(2)
+                && node
+                    .version
+                    .compare_exchange_weak(
+                        version,
+                        version + 1,
+                        Ordering::Acquire,
+                        Ordering::Relaxed,
+                    )
+                    .is_ok()
+            {
+                fence(Ordering::Release);
+                break version;
+            }
+            std::hint::spin_loop();
+        };
+
+        let current_parent = node.parent.load(Ordering::Relaxed);
+        let replaced = (current_parent != ROOT).then_some(current_parent) == current.0
+            && node.value.load(Ordering::Relaxed) == current.1;
+        if replaced {
+            node.parent.store(parent.unwrap_or(ROOT), Ordering::Relaxed);
+            node.value.store(value, Ordering::Relaxed);
+        }
+        node.version
+            .store(version.wrapping_add(2), Ordering::Release);
+        replaced
+    }
+
+    fn find_root_key(&self, key: usize) -> Result<usize, ForestError<O::Error>> {
+        let mut path = vec![key];
+        let (mut parent, _) = self.read(key);
+        while let Some(parent_key) = parent {
+            path.push(parent_key);
+            (parent, _) = self.read(parent_key);
+        }
+
+        // compress from the top so that every node only needs to skip its parent
+        for &key in path.iter().rev() {
+            loop {
+                let (parent, value) = self.read(key);
+                let Some(parent_key) = parent else {
+                    break;
+                };
+                let (Some(grand_parent_key), parent_value) = self.read(parent_key) else {
+                    break;
+                };
+                let merged_values =
+                    O::associate(&parent_value, &value).map_err(ForestError::Operation)?;
+                self.replace(
+                    key,
+                    (parent, value),
+                    (Some(grand_parent_key), merged_values),
+                );
+            }
+        }
+
+        let mut root_key = key;
+        while let (Some(parent_key), _) = self.read(root_key) {
+            root_key = parent_key;
+        }
+        Ok(root_key)
+    }
+}
+
+impl<O> AtomicForest<O>
+where
+    O: CommutativeOperation<usize, Error = Infallible>,
+{
+    /// Infallible version of [`try_find_root`](AtomicForest::try_find_root).
+    ///
+    /// # Panics
+    /// Panics if the id does not belong to the forest.
+    #[inline]
+    pub fn find_root(&self, id: Index<Self>) -> Index<Self> {
+        self.try_find_root(id).unwrap()
+    }
+
+    /// Infallible version of [`try_eval`](AtomicForest::try_eval).
+    ///
+    /// # Panics
+    /// Panics if the id does not belong to the forest.
+    #[inline]
+    pub fn eval(&self, id: Index<Self>) -> usize {
+        self.try_eval(id).unwrap()
+    }
+
+    /// Infallible version of [`try_link`](AtomicForest::try_link).
+    ///
+    /// # Panics
+    /// Panics if an id does not belong to the forest or if both nodes belong to the same tree.
+    #[inline]
+    pub fn link(&self, id_a: Index<Self>, id_b: Index<Self>) {
+        self.try_link(id_a, id_b).unwrap();
+    }
+
+    /// Infallible version of [`try_update`](AtomicForest::try_update).
+    ///
+    /// # Panics
+    /// Panics if the id does not belong to the forest.
+    #[inline]
+    pub fn update(&self, id: Index<Self>, value: usize) {
+        self.try_update(id, value).unwrap();
+    }
+
+    /// Infallible version of [`try_connected`](AtomicForest::try_connected).
+    ///
+    /// # Panics
+    /// Panics if an id does not belong to the forest.
+    #[inline]
+    pub fn connected(&self, id_a: Index<Self>, id_b: Index<Self>) -> bool {
+        self.try_connected(id_a, id_b).unwrap()
+    }
+}
+
+impl<O> EvalLinkUpdate for AtomicForest<O>
+where
+    O: 'static + CommutativeOperation<usize>,
+{
+    type Id = Index<Self>;
+    type Value = usize;
+    type Operation = O;
+    type Error = O::Error;
+
+    #[inline]
+    fn node_count(&self) -> Option<usize> {
+        Some(self.reserved.load(Ordering::Relaxed).min(self.capacity()))
+    }
+
+    fn try_find_root(&mut self, id: Index<Self>) -> Result<Index<Self>, ForestError<O::Error>> {
+        Self::try_find_root(self, id)
+    }
+
+    fn try_link(
+        &mut self,
+        id_a: Index<Self>,
+        id_b: Index<Self>,
+    ) -> Result<(), ForestError<O::Error>> {
+        Self::try_link(self, id_a, id_b)
+    }
+
+    fn try_update(&mut self, id: Index<Self>, value: usize) -> Result<(), ForestError<O::Error>> {
+        Self::try_update(self, id, value)
+    }
+
+    fn try_eval(&mut self, id: Index<Self>) -> Result<usize, ForestError<O::Error>> {
+        Self::try_eval(self, id)
+    }
+}
+
+impl<O> NewRoot for AtomicForest<O>
+where
+    O: 'static + CommutativeOperation<usize>,
+{
+    /// # Panics
+    /// Panics if the forest is full.
+    fn new_root(&mut self, value: usize) -> Index<Self> {
+        Self::new_root(self, value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::operation::*;
+
+    #[test]
+    fn add_forest() {
+        let forest: AtomicForest<CloneAdd> = AtomicForest::with_capacity(4);
+        let v0 = forest.new_root(2);
+        let v1 = forest.new_root(3);
+        let v2 = forest.new_root(4);
+        let v3 = forest.new_root(5);
+
+        forest.link(v0, v1);
+        forest.link(v2, v3);
+
+        assert_eq!(5, forest.eval(v1));
+        assert_eq!(9, forest.eval(v3));
+
+        forest.link(v3, v0);
+
+        assert_eq!(11, forest.eval(v0));
+        assert_eq!(14, forest.eval(v1));
+        assert_eq!(v2, forest.find_root(v1));
+
+        forest.update(v1, 1);
+
+        assert_eq!(11, forest.eval(v1));
+        assert_eq!(Err(ForestError::WouldCreateCycle), forest.try_link(v1, v2));
+        assert_eq!(Err(7), forest.try_new_root(7));
+    }
+
+    #[test]
+    fn shared_chain() {
+        let n = 1024;
+        let threads = 4;
+        let forest: AtomicForest<CloneAdd> = AtomicForest::with_capacity(n);
+        let ids: Vec<_> = (0..n).map(|_| forest.new_root(1)).collect();
+
+        std::thread::scope(|scope| {
+            for thread in 0..threads {
+                let forest = &forest;
+                let ids = &ids;
+                scope.spawn(move || {
+                    for i in (1..n).filter(|i| i % threads == thread) {
+                        forest.link(ids[i - 1], ids[i]);
+                        // other links only grow the path to the root
+                        assert!(forest.eval(ids[i]) >= 2);
+                        assert!(forest.eval(ids[i - 1]) <= i);
+                    }
+                });
+            }
+        });
+
+        for (i, &id) in ids.iter().enumerate() {
+            assert_eq!(i + 1, forest.eval(id));
+            assert_eq!(ids[0], forest.find_root(id));
+        }
+
+        std::thread::scope(|scope| {
+            for thread in 0..threads {
+                let forest = &forest;
+                let ids = &ids;
+                scope.spawn(move || {
+                    for i in (0..n).filter(|i| i % threads == thread) {
+                        forest.update(ids[i], 2);
+                        assert_eq!(i + 2, forest.eval(ids[i]));
+                    }
+                });
+            }
+        });
+    }
+}
@@ -0,0 +1,366 @@
+use std::marker::PhantomData;
+
+use crate::index::Index;
+use crate::operation::GroupOperation;
+use crate::{EvalLinkUpdate, ForestError, NewRoot};
+
+#[derive(Debug, Clone)]
+pub(crate) struct BalancedNode<V> {
+    pub(crate) parent: Option<usize>,
+    // only meaningful for representatives
+    pub(crate) rank: u32,
+    // the actual root of the tree, only meaningful for representatives
+    pub(crate) root: usize,
+    pub(crate) value: V,
+}
+
+impl<V> BalancedNode<V> {
+    pub(crate) fn new_root(key: usize, value: V) -> Self {
+        Self {
+            parent: None,
+            rank: 0,
+            root: key,
+            value,
+        }
+    }
+}
+
+/// An EVAL-LINK-UPDATE forest structure that performs path compression and links by rank.
+///
+/// Each tree is stored under a representative node that is not necessarily its root: when the
+/// root of the tree being linked has a greater rank, it becomes the parent of the representative of the
+/// other tree. Stored values are then adjusted with inverses so that evaluation still follows the actual
+/// link direction, this is why `O` must be a [`GroupOperation`].
+///
+/// `V` is the value type associated to nodes in the forest and `O` is the associative operation applied when evaluating.
+#[derive(Debug, Clone)]
+pub struct BalancedForest<V, O>
+where
+    O: 'static,
+{
+    nodes: Vec<BalancedNode<V>>,
+    _op: PhantomData<O>,
+}
+
+impl<V, O> Default for BalancedForest<V, O>
+where
+    V: Clone,
+    O: GroupOperation<V>,
+{
+    #[inline]
+    fn default() -> Self {
+        Self {
+            nodes: vec![],
+            _op: PhantomData,
+        }
+    }
+}
+
+impl<V, O> BalancedForest<V, O>
+where
+    V: Clone,
+    O: GroupOperation<V>,
+{
+    /// Creates a new empty forest.
+    #[inline]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new empty forest with a given capacity.
+    #[inline]
+    #[must_use]
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            nodes: Vec::with_capacity(capacity),
+            _op: PhantomData,
+        }
+    }
+
+    /// Reserve enough space for a given number of nodes.
+    #[inline]
+    pub fn reserve(&mut self, additional: usize) {
+        self.nodes.reserve(additional);
+    }
+
+    fn check(&self, id: Index<Self>) -> Result<usize, ForestError<O::Error>> {
+        let key: usize = id.into();
+        if key < self.nodes.len() {
+            Ok(key)
+        } else {
+            Err(ForestError::InvalidId)
+        }
+    }
+
+    // Compresses the path from `key` to its representative and returns the representative.
+    // Linking by rank keeps paths logarithmic, so the recursion depth is bounded.
+    fn find(&mut self, key: usize) -> Result<usize, ForestError<O::Error>> {
+        let parent_key = match self.nodes[key].parent {
+            None => return Ok(key),
+            Some(parent_key) => parent_key,
+        };
+
+        let repr_key = self.find(parent_key)?;
+        if repr_key != parent_key {
+            let merged_values = O::associate(&self.nodes[parent_key].value, &self.nodes[key].value)
+                .map_err(ForestError::Operation)?;
+            let node = &mut self.nodes[key];
+            node.value = merged_values;
+            node.parent = Some(repr_key);
+        }
+
+        Ok(repr_key)
+    }
+
+    // Evaluates a node whose parent is its representative (or that is a representative).
+    fn eval_compressed(&self, key: usize, repr_key: usize) -> Result<V, ForestError<O::Error>> {
+        if key == repr_key {
+            Ok(self.nodes[key].value.clone())
+        } else {
+            O::associate(&self.nodes[repr_key].value, &self.nodes[key].value)
+                .map_err(ForestError::Operation)
+        }
+    }
+}
+
+impl<V, O> EvalLinkUpdate for BalancedForest<V, O>
+where
+    V: Clone,
+    O: 'static + GroupOperation<V>,
+{
+    type Id = Index<Self>;
+    type Value = V;
+    type Operation = O;
+    type Error = O::Error;
+
+    #[inline]
+    fn node_count(&self) -> Option<usize> {
+        Some(self.nodes.len())
+    }
+
+    fn try_find_root(&mut self, id: Index<Self>) -> Result<Index<Self>, ForestError<O::Error>> {
+        let key = self.check(id)?;
+        let repr_key = self.find(key)?;
+        Ok(Index::new(self.nodes[repr_key].root))
+    }
+
+    fn try_link(
+        &mut self,
+        id_a: Index<Self>,
+        id_b: Index<Self>,
+    ) -> Result<(), ForestError<O::Error>> {
+        let id_a = self.check(id_a)?;
+        let id_b = self.check(id_b)?;
+
+        let repr_a_key = self.find(id_a)?;
+        let repr_b_key = self.find(id_b)?;
+        if repr_a_key == repr_b_key {
+            return Err(ForestError::WouldCreateCycle);
+        }
+
+        let rank_a = self.nodes[repr_a_key].rank;
+        let rank_b = self.nodes[repr_b_key].rank;
+
+        if rank_b <= rank_a {
+            // the representative of "tree b" becomes a child of the representative of "tree a"
+            // if "node a" is not the representative of its tree
+            // need to update the value of the representative of "tree b"
+            if repr_a_key != id_a {
+                let new_value =
+                    O::associate(&self.nodes[id_a].value, &self.nodes[repr_b_key].value)
+                        .map_err(ForestError::Operation)?;
+                self.nodes[repr_b_key].value = new_value;
+            }
+            self.nodes[repr_b_key].parent = Some(repr_a_key);
+            if rank_a == rank_b {
+                self.nodes[repr_a_key].rank += 1;
+            }
+        } else {
+            // the representative of "tree a" becomes a child of the representative of "tree b"
+            // but "tree b" is still linked below "node a", so values of both representatives must be fixed
+            let value_a = self.eval_compressed(id_a, repr_a_key)?;
+            let new_value_b = O::associate(&value_a, &self.nodes[repr_b_key].value)
+                .map_err(ForestError::Operation)?;
+            let inverse_b = O::inverse(&new_value_b).map_err(ForestError::Operation)?;
+            let new_value_a = O::associate(&inverse_b, &self.nodes[repr_a_key].value)
+                .map_err(ForestError::Operation)?;
+
+            let root_a_key = self.nodes[repr_a_key].root;
+            let repr_a = &mut self.nodes[repr_a_key];
+            repr_a.value = new_value_a;
+            repr_a.parent = Some(repr_b_key);
+            let repr_b = &mut self.nodes[repr_b_key];
+            repr_b.value = new_value_b;
+            repr_b.root = root_a_key;
+        }
+
+        Ok(())
+    }
+
+    fn try_update(&mut self, id: Index<Self>, value: V) -> Result<(), ForestError<O::Error>> {
+        let key = self.check(id)?;
+        let repr_key = self.find(key)?;
+        let root_key = self.nodes[repr_key].root;
+
+        if root_key == repr_key {
+            self.nodes[repr_key].value = value;
+        } else {
+            // every value of the tree is prefixed by the value of its root,
+            // replace this prefix in the value of the representative
+            self.find(root_key)?;
+            let old_value = self.eval_compressed(root_key, repr_key)?;
+            let inverse = O::inverse(&old_value).map_err(ForestError::Operation)?;
+            let delta = O::associate(&value, &inverse).map_err(ForestError::Operation)?;
+            let new_value = O::associate(&delta, &self.nodes[repr_key].value)
+                .map_err(ForestError::Operation)?;
+            self.nodes[repr_key].value = new_value;
+        }
+
+        Ok(())
+    }
+
+    fn try_eval(&mut self, id: Index<Self>) -> Result<V, ForestError<O::Error>> {
+        let key = self.check(id)?;
+        let repr_key = self.find(key)?;
+        self.eval_compressed(key, repr_key)
+    }
+}
+
+impl<V, O> NewRoot for BalancedForest<V, O>
+where
+    V: Clone,
+    O: 'static + GroupOperation<V>,
+{
+    fn new_root(&mut self, value: V) -> Index<Self> {
+        let index = self.nodes.len();
+        self.nodes.push(BalancedNode::new_root(index, value));
+        Index::new(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::operation::*;
+    use crate::CompressedForest;
+
+    // Composition of permutations of three elements, a non-commutative group.
+    struct Compose;
+
+    impl AssociativeOperation<[u8; 3]> for Compose {
+        type Error = std::convert::Infallible;
+
+        fn associate(lhs: &[u8; 3], rhs: &[u8; 3]) -> Result<[u8; 3], Self::Error> {
+            Ok(lhs.map(|i| rhs[usize::from(i)]))
+        }
+    }
+
+    impl GroupOperation<[u8; 3]> for Compose {
+        fn inverse(value: &[u8; 3]) -> Result<[u8; 3], Self::Error> {
+            let mut inverse = [0; 3];
+            for (i, &v) in value.iter().enumerate() {
+                inverse[usize::from(v)] = i as u8;
+            }
+            Ok(inverse)
+        }
+    }
+
+    #[test]
+    fn add_forest() {
+        let mut forest: BalancedForest<i64, CloneAdd> = BalancedForest::with_capacity(4);
+        let v0 = forest.new_root(2);
+        let v1 = forest.new_root(3);
+        let v2 = forest.new_root(4);
+        let v3 = forest.new_root(5);
+
+        forest.link(v0, v1);
+        forest.link(v2, v3);
+
+        assert_eq!(5, forest.eval(v1));
+        assert_eq!(9, forest.eval(v3));
+
+        forest.link(v3, v0);
+
+        assert_eq!(11, forest.eval(v0));
+        assert_eq!(14, forest.eval(v1));
+        assert_eq!(v2, forest.find_root(v1));
+
+        forest.update(v1, 1);
+
+        assert_eq!(1, forest.eval(v2));
+        assert_eq!(11, forest.eval(v1));
+    }
+
+    #[test]
+    fn same_as_compressed() {
+        let permutations = [
+            [0, 1, 2],
+            [0, 2, 1],
+            [1, 0, 2],
+            [1, 2, 0],
+            [2, 0, 1],
+            [2, 1, 0],
+        ];
+        let mut balanced: BalancedForest<[u8; 3], Compose> = BalancedForest::new();
+        let mut compressed: CompressedForest<[u8; 3], Compose> = CompressedForest::new();
+
+        let n = 64;
+        let mut state: usize = 7;
+        let mut next = |bound: usize| {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 33) % bound
+        };
+
+        let balanced_ids: Vec<_> = (0..n)
+            .map(|i| balanced.new_root(permutations[i % 6]))
+            .collect();
+        let compressed_ids: Vec<_> = (0..n)
+            .map(|i| compressed.new_root(permutations[i % 6]))
+            .collect();
+
+        for _ in 0..512 {
+            let a = next(n);
+            let b = next(n);
+            match next(3) {
+                0 => {
+                    let linked = balanced.try_link(balanced_ids[a], balanced_ids[b]);
+                    let expected = compressed.try_link(compressed_ids[a], compressed_ids[b]);
+                    assert_eq!(expected, linked);
+                }
+                1 => {
+                    let value = permutations[next(6)];
+                    balanced.update(balanced_ids[a], value);
+                    compressed.update(compressed_ids[a], value);
+                }
+                _ => {}
+            }
+            assert_eq!(
+                compressed.eval(compressed_ids[a]),
+                balanced.eval(balanced_ids[a])
+            );
+            assert_eq!(
+                usize::from(compressed.find_root(compressed_ids[b])),
+                usize::from(balanced.find_root(balanced_ids[b]))
+            );
+        }
+    }
+
+    #[test]
+    fn balanced_chain() {
+        let mut forest: BalancedForest<i64, CloneAdd> = BalancedForest::new();
+        let mut root = forest.new_root(0);
+        // each new root becomes the parent of the whole chain
+        for i in 1..1024 {
+            let new_root = forest.new_root(i);
+            forest.link(new_root, root);
+            root = new_root;
+        }
+
+        let max_rank = forest.nodes.iter().map(|node| node.rank).max().unwrap();
+        assert!(max_rank <= 10);
+        assert_eq!((0..1024).sum::<i64>(), forest.eval(Index::new(0)));
+    }
+}
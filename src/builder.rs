@@ -0,0 +1,184 @@
+use crate::compression::Compression;
+use crate::index::{Index, IndexType, Indices};
+use crate::observer::ForestObserver;
+use crate::operation::AssociativeOperation;
+use crate::{CompressedForest, ForestError};
+
+/// A builder of [`CompressedForest`] from values and parent-child relations given up front.
+///
+/// Nodes are referred to by their position, in the order their values are given. A relation `(parent, child)` makes
+/// `child` a child of `parent`, as if `child` was a root linked to `parent` (see
+/// [`EvalLinkUpdate::try_link`](crate::EvalLinkUpdate::try_link)): its evaluation is the evaluation of its parent
+/// associated with its value. Relations are validated once, when the forest is built.
+///
+/// `V` is the value type associated to nodes in the forest.
+#[derive(Debug, Clone)]
+pub struct ForestBuilder<V> {
+    values: Vec<V>,
+    relations: Vec<(usize, usize)>,
+}
+
+impl<V> Default for ForestBuilder<V> {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            values: vec![],
+            relations: vec![],
+        }
+    }
+}
+
+impl<V> ForestBuilder<V>
+where
+    V: Clone,
+{
+    /// Creates a new builder without nodes.
+    #[inline]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new builder with a given capacity of nodes.
+    #[inline]
+    #[must_use]
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            values: Vec::with_capacity(capacity),
+            relations: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Returns the number of nodes given to the builder.
+    #[inline]
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Returns `true` if no node was given to the builder.
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Adds a node with the given value, its position is the number of nodes given before.
+    #[inline]
+    #[must_use]
+    pub fn value(mut self, value: V) -> Self {
+        self.values.push(value);
+        self
+    }
+
+    /// Adds a node for each value, in order.
+    #[inline]
+    #[must_use]
+    pub fn values<I>(mut self, values: I) -> Self
+    where
+        I: IntoIterator<Item = V>,
+    {
+        self.values.extend(values);
+        self
+    }
+
+    /// Makes the node at position `child` a child of the node at position `parent`.
+    #[inline]
+    #[must_use]
+    pub fn relation(mut self, parent: usize, child: usize) -> Self {
+        self.relations.push((parent, child));
+        self
+    }
+
+    /// Adds every `(parent, child)` relation, see [`relation`](ForestBuilder::relation).
+    #[inline]
+    #[must_use]
+    pub fn relations<I>(mut self, relations: I) -> Self
+    where
+        I: IntoIterator<Item = (usize, usize)>,
+    {
+        self.relations.extend(relations);
+        self
+    }
+
+    /// Builds the forest, and returns it with the ids of its nodes: the `i`-th id identifies the node at position `i`.
+    ///
+    /// # Errors
+    /// Will return [`ForestError::CapacityExceeded`] if there are more than `Ix::MAX` nodes,
+    /// [`ForestError::InvalidId`] if a relation refers to a position without node
+    /// and [`ForestError::WouldCreateCycle`] if a node is given several parents or if relations form a cycle.
+    #[allow(clippy::type_complexity)]
+    pub fn try_build<O, Ix, C, B>(
+        self,
+    ) -> Result<
+        (
+            CompressedForest<V, O, Ix, C, B>,
+            Indices<CompressedForest<V, O, Ix, C, B>>,
+        ),
+        ForestError<O::Error>,
+    >
+    where
+        O: AssociativeOperation<V>,
+        Ix: IndexType,
+        C: Compression,
+        B: ForestObserver<Index<CompressedForest<V, O, Ix, C, B>>, V> + Default,
+    {
+        let mut parents = vec![None; self.values.len()];
+        for (parent, child) in self.relations {
+            if parent >= parents.len() || child >= parents.len() {
+                return Err(ForestError::InvalidId);
+            }
+            // the child already belongs to the tree of its first parent
+            if parents[child].replace(parent).is_some() {
+                return Err(ForestError::WouldCreateCycle);
+            }
+        }
+
+        let forest =
+            CompressedForest::from_parent_array(parents.into_iter().zip(self.values).collect())?;
+        let ids = forest.ids();
+        Ok((forest, ids))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::operation::*;
+    use crate::EvalLinkUpdate;
+
+    #[test]
+    fn add_forest() {
+        let (mut forest, ids): (CompressedForest<usize, CloneAdd>, _) =
+            ForestBuilder::with_capacity(4)
+                .values([2, 3, 4])
+                .value(5)
+                .relation(0, 1)
+                .relations([(2, 3), (3, 0)])
+                .try_build()
+                .unwrap();
+        let ids: Vec<_> = ids.collect();
+
+        assert_eq!(4, forest.len());
+        assert_eq!(14, forest.eval(ids[1]));
+        assert_eq!(ids[2], forest.find_root(ids[1]));
+        assert_eq!(1, forest.tree_count());
+    }
+
+    #[test]
+    fn invalid_relations() {
+        type Forest = CompressedForest<usize, CloneAdd>;
+        let builder = ForestBuilder::new().values([2, 3, 4]);
+        assert!(!builder.is_empty() && builder.len() == 3);
+
+        let invalid: Result<(Forest, _), _> = builder.clone().relation(0, 3).try_build();
+        assert!(matches!(invalid, Err(ForestError::InvalidId)));
+
+        let two_parents: Result<(Forest, _), _> =
+            builder.clone().relations([(0, 2), (1, 2)]).try_build();
+        assert!(matches!(two_parents, Err(ForestError::WouldCreateCycle)));
+
+        let cycle: Result<(Forest, _), _> = builder.relations([(0, 1), (1, 2), (2, 0)]).try_build();
+        assert!(matches!(cycle, Err(ForestError::WouldCreateCycle)));
+    }
+}
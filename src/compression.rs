@@ -0,0 +1,66 @@
+/// A path compression strategy of a [`CompressedForest`](crate::CompressedForest).
+///
+/// Every strategy shortens the path from a node to its root whenever this root is looked for, i.e. when evaluating,
+/// linking or updating. Shorter paths make later queries cheaper, but each relinked node costs an association.
+///
+/// This trait is sealed: the available strategies are [`FullCompression`], [`PathHalving`], [`PathSplitting`]
+/// and [`NoCompression`].
+pub trait Compression: sealed::Sealed {}
+
+/// Links every node of the path to the root, in two passes over the path.
+///
+/// This is the default strategy: queries on nodes that were already visited take constant time, which suits
+/// read-heavy workloads.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct FullCompression;
+
+/// Links every other node of the path to its grandparent, in a single pass over the path.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct PathHalving;
+
+/// Links every node of the path to its grandparent, in a single pass over the path.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct PathSplitting;
+
+/// Never modifies paths: queries walk the whole path from the node to its root.
+///
+/// Finding a root never associates values, which suits write-heavy workloads on shallow trees. Linked roots keep
+/// the node they were linked to as parent, so that nodes can be cut from their parent
+/// (see [`CompressedForest::try_cut`](crate::CompressedForest::try_cut)).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct NoCompression;
+
+pub(crate) mod sealed {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Kind {
+        Full,
+        Halving,
+        Splitting,
+        None,
+    }
+
+    pub trait Sealed {
+        const KIND: Kind;
+    }
+
+    impl Sealed for super::FullCompression {
+        const KIND: Kind = Kind::Full;
+    }
+
+    impl Sealed for super::PathHalving {
+        const KIND: Kind = Kind::Halving;
+    }
+
+    impl Sealed for super::PathSplitting {
+        const KIND: Kind = Kind::Splitting;
+    }
+
+    impl Sealed for super::NoCompression {
+        const KIND: Kind = Kind::None;
+    }
+}
+
+impl Compression for FullCompression {}
+impl Compression for PathHalving {}
+impl Compression for PathSplitting {}
+impl Compression for NoCompression {}
@@ -0,0 +1,529 @@
+use std::convert::Infallible;
+use std::fmt;
+use std::marker::PhantomData;
+use std::ptr;
+use std::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+use std::sync::{Mutex, PoisonError};
+
+use crate::index::Index;
+use crate::operation::{CommutativeOperation, DefaultOperation};
+use crate::{EvalLinkUpdate, ForestError, NewRoot};
+
+// Records are immutable once published: nodes are modified by swapping their record,
+// and replaced records are kept alive until the forest is dropped since other threads may still read them.
+struct Record<V> {
+    parent: Option<usize>,
+    value: V,
+    // next record in the stack of retired records
+    next: AtomicPtr<Record<V>>,
+}
+
+impl<V> Record<V> {
+    fn new(parent: Option<usize>, value: V) -> *mut Self {
+        Box::into_raw(Box::new(Self {
+            parent,
+            value,
+            next: AtomicPtr::new(ptr::null_mut()),
+        }))
+    }
+}
+
+/// An EVAL-LINK-UPDATE forest structure with path compression that can be shared across threads.
+///
+/// Every operation takes `&self`: nodes hold atomic pointers to their parent and value, paths are compressed with
+/// compare-and-swap operations and updates retry until they succeed. Evaluating, finding roots and updating are
+/// lock-free, while links are serialized so that cycles can be detected.
+///
+/// The number of nodes is bounded by the capacity given at creation. Values replaced by compressions and updates
+/// are only freed when the forest is dropped, so memory usage grows with the number of operations.
+///
+/// `V` is the value type associated to nodes in the forest and `O` is the associative operation applied when evaluating.
+/// It must be a [`CommutativeOperation`], so that the forest is free to reorder its operands when threads compress and
+/// link concurrently.
+pub struct ConcurrentForest<V, O = DefaultOperation>
+where
+    O: 'static,
+{
+    nodes: Box<[AtomicPtr<Record<V>>]>,
+    // number of reserved slots, may exceed the capacity
+    reserved: AtomicUsize,
+    retired: AtomicPtr<Record<V>>,
+    link_lock: Mutex<()>,
+    _op: PhantomData<O>,
+}
+
+// SAFETY: values are moved in and copied out of the forest from any thread.
+unsafe impl<V, O> Send for ConcurrentForest<V, O>
+where
+    V: Send,
+    O: Send,
+{
+}
+
+// SAFETY: values are shared and copied out of the forest from any thread.
+unsafe impl<V, O> Sync for ConcurrentForest<V, O>
+where
+    V: Send + Sync,
+    O: Sync,
+{
+}
+
+impl<V, O> fmt::Debug for ConcurrentForest<V, O>
+where
+    O: 'static,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConcurrentForest")
+            .field("capacity", &self.nodes.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<V, O> Drop for ConcurrentForest<V, O>
+where
+    O: 'static,
+{
+    fn drop(&mut self) {
+        for node in self.nodes.iter_mut() {
+            let record = *node.get_mut();
+            if !record.is_null() {
+                // SAFETY: published records were allocated by `Record::new` and are owned by the forest
+                drop(unsafe { Box::from_raw(record) });
+            }
+        }
+        let mut record = *self.retired.get_mut();
+        while !record.is_null() {
+            // SAFETY: retired records were allocated by `Record::new` and are no longer referenced by any node
+            let mut retired = unsafe { Box::from_raw(record) };
+            record = *retired.next.get_mut();
+        }
+    }
+}
+
+impl<V, O> ConcurrentForest<V, O>
+where
+    V: Copy,
+    O: CommutativeOperation<V>,
+{
+    /// Creates a new empty forest that can hold up to `capacity` nodes.
+    #[must_use]
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            nodes: (0..capacity)
+                .map(|_| AtomicPtr::new(ptr::null_mut()))
+                .collect(),
+            reserved: AtomicUsize::new(0),
+            retired: AtomicPtr::new(ptr::null_mut()),
+            link_lock: Mutex::new(()),
+            _op: PhantomData,
+        }
+    }
+
+    /// Returns the maximum number of nodes the forest can hold.
+    #[inline]
+    #[must_use]
+    pub fn capacity(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Creates a new tree root in the forest with the given value.
+    ///
+    /// # Errors
+    /// Gives the value back if the forest is full.
+    pub fn try_new_root(&self, value: V) -> Result<Index<Self>, V> {
+        let key = self.reserved.fetch_add(1, Ordering::Relaxed);
+        if key >= self.capacity() {
+            return Err(value);
+        }
+        self.nodes[key].store(Record::new(None, value), Ordering::Release);
+        Ok(Index::new(key))
+    }
+
+    /// Infallible version of [`try_new_root`](ConcurrentForest::try_new_root).
+    ///
+    /// # Panics
+    /// Panics if the forest is full.
+    #[inline]
+    #[must_use]
+    pub fn new_root(&self, value: V) -> Index<Self> {
+        match self.try_new_root(value) {
+            Ok(id) => id,
+            Err(_) => panic!("the forest is full"),
+        }
+    }
+
+    /// Finds the root of the tree that contains the node identified by `id`.
+    ///
+    /// # Errors
+    /// Will return [`ForestError::InvalidId`] if the id does not belong to the forest
+    /// and [`ForestError::Operation`] if [`O::associate`](crate::AssociativeOperation::associate) fails.
+    pub fn try_find_root(&self, id: Index<Self>) -> Result<Index<Self>, ForestError<O::Error>> {
+        let key = self.check(id)?;
+        Ok(Index::new(self.find_root_key(key)?))
+    }
+
+    /// Evaluates the node identified by `id`.
+    ///
+    /// # Errors
+    /// Will return [`ForestError::InvalidId`] if the id does not belong to the forest
+    /// and [`ForestError::Operation`] if [`O::associate`](crate::AssociativeOperation::associate) fails.
+    pub fn try_eval(&self, id: Index<Self>) -> Result<V, ForestError<O::Error>> {
+        let key = self.check(id)?;
+        self.find_root_key(key)?;
+
+        // records are read from the node up to its root: a concurrent link or update of the root
+        // only affects the records read last, so the result is consistent
+        let mut record = self.record(key);
+        let mut value = record.value;
+        while let Some(parent_key) = record.parent {
+            record = self.record(parent_key);
+            value = O::associate(&record.value, &value).map_err(ForestError::Operation)?;
+        }
+        Ok(value)
+    }
+
+    /// Links the root of the tree that contains the node identified by `id_b` to the node identified by `id_a`.
+    ///
+    /// # Errors
+    /// Will return [`ForestError::InvalidId`] if an id does not belong to the forest,
+    /// [`ForestError::WouldCreateCycle`] if both nodes belong to the same tree
+    /// and [`ForestError::Operation`] if [`O::associate`](crate::AssociativeOperation::associate) fails.
+    pub fn try_link(
+        &self,
+        id_a: Index<Self>,
+        id_b: Index<Self>,
+    ) -> Result<(), ForestError<O::Error>> {
+        let id_a = self.check(id_a)?;
+        let id_b = self.check(id_b)?;
+
+        // roots only stop being roots when linked, so they cannot change while the lock is held
+        let _guard = self
+            .link_lock
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let root_a_key = self.find_root_key(id_a)?;
+        let root_b_key = self.find_root_key(id_b)?;
+        if root_a_key == root_b_key {
+            return Err(ForestError::WouldCreateCycle);
+        }
+
+        // value of "node a" relative to its root
+        let relative_a = if root_a_key == id_a {
+            None
+        } else {
+            let mut record = self.record(id_a);
+            let mut value = record.value;
+            while let Some(parent_key) = record.parent.filter(|&key| key != root_a_key) {
+                record = self.record(parent_key);
+                value = O::associate(&record.value, &value).map_err(ForestError::Operation)?;
+            }
+            Some(value)
+        };
+
+        // the root of "tree b" may be updated concurrently
+        loop {
+            let current = self.nodes[root_b_key].load(Ordering::Acquire);
+            // SAFETY: records are only freed when the forest is dropped
+            let value_b = unsafe { (*current).value };
+            let new_value = match &relative_a {
+                Some(value_a) => O::associate(value_a, &value_b).map_err(ForestError::Operation)?,
+                None => value_b,
+            };
+            if self.replace(root_b_key, current, Some(root_a_key), new_value) {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Replaces the value of the root of the tree that contains the node identified by `id`.
+    ///
+    /// # Errors
+    /// Will return [`ForestError::InvalidId`] if the id does not belong to the forest
+    /// and [`ForestError::Operation`] if [`O::associate`](crate::AssociativeOperation::associate) fails.
+    pub fn try_update(&self, id: Index<Self>, value: V) -> Result<(), ForestError<O::Error>> {
+        let key = self.check(id)?;
+        loop {
+            let root_key = self.find_root_key(key)?;
+            let current = self.nodes[root_key].load(Ordering::Acquire);
+            // SAFETY: records are only freed when the forest is dropped
+            if unsafe { (*current).parent }.is_none()
+                && self.replace(root_key, current, None, value)
+            {
+                return Ok(());
+            }
+            // the root has been linked or updated concurrently
+        }
+    }
+
+    /// Checks if two nodes belong to the same tree.
+    ///
+    /// # Errors
+    /// Will return [`ForestError::InvalidId`] if an id does not belong to the forest
+    /// and [`ForestError::Operation`] if [`O::associate`](crate::AssociativeOperation::associate) fails.
+    pub fn try_connected(
+        &self,
+        id_a: Index<Self>,
+        id_b: Index<Self>,
+    ) -> Result<bool, ForestError<O::Error>> {
+        Ok(self.try_find_root(id_a)? == self.try_find_root(id_b)?)
+    }
+
+    fn check(&self, id: Index<Self>) -> Result<usize, ForestError<O::Error>> {
+        let key: usize = id.into();
+        match self.nodes.get(key) {
+            Some(node) if !node.load(Ordering::Acquire).is_null() => Ok(key),
+            _ => Err(ForestError::InvalidId),
+        }
+    }
+
+    fn record(&self, key: usize) -> &Record<V> {
+        // SAFETY: records of valid nodes are never null and only freed when the forest is dropped
+        unsafe { &*self.nodes[key].load(Ordering::Acquire) }
+    }
+
+    // Publishes a new record for the node if its record is still `current`.
+    fn replace(
+        &self,
+        key: usize,
+        current: *mut Record<V>,
+        parent: Option<usize>,
+        value: V,
+    ) -> bool {
+        let new = Record::new(parent, value);
+        match self.nodes[key].compare_exchange(current, new, Ordering::AcqRel, Ordering::Acquire) {
+            Ok(_) => {
+                self.retire(current);
+                true
+            }
+            Err(_) => {
+                // SAFETY: the new record has not been published
+                drop(unsafe { Box::from_raw(new) });
+                false
+            }
+        }
+    }
+
+    fn retire(&self, record: *mut Record<V>) {
+        let mut head = self.retired.load(Ordering::Relaxed);
+        loop {
+            // SAFETY: the record is no longer published and only this thread may retire it
+            unsafe { (*record).next.store(head, Ordering::Relaxed) };
+            match self.retired.compare_exchange_weak(
+                head,
+                record,
+                Ordering::Release,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return,
+                Err(current) => head = current,
+            }
+        }
+    }
+
+    fn find_root_key(&self, key: usize) -> Result<usize, ForestError<O::Error>> {
+        let mut path = vec![key];
+        let mut record = self.record(key);
+        while let Some(parent_key) = record.parent {
+            path.push(parent_key);
+            record = self.record(parent_key);
+        }
+
+        // compress from the top so that every node only needs to skip its parent
+        for &key in path.iter().rev() {
+            loop {
+                let current = self.nodes[key].load(Ordering::Acquire);
+                // SAFETY: records are only freed when the forest is dropped
+                let record = unsafe { &*current };
+                let Some(parent_key) = record.parent else {
+                    break;
+                };
+                let parent = self.record(parent_key);
+                let Some(grand_parent_key) = parent.parent else {
+                    break;
+                };
+                let merged_values =
+                    O::associate(&parent.value, &record.value).map_err(ForestError::Operation)?;
+                self.replace(key, current, Some(grand_parent_key), merged_values);
+            }
+        }
+
+        let mut root_key = key;
+        while let Some(parent_key) = self.record(root_key).parent {
+            root_key = parent_key;
+        }
+        Ok(root_key)
+    }
+}
+
+impl<V, O> ConcurrentForest<V, O>
+where
+    V: Copy,
+    O: CommutativeOperation<V, Error = Infallible>,
+{
+    /// Infallible version of [`try_find_root`](ConcurrentForest::try_find_root).
+    ///
+    /// # Panics
+    /// Panics if the id does not belong to the forest.
+    #[inline]
+    pub fn find_root(&self, id: Index<Self>) -> Index<Self> {
+        self.try_find_root(id).unwrap()
+    }
+
+    /// Infallible version of [`try_eval`](ConcurrentForest::try_eval).
+    ///
+    /// # Panics
+    /// Panics if the id does not belong to the forest.
+    #[inline]
+    pub fn eval(&self, id: Index<Self>) -> V {
+        self.try_eval(id).unwrap()
+    }
+
+    /// Infallible version of [`try_link`](ConcurrentForest::try_link).
+    ///
+    /// # Panics
+    /// Panics if an id does not belong to the forest or if both nodes belong to the same tree.
+    #[inline]
+    pub fn link(&self, id_a: Index<Self>, id_b: Index<Self>) {
+        self.try_link(id_a, id_b).unwrap();
+    }
+
+    /// Infallible version of [`try_update`](ConcurrentForest::try_update).
+    ///
+    /// # Panics
+    /// Panics if the id does not belong to the forest.
+    #[inline]
+    pub fn update(&self, id: Index<Self>, value: V) {
+        self.try_update(id, value).unwrap();
+    }
+
+    /// Infallible version of [`try_connected`](ConcurrentForest::try_connected).
+    ///
+    /// # Panics
+    /// Panics if an id does not belong to the forest.
+    #[inline]
+    pub fn connected(&self, id_a: Index<Self>, id_b: Index<Self>) -> bool {
+        self.try_connected(id_a, id_b).unwrap()
+    }
+}
+
+impl<V, O> EvalLinkUpdate for ConcurrentForest<V, O>
+where
+    V: Copy,
+    O: 'static + CommutativeOperation<V>,
+{
+    type Id = Index<Self>;
+    type Value = V;
+    type Operation = O;
+    type Error = O::Error;
+
+    #[inline]
+    fn node_count(&self) -> Option<usize> {
+        Some(self.reserved.load(Ordering::Relaxed).min(self.capacity()))
+    }
+
+    fn try_find_root(&mut self, id: Index<Self>) -> Result<Index<Self>, ForestError<O::Error>> {
+        Self::try_find_root(self, id)
+    }
+
+    fn try_link(
+        &mut self,
+        id_a: Index<Self>,
+        id_b: Index<Self>,
+    ) -> Result<(), ForestError<O::Error>> {
+        Self::try_link(self, id_a, id_b)
+    }
+
+    fn try_update(&mut self, id: Index<Self>, value: V) -> Result<(), ForestError<O::Error>> {
+        Self::try_update(self, id, value)
+    }
+
+    fn try_eval(&mut self, id: Index<Self>) -> Result<V, ForestError<O::Error>> {
+        Self::try_eval(self, id)
+    }
+}
+
+impl<V, O> NewRoot for ConcurrentForest<V, O>
+where
+    V: Copy,
+    O: 'static + CommutativeOperation<V>,
+{
+    /// # Panics
+    /// Panics if the forest is full.
+    fn new_root(&mut self, value: V) -> Index<Self> {
+        Self::new_root(self, value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::operation::*;
+
+    #[test]
+    fn add_forest() {
+        let forest: ConcurrentForest<usize, CloneAdd> = ConcurrentForest::with_capacity(4);
+        let v0 = forest.new_root(2);
+        let v1 = forest.new_root(3);
+        let v2 = forest.new_root(4);
+        let v3 = forest.new_root(5);
+
+        forest.link(v0, v1);
+        forest.link(v2, v3);
+
+        assert_eq!(5, forest.eval(v1));
+        assert_eq!(9, forest.eval(v3));
+
+        forest.link(v3, v0);
+
+        assert_eq!(11, forest.eval(v0));
+        assert_eq!(14, forest.eval(v1));
+        assert_eq!(v2, forest.find_root(v1));
+
+        forest.update(v1, 1);
+
+        assert_eq!(11, forest.eval(v1));
+        assert_eq!(Err(ForestError::WouldCreateCycle), forest.try_link(v1, v2));
+        assert_eq!(Err(7), forest.try_new_root(7));
+    }
+
+    #[test]
+    fn shared_chain() {
+        let n = 1024;
+        let threads = 4;
+        let forest: ConcurrentForest<usize, CloneAdd> = ConcurrentForest::with_capacity(n);
+        let ids: Vec<_> = (0..n).map(|_| forest.new_root(1)).collect();
+
+        std::thread::scope(|scope| {
+            for thread in 0..threads {
+                let forest = &forest;
+                let ids = &ids;
+                scope.spawn(move || {
+                    for i in (1..n).filter(|i| i % threads == thread) {
+                        forest.link(ids[i - 1], ids[i]);
+                        // other links only grow the path to the root
+                        assert!(forest.eval(ids[i]) >= 2);
+                        assert!(forest.eval(ids[i - 1]) <= i);
+                    }
+                });
+            }
+        });
+
+        for (i, &id) in ids.iter().enumerate() {
+            assert_eq!(i + 1, forest.eval(id));
+            assert_eq!(ids[0], forest.find_root(id));
+        }
+
+        std::thread::scope(|scope| {
+            for thread in 0..threads {
+                let forest = &forest;
+                let ids = &ids;
+                scope.spawn(move || {
+                    for i in (0..n).filter(|i| i % threads == thread) {
+                        forest.update(ids[i], 2);
+                        assert_eq!(i + 2, forest.eval(ids[i]));
+                    }
+                });
+            }
+        });
+    }
+}
@@ -0,0 +1,214 @@
+use crate::offline::Op;
+use crate::operation::AssociativeOperation;
+use crate::{CompressedForest, ForestError, NaiveForest, NewRoot, OperationError};
+
+/// The outcome of an operation: the answer of an [`Op::Eval`], `None` for other successful operations.
+pub type Outcome<V, E> = Result<Option<V>, ForestError<E>>;
+
+/// An operation on which a forest and the naive model disagree, see [`check`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence<V, E> {
+    /// A minimized sequence of operations whose last operation is the first one to diverge.
+    pub trace: Vec<Op<V>>,
+    /// The outcome of the last operation of the trace on the naive model.
+    pub expected: Outcome<V, E>,
+    /// The outcome of the last operation of the trace on the checked forest.
+    pub actual: Outcome<V, E>,
+}
+
+/// Runs a sequence of operations on a [`CompressedForest`] and on a [`NaiveForest`], which never compresses paths,
+/// and reports the first operation on which they disagree.
+///
+/// Both forests use the associative operation `O`: a divergence means that `O` is not associative on the values of the
+/// sequence, or that path compression is wrong. Operations that fail are not fatal, their errors are compared as well.
+///
+/// # Errors
+/// Will return the [`Divergence`] of the first operation whose outcomes differ, with a trace minimized by removing
+/// every operation that is not needed to diverge.
+pub fn check<V, O>(operations: &[Op<V>]) -> Result<(), Divergence<V, O::Error>>
+where
+    V: Clone + PartialEq,
+    O: 'static + AssociativeOperation<V>,
+    O::Error: PartialEq,
+{
+    check_forest::<CompressedForest<V, O>>(operations)
+}
+
+/// Same as [`check`], with any forest `F` in place of [`CompressedForest`].
+///
+/// # Errors
+/// See [`check`].
+pub fn check_forest<F>(
+    operations: &[Op<F::Value>],
+) -> Result<(), Divergence<F::Value, OperationError<F>>>
+where
+    F: NewRoot + Default,
+    F::Operation: 'static + AssociativeOperation<F::Value, Error = OperationError<F>>,
+    F::Id: Clone,
+    F::Value: Clone + PartialEq,
+    OperationError<F>: PartialEq,
+{
+    let Some(mut divergence) = first_divergence::<F>(operations) else {
+        return Ok(());
+    };
+
+    // removes every operation that is not needed, from the last one
+    let mut key = divergence.trace.len();
+    while key > 0 {
+        key -= 1;
+        if let Some(smaller) =
+            without(&divergence.trace, key).and_then(|trace| first_divergence::<F>(&trace))
+        {
+            key = key.min(smaller.trace.len());
+            divergence = smaller;
+        }
+    }
+    Err(divergence)
+}
+
+// Removes the operation of the given key, renumbering nodes if it creates a node that is never referred to.
+fn without<V: Clone>(operations: &[Op<V>], key: usize) -> Option<Vec<Op<V>>> {
+    let node = match operations[key] {
+        Op::NewRoot(_) => Some(
+            operations[..key]
+                .iter()
+                .filter(|operation| matches!(operation, Op::NewRoot(_)))
+                .count(),
+        ),
+        _ => None,
+    };
+    let renumber = |n: usize| match node {
+        Some(node) if n == node => None,
+        Some(node) if n > node => Some(n - 1),
+        _ => Some(n),
+    };
+
+    operations
+        .iter()
+        .enumerate()
+        .filter(|&(k, _)| k != key)
+        .map(|(_, operation)| {
+            Some(match operation {
+                Op::NewRoot(value) => Op::NewRoot(value.clone()),
+                Op::Link(a, b) => Op::Link(renumber(*a)?, renumber(*b)?),
+                Op::Update(a, value) => Op::Update(renumber(*a)?, value.clone()),
+                Op::Eval(a) => Op::Eval(renumber(*a)?),
+            })
+        })
+        .collect()
+}
+
+// Runs the operations until the first one whose outcomes differ.
+fn first_divergence<F>(
+    operations: &[Op<F::Value>],
+) -> Option<Divergence<F::Value, OperationError<F>>>
+where
+    F: NewRoot + Default,
+    F::Operation: 'static + AssociativeOperation<F::Value, Error = OperationError<F>>,
+    F::Id: Clone,
+    F::Value: Clone + PartialEq,
+    OperationError<F>: PartialEq,
+{
+    let mut model: NaiveForest<F::Value, F::Operation> = NaiveForest::new();
+    let mut forest = F::default();
+    let (mut model_ids, mut forest_ids) = (vec![], vec![]);
+
+    operations.iter().enumerate().find_map(|(key, operation)| {
+        let expected = step(&mut model, &mut model_ids, operation);
+        let actual = step(&mut forest, &mut forest_ids, operation);
+        (expected != actual).then(|| Divergence {
+            trace: operations[..=key].to_vec(),
+            expected,
+            actual,
+        })
+    })
+}
+
+fn step<F>(
+    forest: &mut F,
+    ids: &mut Vec<F::Id>,
+    operation: &Op<F::Value>,
+) -> Outcome<F::Value, OperationError<F>>
+where
+    F: NewRoot,
+    F::Id: Clone,
+    F::Value: Clone,
+{
+    let id = |ids: &[F::Id], key: usize| ids.get(key).cloned().ok_or(ForestError::InvalidId);
+    match operation {
+        Op::NewRoot(value) => {
+            ids.push(forest.new_root(value.clone()));
+            Ok(None)
+        }
+        Op::Link(a, b) => forest.try_link(id(ids, *a)?, id(ids, *b)?).map(|()| None),
+        Op::Update(a, value) => forest
+            .try_update(id(ids, *a)?, value.clone())
+            .map(|()| None),
+        Op::Eval(a) => forest.try_eval(id(ids, *a)?).map(Some),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::operation::*;
+    use crate::PathHalving;
+
+    #[test]
+    fn consistent() {
+        let operations = [
+            Op::NewRoot(2),
+            Op::NewRoot(3),
+            Op::NewRoot(4),
+            Op::Link(0, 1),
+            Op::Link(1, 2),
+            Op::Eval(2),
+            Op::Link(2, 0),
+            Op::Update(2, 1),
+            Op::Eval(2),
+            Op::Eval(3),
+        ];
+
+        assert_eq!(Ok(()), check::<usize, CloneAdd>(&operations));
+        assert_eq!(
+            Ok(()),
+            check_forest::<CompressedForest<usize, CloneAdd, usize, PathHalving>>(&operations)
+        );
+    }
+
+    crate::define_operation!(Sub, i64, |lhs, rhs| lhs - rhs);
+
+    #[test]
+    fn divergence() {
+        let operations = [
+            Op::NewRoot(10),
+            Op::NewRoot(1),
+            Op::NewRoot(2),
+            Op::Eval(0),
+            Op::NewRoot(3),
+            Op::Link(1, 2),
+            Op::Eval(1),
+            Op::Link(2, 3),
+            Op::Eval(2),
+            Op::Eval(3),
+            Op::Eval(0),
+        ];
+
+        assert_eq!(
+            Err(Divergence {
+                trace: vec![
+                    Op::NewRoot(1),
+                    Op::NewRoot(2),
+                    Op::NewRoot(3),
+                    Op::Link(0, 1),
+                    Op::Link(1, 2),
+                    Op::Eval(2),
+                ],
+                expected: Ok(Some(-4)),
+                actual: Ok(Some(2)),
+            }),
+            check::<i64, Sub>(&operations)
+        );
+        assert_eq!(Ok(()), check_forest::<NaiveForest<i64, Sub>>(&operations));
+    }
+}
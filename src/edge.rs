@@ -0,0 +1,216 @@
+use crate::index::Index;
+use crate::operation::{AssociativeOperation, DefaultOperation, Lift};
+use crate::{CompressedForest, EvalLinkUpdate, ForestError, NewRoot};
+
+/// An EVAL-LINK-UPDATE forest structure with path compression whose values are carried by edges instead of nodes.
+///
+/// Each link is given the weight of the edge it creates, evaluating a node computes the product of the weights
+/// of the edges on the path from its root (i.e `weight(r, n1)` ⊕ ... ⊕ `weight(nk, n)`). Internally, the weight
+/// of an edge is stored as the value of its child node and roots hold no value.
+///
+/// `V` is the weight type associated to edges in the forest and `O` is the associative operation applied when evaluating.
+#[derive(Debug, Clone)]
+pub struct EdgeForest<V, O = DefaultOperation>
+where
+    O: 'static,
+{
+    forest: CompressedForest<Option<V>, Lift<O>>,
+}
+
+impl<V, O> Default for EdgeForest<V, O>
+where
+    V: Clone,
+    O: AssociativeOperation<V>,
+{
+    #[inline]
+    fn default() -> Self {
+        Self {
+            forest: CompressedForest::new(),
+        }
+    }
+}
+
+impl<V, O> EdgeForest<V, O>
+where
+    V: Clone,
+    O: AssociativeOperation<V>,
+{
+    /// Creates a new empty forest.
+    #[inline]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new empty forest with a given capacity.
+    #[inline]
+    #[must_use]
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            forest: CompressedForest::with_capacity(capacity),
+        }
+    }
+
+    /// Reserve enough space for a given number of nodes.
+    #[inline]
+    pub fn reserve(&mut self, additional: usize) {
+        self.forest.reserve(additional);
+    }
+
+    /// Returns the number of nodes in the forest.
+    #[inline]
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.forest.len()
+    }
+
+    /// Checks whether the forest has no nodes.
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.forest.is_empty()
+    }
+
+    /// Creates a new tree root in the forest.
+    #[inline]
+    #[must_use]
+    pub fn new_node(&mut self) -> Index<Self> {
+        Index::new(self.forest.new_root(None).into())
+    }
+
+    /// Finds the root of the tree that contains the node identified by `id`.
+    ///
+    /// # Errors
+    /// Will return [`ForestError::InvalidId`] if the id does not belong to the forest
+    /// and [`ForestError::Operation`] if [`O::associate`](AssociativeOperation::associate) fails.
+    pub fn try_find_root(&mut self, id: Index<Self>) -> Result<Index<Self>, ForestError<O::Error>> {
+        let root = self.forest.try_find_root(Self::inner(id))?;
+        Ok(Index::new(root.into()))
+    }
+
+    /// Links the root of the tree that contains the node identified by `id_b` to the node identified by `id_a`
+    /// with an edge of the given weight.
+    ///
+    /// # Errors
+    /// Will return [`ForestError::InvalidId`] if an id does not belong to the forest,
+    /// [`ForestError::WouldCreateCycle`] if both nodes already belong to the same tree
+    /// and [`ForestError::Operation`] if [`O::associate`](AssociativeOperation::associate) fails.
+    pub fn try_link_weighted(
+        &mut self,
+        id_a: Index<Self>,
+        id_b: Index<Self>,
+        weight: V,
+    ) -> Result<(), ForestError<O::Error>> {
+        let root_a = self.forest.try_find_root(Self::inner(id_a))?;
+        let root_b = self.forest.try_find_root(Self::inner(id_b))?;
+        if root_a == root_b {
+            return Err(ForestError::WouldCreateCycle);
+        }
+        // "root b" becomes the child of the new edge, its value is restored if the link fails
+        let value = self.forest.stored_value_mut(root_b).replace(weight);
+        let linked = self.forest.try_link(Self::inner(id_a), root_b);
+        if linked.is_err() {
+            *self.forest.stored_value_mut(root_b) = value;
+        }
+        linked
+    }
+
+    /// Computes the product of the weights of the edges on the path from the root of the tree to the node
+    /// identified by `id`, or `None` if the node is a root.
+    ///
+    /// # Errors
+    /// Will return [`ForestError::InvalidId`] if the id does not belong to the forest
+    /// and [`ForestError::Operation`] if [`O::associate`](AssociativeOperation::associate) fails.
+    pub fn try_eval(&mut self, id: Index<Self>) -> Result<Option<V>, ForestError<O::Error>> {
+        self.forest.try_eval(Self::inner(id))
+    }
+
+    #[inline]
+    fn inner(id: Index<Self>) -> Index<CompressedForest<Option<V>, Lift<O>>> {
+        Index::new(id.into())
+    }
+}
+
+impl<V, O> EdgeForest<V, O>
+where
+    V: Clone,
+    O: AssociativeOperation<V, Error = std::convert::Infallible>,
+{
+    /// Infallible version of [`try_find_root`](EdgeForest::try_find_root).
+    ///
+    /// # Panics
+    /// Panics if the id does not belong to the forest.
+    #[inline]
+    pub fn find_root(&mut self, id: Index<Self>) -> Index<Self> {
+        self.try_find_root(id).unwrap()
+    }
+
+    /// Infallible version of [`try_link_weighted`](EdgeForest::try_link_weighted).
+    ///
+    /// # Panics
+    /// Panics if an id does not belong to the forest or if both nodes already belong to the same tree.
+    #[inline]
+    pub fn link_weighted(&mut self, id_a: Index<Self>, id_b: Index<Self>, weight: V) {
+        self.try_link_weighted(id_a, id_b, weight).unwrap();
+    }
+
+    /// Infallible version of [`try_eval`](EdgeForest::try_eval).
+    ///
+    /// # Panics
+    /// Panics if the id does not belong to the forest.
+    #[inline]
+    pub fn eval(&mut self, id: Index<Self>) -> Option<V> {
+        self.try_eval(id).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::operation::*;
+
+    #[test]
+    fn offsets() {
+        let mut forest: EdgeForest<i64, CloneAdd> = EdgeForest::with_capacity(4);
+        let v0 = forest.new_node();
+        let v1 = forest.new_node();
+        let v2 = forest.new_node();
+        let v3 = forest.new_node();
+
+        forest.link_weighted(v0, v1, 3);
+        forest.link_weighted(v2, v3, -2);
+
+        assert_eq!(None, forest.eval(v0));
+        assert_eq!(Some(3), forest.eval(v1));
+        assert_eq!(Some(-2), forest.eval(v3));
+
+        // the weight goes to the edge between "v1" and the root of "v3"
+        forest.link_weighted(v1, v3, 10);
+
+        assert_eq!(Some(13), forest.eval(v2));
+        assert_eq!(Some(11), forest.eval(v3));
+        assert_eq!(v0, forest.find_root(v3));
+        assert_eq!(
+            Err(ForestError::WouldCreateCycle),
+            forest.try_link_weighted(v3, v1, 1)
+        );
+        assert_eq!(Some(11), forest.eval(v3));
+        assert_eq!(4, forest.len());
+    }
+
+    #[test]
+    fn failed_link() {
+        let mut forest: EdgeForest<u8, CheckedAdd> = EdgeForest::new();
+        let ids: Vec<_> = (0..4).map(|_| forest.new_node()).collect();
+        forest.try_link_weighted(ids[0], ids[1], 200).unwrap();
+        forest.try_link_weighted(ids[2], ids[3], 100).unwrap();
+
+        assert_eq!(
+            Err(ForestError::Operation(Overflow)),
+            forest.try_link_weighted(ids[1], ids[2], 60)
+        );
+        assert_eq!(Ok(None), forest.try_eval(ids[2]));
+        assert_eq!(Ok(Some(100)), forest.try_eval(ids[3]));
+        assert_eq!(Ok(ids[2]), forest.try_find_root(ids[3]));
+    }
+}
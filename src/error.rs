@@ -0,0 +1,39 @@
+use std::fmt;
+
+/// The crate-level error type, every fallible operation of the crate fails with a [`ForestError`].
+pub type EluError<E> = ForestError<E>;
+
+/// The error type returned by the fallible operations of EVAL-LINK-UPDATE structures.
+///
+/// `E` is the error type of the underlying [`AssociativeOperation`](crate::AssociativeOperation).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ForestError<E> {
+    /// The given id does not identify a node of the forest.
+    InvalidId,
+    /// Linking the given nodes would create a cycle, i.e. they already belong to the same tree.
+    WouldCreateCycle,
+    /// The forest cannot hold more nodes, its number of nodes is bounded by its [`IndexType`](crate::IndexType).
+    CapacityExceeded,
+    /// The associative operation failed.
+    Operation(E),
+}
+
+impl<E: fmt::Display> fmt::Display for ForestError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidId => write!(f, "id does not belong to this forest"),
+            Self::WouldCreateCycle => write!(f, "nodes already belong to the same tree"),
+            Self::CapacityExceeded => write!(f, "forest cannot hold more nodes"),
+            Self::Operation(e) => write!(f, "associative operation failed: {e}"),
+        }
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for ForestError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Operation(e) => Some(e),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,495 @@
+use std::convert::Infallible;
+use std::marker::PhantomData;
+
+use crate::index::Index;
+use crate::operation::{AssociativeOperation, DefaultOperation};
+use crate::ForestError;
+
+#[derive(Debug, Clone)]
+struct Token<V> {
+    parent: Option<usize>,
+    left: Option<usize>,
+    right: Option<usize>,
+    // only opening tokens hold a value
+    value: Option<V>,
+    // product of the values of the splay subtree, in order
+    aggregate: Option<V>,
+}
+
+/// An Euler-tour forest structure that maintains subtree aggregates.
+///
+/// Each tree is stored as its Euler tour (each node is entered and then exited once its children have been visited),
+/// kept in a splay tree that maintains the product of the values of the entered nodes.
+/// The aggregate of a subtree is thus the product of its values in preorder, children being ordered by link time.
+/// Linking, cutting and querying run in amortized `O(log n)` time.
+///
+/// Aggregates are recomputed while splay trees are restructured, so only infallible operations are supported.
+///
+/// `V` is the value type associated to nodes in the forest and `O` is the associative operation applied when aggregating.
+#[derive(Debug, Clone)]
+pub struct EulerTourForest<V, O = DefaultOperation>
+where
+    O: 'static,
+{
+    // node `i` is entered by token `2 * i` and exited by token `2 * i + 1`
+    tokens: Vec<Token<V>>,
+    _op: PhantomData<O>,
+}
+
+impl<V, O> Default for EulerTourForest<V, O>
+where
+    V: Clone,
+    O: AssociativeOperation<V, Error = Infallible>,
+{
+    #[inline]
+    fn default() -> Self {
+        Self {
+            tokens: vec![],
+            _op: PhantomData,
+        }
+    }
+}
+
+impl<V, O> EulerTourForest<V, O>
+where
+    V: Clone,
+    O: AssociativeOperation<V, Error = Infallible>,
+{
+    /// Creates a new empty forest.
+    #[inline]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new empty forest with a given capacity.
+    #[inline]
+    #[must_use]
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            tokens: Vec::with_capacity(2 * capacity),
+            _op: PhantomData,
+        }
+    }
+
+    /// Reserve enough space for a given number of nodes.
+    #[inline]
+    pub fn reserve(&mut self, additional: usize) {
+        self.tokens.reserve(2 * additional);
+    }
+
+    /// Creates a new tree root in the forest with the given value.
+    #[must_use]
+    pub fn new_root(&mut self, value: V) -> Index<Self> {
+        let index = self.tokens.len() / 2;
+        self.tokens.push(Token {
+            parent: None,
+            left: None,
+            right: Some(2 * index + 1),
+            aggregate: Some(value.clone()),
+            value: Some(value),
+        });
+        self.tokens.push(Token {
+            parent: Some(2 * index),
+            left: None,
+            right: None,
+            value: None,
+            aggregate: None,
+        });
+        Index::new(index)
+    }
+
+    /// Finds the root of the tree that contains the node identified by `id`.
+    ///
+    /// # Errors
+    /// Will return [`ForestError::InvalidId`] if the id does not belong to the forest.
+    pub fn try_find_root(
+        &mut self,
+        id: Index<Self>,
+    ) -> Result<Index<Self>, ForestError<Infallible>> {
+        let key = self.check(id)?;
+        Ok(Index::new(self.first_token(2 * key) / 2))
+    }
+
+    /// Infallible version of [`try_find_root`](EulerTourForest::try_find_root).
+    ///
+    /// # Panics
+    /// Panics if the id does not belong to the forest.
+    #[inline]
+    pub fn find_root(&mut self, id: Index<Self>) -> Index<Self> {
+        self.try_find_root(id).unwrap()
+    }
+
+    /// Checks whether the nodes identified by `id_a` and `id_b` belong to the same tree.
+    ///
+    /// # Errors
+    /// Will return [`ForestError::InvalidId`] if an id does not belong to the forest.
+    pub fn try_connected(
+        &mut self,
+        id_a: Index<Self>,
+        id_b: Index<Self>,
+    ) -> Result<bool, ForestError<Infallible>> {
+        Ok(self.try_find_root(id_a)? == self.try_find_root(id_b)?)
+    }
+
+    /// Infallible version of [`try_connected`](EulerTourForest::try_connected).
+    ///
+    /// # Panics
+    /// Panics if an id does not belong to the forest.
+    #[inline]
+    pub fn connected(&mut self, id_a: Index<Self>, id_b: Index<Self>) -> bool {
+        self.try_connected(id_a, id_b).unwrap()
+    }
+
+    /// Links the root of the tree that contains the node identified by `id_b` to the node identified by `id_a`,
+    /// as its last child.
+    ///
+    /// # Errors
+    /// Will return [`ForestError::InvalidId`] if an id does not belong to the forest
+    /// and [`ForestError::WouldCreateCycle`] if both nodes already belong to the same tree.
+    pub fn try_link(
+        &mut self,
+        id_a: Index<Self>,
+        id_b: Index<Self>,
+    ) -> Result<(), ForestError<Infallible>> {
+        let id_a = self.check(id_a)?;
+        let id_b = self.check(id_b)?;
+
+        let first_b = self.first_token(2 * id_b);
+        if self.first_token(2 * id_a) == first_b {
+            return Err(ForestError::WouldCreateCycle);
+        }
+
+        // insert the whole tour of "tree b" right before "node a" is exited
+        self.splay(first_b);
+        let (before, after) = self.split_before(2 * id_a + 1);
+        let joined = self.join(before, Some(first_b));
+        self.join(joined, after);
+        Ok(())
+    }
+
+    /// Infallible version of [`try_link`](EulerTourForest::try_link).
+    ///
+    /// # Panics
+    /// Panics if an id does not belong to the forest or if both nodes already belong to the same tree.
+    #[inline]
+    pub fn link(&mut self, id_a: Index<Self>, id_b: Index<Self>) {
+        self.try_link(id_a, id_b).unwrap();
+    }
+
+    /// Cuts the node identified by `id` from its parent, making it the root of a new tree.
+    /// Does nothing if the node is already a root.
+    ///
+    /// # Errors
+    /// Will return [`ForestError::InvalidId`] if the id does not belong to the forest.
+    pub fn try_cut(&mut self, id: Index<Self>) -> Result<(), ForestError<Infallible>> {
+        let key = self.check(id)?;
+        let (before, _) = self.split_before(2 * key);
+        let (_, after) = self.split_after(2 * key + 1);
+        self.join(before, after);
+        Ok(())
+    }
+
+    /// Infallible version of [`try_cut`](EulerTourForest::try_cut).
+    ///
+    /// # Panics
+    /// Panics if the id does not belong to the forest.
+    #[inline]
+    pub fn cut(&mut self, id: Index<Self>) {
+        self.try_cut(id).unwrap();
+    }
+
+    /// Replaces the value of the node identified by `id`.
+    ///
+    /// # Errors
+    /// Will return [`ForestError::InvalidId`] if the id does not belong to the forest.
+    pub fn try_set_value(
+        &mut self,
+        id: Index<Self>,
+        value: V,
+    ) -> Result<(), ForestError<Infallible>> {
+        let key = self.check(id)?;
+        self.splay(2 * key);
+        self.tokens[2 * key].value = Some(value);
+        self.pull(2 * key);
+        Ok(())
+    }
+
+    /// Infallible version of [`try_set_value`](EulerTourForest::try_set_value).
+    ///
+    /// # Panics
+    /// Panics if the id does not belong to the forest.
+    #[inline]
+    pub fn set_value(&mut self, id: Index<Self>, value: V) {
+        self.try_set_value(id, value).unwrap();
+    }
+
+    /// Computes the product of the values of the subtree rooted at the node identified by `id`, in preorder.
+    ///
+    /// # Errors
+    /// Will return [`ForestError::InvalidId`] if the id does not belong to the forest.
+    pub fn try_eval_subtree(&mut self, id: Index<Self>) -> Result<V, ForestError<Infallible>> {
+        let key = self.check(id)?;
+        let (before, _) = self.split_before(2 * key);
+        let (subtree, after) = self.split_after(2 * key + 1);
+        // the subtree contains at least the value of the node
+        let value = self.tokens[subtree.unwrap()].aggregate.clone().unwrap();
+        let joined = self.join(before, subtree);
+        self.join(joined, after);
+        Ok(value)
+    }
+
+    /// Infallible version of [`try_eval_subtree`](EulerTourForest::try_eval_subtree).
+    ///
+    /// # Panics
+    /// Panics if the id does not belong to the forest.
+    #[inline]
+    pub fn eval_subtree(&mut self, id: Index<Self>) -> V {
+        self.try_eval_subtree(id).unwrap()
+    }
+
+    fn check(&self, id: Index<Self>) -> Result<usize, ForestError<Infallible>> {
+        let key: usize = id.into();
+        if 2 * key < self.tokens.len() {
+            Ok(key)
+        } else {
+            Err(ForestError::InvalidId)
+        }
+    }
+
+    fn associate(lhs: &V, rhs: &V) -> V {
+        match O::associate(lhs, rhs) {
+            Ok(value) => value,
+            Err(e) => match e {},
+        }
+    }
+
+    fn pull(&mut self, key: usize) {
+        let token = &self.tokens[key];
+        let mut aggregate = token
+            .left
+            .and_then(|left_key| self.tokens[left_key].aggregate.clone());
+        aggregate = match (aggregate, &token.value) {
+            (Some(lhs), Some(rhs)) => Some(Self::associate(&lhs, rhs)),
+            (lhs, rhs) => lhs.or_else(|| rhs.clone()),
+        };
+        if let Some(right_key) = token.right {
+            aggregate = match (aggregate, &self.tokens[right_key].aggregate) {
+                (Some(lhs), Some(rhs)) => Some(Self::associate(&lhs, rhs)),
+                (lhs, rhs) => lhs.or_else(|| rhs.clone()),
+            };
+        }
+        self.tokens[key].aggregate = aggregate;
+    }
+
+    fn rotate(&mut self, key: usize) {
+        // assume it's not the root of its splay tree
+        let parent_key = self.tokens[key].parent.unwrap();
+        let grand_parent_key = self.tokens[parent_key].parent;
+
+        if self.tokens[parent_key].left == Some(key) {
+            let moved = self.tokens[key].right;
+            self.tokens[parent_key].left = moved;
+            if let Some(moved_key) = moved {
+                self.tokens[moved_key].parent = Some(parent_key);
+            }
+            self.tokens[key].right = Some(parent_key);
+        } else {
+            let moved = self.tokens[key].left;
+            self.tokens[parent_key].right = moved;
+            if let Some(moved_key) = moved {
+                self.tokens[moved_key].parent = Some(parent_key);
+            }
+            self.tokens[key].left = Some(parent_key);
+        }
+        self.tokens[parent_key].parent = Some(key);
+        self.tokens[key].parent = grand_parent_key;
+        if let Some(grand_parent_key) = grand_parent_key {
+            let grand_parent = &mut self.tokens[grand_parent_key];
+            if grand_parent.left == Some(parent_key) {
+                grand_parent.left = Some(key);
+            } else {
+                grand_parent.right = Some(key);
+            }
+        }
+
+        self.pull(parent_key);
+        self.pull(key);
+    }
+
+    fn splay(&mut self, key: usize) {
+        while let Some(parent_key) = self.tokens[key].parent {
+            if let Some(grand_parent_key) = self.tokens[parent_key].parent {
+                let zig_zig = (self.tokens[grand_parent_key].left == Some(parent_key))
+                    == (self.tokens[parent_key].left == Some(key));
+                if zig_zig {
+                    self.rotate(parent_key);
+                } else {
+                    self.rotate(key);
+                }
+            }
+            self.rotate(key);
+        }
+    }
+
+    // Returns the first token of the tour that contains `key`, which enters the root of the tree.
+    fn first_token(&mut self, key: usize) -> usize {
+        self.splay(key);
+        let mut first_key = key;
+        while let Some(left_key) = self.tokens[first_key].left {
+            first_key = left_key;
+        }
+        self.splay(first_key);
+        first_key
+    }
+
+    // Splits the tour that contains `key` into the tokens before `key` and the remaining ones.
+    fn split_before(&mut self, key: usize) -> (Option<usize>, Option<usize>) {
+        self.splay(key);
+        let left = self.tokens[key].left.take();
+        if let Some(left_key) = left {
+            self.tokens[left_key].parent = None;
+            self.pull(key);
+        }
+        (left, Some(key))
+    }
+
+    // Splits the tour that contains `key` into the tokens up to `key` and the remaining ones.
+    fn split_after(&mut self, key: usize) -> (Option<usize>, Option<usize>) {
+        self.splay(key);
+        let right = self.tokens[key].right.take();
+        if let Some(right_key) = right {
+            self.tokens[right_key].parent = None;
+            self.pull(key);
+        }
+        (Some(key), right)
+    }
+
+    // Concatenates two tours given by the roots of their splay trees.
+    fn join(&mut self, left: Option<usize>, right: Option<usize>) -> Option<usize> {
+        let Some(mut last_key) = left else {
+            return right;
+        };
+        while let Some(right_key) = self.tokens[last_key].right {
+            last_key = right_key;
+        }
+        self.splay(last_key);
+        self.tokens[last_key].right = right;
+        if let Some(right_key) = right {
+            self.tokens[right_key].parent = Some(last_key);
+        }
+        self.pull(last_key);
+        Some(last_key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::operation::*;
+
+    // Concatenation of byte strings, a non-commutative operation.
+    struct Concat;
+
+    impl AssociativeOperation<Vec<u8>> for Concat {
+        type Error = Infallible;
+
+        fn associate(lhs: &Vec<u8>, rhs: &Vec<u8>) -> Result<Vec<u8>, Self::Error> {
+            Ok([lhs.as_slice(), rhs.as_slice()].concat())
+        }
+    }
+
+    #[test]
+    fn add_forest() {
+        let mut forest: EulerTourForest<usize, CloneAdd> = EulerTourForest::with_capacity(4);
+        let v0 = forest.new_root(2);
+        let v1 = forest.new_root(3);
+        let v2 = forest.new_root(4);
+        let v3 = forest.new_root(5);
+
+        forest.link(v0, v1);
+        forest.link(v2, v3);
+
+        assert_eq!(5, forest.eval_subtree(v0));
+        assert_eq!(9, forest.eval_subtree(v2));
+
+        forest.link(v3, v0);
+
+        assert_eq!(14, forest.eval_subtree(v2));
+        assert_eq!(10, forest.eval_subtree(v3));
+        assert_eq!(v2, forest.find_root(v1));
+
+        forest.cut(v0);
+        forest.set_value(v1, 1);
+
+        assert_eq!(3, forest.eval_subtree(v0));
+        assert_eq!(9, forest.eval_subtree(v2));
+        assert!(!forest.connected(v1, v3));
+        assert_eq!(Err(ForestError::WouldCreateCycle), forest.try_link(v1, v0));
+    }
+
+    #[test]
+    fn same_as_children_lists() {
+        fn preorder(children: &[Vec<usize>], values: &[Vec<u8>], key: usize) -> Vec<u8> {
+            let mut value = values[key].clone();
+            for &child_key in &children[key] {
+                value.extend(preorder(children, values, child_key));
+            }
+            value
+        }
+
+        let n = 48;
+        let mut forest: EulerTourForest<Vec<u8>, Concat> = EulerTourForest::new();
+        let ids: Vec<_> = (0..n).map(|i| forest.new_root(vec![i as u8])).collect();
+        let mut parents: Vec<Option<usize>> = vec![None; n];
+        let mut children: Vec<Vec<usize>> = vec![vec![]; n];
+        let mut values: Vec<Vec<u8>> = (0..n).map(|i| vec![i as u8]).collect();
+
+        let root = |parents: &[Option<usize>], mut key: usize| {
+            while let Some(parent_key) = parents[key] {
+                key = parent_key;
+            }
+            key
+        };
+
+        let mut state: usize = 5;
+        let mut next = |bound: usize| {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 33) % bound
+        };
+
+        for _ in 0..1024 {
+            let a = next(n);
+            let b = next(n);
+            match next(4) {
+                0 => {
+                    let linked = forest.try_link(ids[a], ids[b]);
+                    let root_b = root(&parents, b);
+                    if root(&parents, a) == root_b {
+                        assert_eq!(Err(ForestError::WouldCreateCycle), linked);
+                    } else {
+                        assert_eq!(Ok(()), linked);
+                        parents[root_b] = Some(a);
+                        children[a].push(root_b);
+                    }
+                }
+                1 => {
+                    forest.cut(ids[a]);
+                    if let Some(parent_key) = parents[a].take() {
+                        children[parent_key].retain(|&child_key| child_key != a);
+                    }
+                }
+                2 => {
+                    forest.set_value(ids[a], vec![b as u8, b as u8]);
+                    values[a] = vec![b as u8, b as u8];
+                }
+                _ => {}
+            }
+
+            assert_eq!(preorder(&children, &values, a), forest.eval_subtree(ids[a]));
+            assert_eq!(root(&parents, b), usize::from(forest.find_root(ids[b])));
+        }
+    }
+}
@@ -1,246 +1,2690 @@
+use std::borrow::Cow;
 use std::marker::PhantomData;
+use std::sync::Arc;
 
-use crate::node::Node;
-use crate::operation::{AssociativeOperation, DefaultOperation};
-use crate::EvalLinkUpdate;
+use crate::compression::sealed::Kind;
+use crate::compression::{Compression, FullCompression, NoCompression};
+use crate::index::{Generation, Index, IndexOffset, IndexType, Indices};
+use crate::node::{compress_path, Node};
+use crate::observer::ForestObserver;
+use crate::operation::{AssociativeOperation, DefaultOperation, MonoidOperation};
+use crate::stats::Counters;
+#[cfg(feature = "stats")]
+use crate::Stats;
+use crate::{EvalLinkUpdate, ForestError, FrozenForest, NewRoot, SharedEval};
 
-// A simple safe index type for identifying nodes in a compressed forest.
-#[derive(Debug)]
-pub struct Index<F>(usize, PhantomData<F>);
-
-impl<F> From<Index<F>> for usize {
-    fn from(i: Index<F>) -> usize {
-        i.0
-    }
-}
-
-impl<F> Clone for Index<F> {
-    fn clone(&self) -> Self {
-        Self(self.0, PhantomData)
-    }
-}
-impl<F> Copy for Index<F> {}
-
-impl<F> PartialEq for Index<F> {
-    fn eq(&self, other: &Self) -> bool {
-        self.0 == other.0
-    }
-}
-impl<F> Eq for Index<F> {}
-
-impl<F> PartialOrd for Index<F> {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        self.0.partial_cmp(&other.0)
-    }
-}
-impl<F> Ord for Index<F> {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.0.cmp(&other.0)
-    }
-}
+// Ids of nodes of the forest `F`.
+type Ids<F> = Vec<Index<F>>;
 
 /// A simple EVAL-LINK-UPDATE forest structure that performs (unbalanced) path compression.
 ///
+/// Compressed paths no longer reflect the links, so edges cannot be removed: see [`NaiveForest::try_cut`](crate::NaiveForest::try_cut)
+/// and [`LinkCutForest::try_cut`](crate::LinkCutForest::try_cut) instead.
+///
 /// `V` is the value type associated to nodes in the forest and `O` is the associative operation applied when evaluating.
+/// `Ix` is the integer type used to store parent indices (see [`IndexType`]), it bounds the number of nodes in the forest.
+/// `C` is the strategy used to compress paths (see [`Compression`]) and `B` is notified of structural changes
+/// (see [`ForestObserver`]).
 #[derive(Debug, Clone)]
-pub struct CompressedForest<V, O = DefaultOperation>
+pub struct CompressedForest<V, O = DefaultOperation, Ix = usize, C = FullCompression, B = ()>
 where
     O: 'static,
 {
-    nodes: Vec<Node<V>>,
+    nodes: Vec<Node<V, Ix>>,
+    // number of nodes of each tree, only meaningful for roots
+    sizes: Vec<Ix>,
+    tree_count: usize,
+    // incremented when the forest is cleared, so that former ids are detected as stale
+    generation: Generation,
+    stats: Counters,
+    observer: B,
     _op: PhantomData<O>,
+    _compression: PhantomData<C>,
 }
 
-impl<V, O> Default for CompressedForest<V, O>
+impl<V, O, Ix, C, B> Default for CompressedForest<V, O, Ix, C, B>
 where
     V: Clone,
     O: AssociativeOperation<V>,
+    Ix: IndexType,
+    C: Compression,
+    B: ForestObserver<Index<Self>, V> + Default,
 {
     #[inline]
     fn default() -> Self {
-        Self {
-            nodes: vec![],
-            _op: PhantomData,
-        }
+        Self::with_observer(B::default())
     }
 }
 
-impl<V, O> CompressedForest<V, O>
+impl<V, O, Ix, C, B> CompressedForest<V, O, Ix, C, B>
 where
     V: Clone,
     O: AssociativeOperation<V>,
+    Ix: IndexType,
+    C: Compression,
+    B: ForestObserver<Index<Self>, V>,
 {
     /// Creates a new empty forest.
     #[inline]
     #[must_use]
-    pub fn new() -> Self {
+    pub fn new() -> Self
+    where
+        B: Default,
+    {
         Self::default()
     }
 
     /// Creates a new empty forest with a given capacity.
     #[inline]
     #[must_use]
-    pub fn with_capacity(capacity: usize) -> Self {
+    pub fn with_capacity(capacity: usize) -> Self
+    where
+        B: Default,
+    {
+        let mut forest = Self::default();
+        forest.reserve(capacity);
+        forest
+    }
+
+    /// Creates a new empty forest that notifies `observer` of every structural change.
+    #[inline]
+    #[must_use]
+    pub fn with_observer(observer: B) -> Self {
         Self {
-            nodes: Vec::with_capacity(capacity),
+            nodes: vec![],
+            sizes: vec![],
+            tree_count: 0,
+            generation: Generation::default(),
+            stats: Counters::default(),
+            observer,
             _op: PhantomData,
+            _compression: PhantomData,
         }
     }
 
+    /// Returns the observer of the forest.
+    #[inline]
+    #[must_use]
+    pub fn observer(&self) -> &B {
+        &self.observer
+    }
+
+    /// Returns a mutable reference to the observer of the forest.
+    #[inline]
+    #[must_use]
+    pub fn observer_mut(&mut self) -> &mut B {
+        &mut self.observer
+    }
+
     /// Reserve enough space for a given number of nodes.
     #[inline]
     pub fn reserve(&mut self, additional: usize) {
         self.nodes.reserve(additional);
+        self.sizes.reserve(additional);
     }
 
-    fn compress(&mut self, key: usize) -> Result<(), O::Error> {
-        let current = &self.nodes[key];
-        // assume it's not a root
-        let parent_key = current.parent().unwrap();
-        let parent = &self.nodes[parent_key];
+    /// Shrinks the capacity of the forest as much as possible.
+    #[inline]
+    pub fn shrink_to_fit(&mut self) {
+        self.nodes.shrink_to_fit();
+        self.sizes.shrink_to_fit();
+    }
 
-        // while the parent is not a root
-        if !parent.is_root() {
-            //TODO: get rid of recursive call
-            self.compress(parent_key)?;
+    /// Shrinks the capacity of the forest with a lower bound.
+    ///
+    /// The capacity will remain at least as large as both the number of nodes and the supplied value.
+    #[inline]
+    pub fn shrink_to(&mut self, min_capacity: usize) {
+        self.nodes.shrink_to(min_capacity);
+        self.sizes.shrink_to(min_capacity);
+    }
 
-            let current_val = self.nodes[key].value();
-            let parent = &self.nodes[parent_key];
-            let parent_val = parent.value();
-            let parent_parent = parent.parent().unwrap();
+    /// Removes every node from the forest, keeping the allocated memory.
+    ///
+    /// Ids obtained before clearing must not be used anymore: they are reported as invalid until
+    /// new nodes are created, after which they may identify these new nodes. With the `generations` feature,
+    /// they are always reported as invalid.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+        self.sizes.clear();
+        self.tree_count = 0;
+        self.generation = self.generation.next();
+    }
 
-            let merged_values = O::associate(parent_val, current_val)?;
-            self.nodes[key].set_value(merged_values);
-            self.nodes[key].set_parent(parent_parent);
+    /// Moves all the trees of `other` into the forest, leaving `other` empty, and returns the translation of the ids
+    /// of `other`.
+    ///
+    /// # Errors
+    /// Will return [`ForestError::CapacityExceeded`] if the forest would hold more than `Ix::MAX` nodes,
+    /// in which case both forests are left unchanged.
+    pub fn try_append(
+        &mut self,
+        other: &mut Self,
+    ) -> Result<IndexOffset<Self>, ForestError<O::Error>> {
+        let offset = self.nodes.len();
+        let len = other.nodes.len();
+        // `Ix::MAX` is reserved for roots
+        if offset + len > Ix::MAX {
+            return Err(ForestError::CapacityExceeded);
         }
+        self.nodes.extend(other.nodes.drain(..).map(|mut node| {
+            if let Some(parent_key) = node.parent() {
+                node.set_parent(parent_key + offset);
+            }
+            node
+        }));
+        self.sizes.append(&mut other.sizes);
+        self.tree_count += std::mem::take(&mut other.tree_count);
+        // ids of `other` are translated, they do not belong to it anymore
+        let generation = other.generation;
+        other.generation = other.generation.next();
+        Ok(IndexOffset::new(offset, len, (generation, self.generation)))
+    }
 
-        Ok(())
+    /// Moves all the trees of `other` into the forest, and returns the translation of the ids of `other`,
+    /// see [`try_append`](CompressedForest::try_append).
+    ///
+    /// # Panics
+    /// Panics if the forest would hold more than `Ix::MAX` nodes.
+    pub fn append(&mut self, mut other: Self) -> IndexOffset<Self> {
+        match self.try_append(&mut other) {
+            Ok(offset) => offset,
+            Err(_) => panic!("the forest is full"),
+        }
     }
-}
 
-impl<V, O> EvalLinkUpdate for CompressedForest<V, O>
-where
-    V: Clone,
-    O: 'static + AssociativeOperation<V>,
-{
-    type Id = Index<Self>;
-    type Value = V;
-    type Operation = O;
+    // Ids of every node of the forest, in creation order.
+    #[inline]
+    pub(crate) fn ids(&self) -> Indices<Self> {
+        Indices::with_generation(0..self.nodes.len(), self.generation)
+    }
+
+    /// Creates a new tree root in the forest with the given value, see [`NewRoot::new_root`].
+    ///
+    /// # Errors
+    /// Will return [`ForestError::CapacityExceeded`] if the forest already holds `Ix::MAX` nodes.
+    pub fn try_new_root(&mut self, value: V) -> Result<Index<Self>, ForestError<O::Error>> {
+        if self.nodes.len() >= Ix::MAX {
+            return Err(ForestError::CapacityExceeded);
+        }
+        Ok(self.new_root(value))
+    }
+
+    /// Creates a forest with a new tree root for each value, and returns the ids of these roots.
+    ///
+    /// # Panics
+    /// Panics if there are more than `Ix::MAX` values.
+    pub fn from_values<I>(values: I) -> (Self, Indices<Self>)
+    where
+        I: IntoIterator<Item = V>,
+        B: Default,
+    {
+        let forest: Self = values.into_iter().collect();
+        let ids = forest.ids();
+        (forest, ids)
+    }
+
+    /// Creates a new tree root for each value, and returns the ids of these roots.
+    ///
+    /// # Panics
+    /// Panics if the forest would hold more than `Ix::MAX` nodes.
+    pub fn extend_roots<I>(&mut self, values: I) -> Indices<Self>
+    where
+        I: IntoIterator<Item = V>,
+    {
+        let values = values.into_iter();
+        self.reserve(values.size_hint().0);
+
+        let start = self.nodes.len();
+        for value in values {
+            assert!(self.nodes.len() < Ix::MAX, "the forest is full");
+            let key = self.nodes.len();
+            self.nodes.push(Node::new_root(value));
+            self.sizes.push(Ix::new(1));
+            self.observer
+                .on_new_root(self.id(key), self.nodes[key].value());
+        }
+        self.tree_count += self.nodes.len() - start;
+        Indices::with_generation(start..self.nodes.len(), self.generation)
+    }
+
+    /// Creates `n` new tree roots with default values, and returns the ids of these roots.
+    ///
+    /// # Panics
+    /// Panics if the forest would hold more than `Ix::MAX` nodes.
+    #[inline]
+    pub fn new_roots(&mut self, n: usize) -> Indices<Self>
+    where
+        V: Default,
+    {
+        self.new_roots_with(n, V::default)
+    }
 
+    /// Creates a new tree root whose value is the identity of the operation, e.g. for a node that does not contribute
+    /// to the evaluation of its descendants.
+    ///
+    /// # Panics
+    /// Panics if the forest already holds `Ix::MAX` nodes.
+    #[inline]
     #[must_use]
-    fn new_root(&mut self, value: V) -> Index<Self> {
-        let index = self.nodes.len();
-        self.nodes.push(Node::new_root(value));
-        Index(index, PhantomData)
+    pub fn new_root_identity(&mut self) -> Index<Self>
+    where
+        O: MonoidOperation<V>,
+    {
+        self.new_root(O::identity())
     }
 
-    fn try_link(&mut self, id_a: Index<Self>, id_b: Index<Self>) -> Result<(), O::Error> {
-        let id_a: usize = id_a.into();
-        let id_b: usize = id_b.into();
+    /// Creates `n` new tree roots whose values are the identity of the operation, and returns the ids of these roots.
+    ///
+    /// # Panics
+    /// Panics if the forest would hold more than `Ix::MAX` nodes.
+    #[inline]
+    pub fn new_roots_identity(&mut self, n: usize) -> Indices<Self>
+    where
+        O: MonoidOperation<V>,
+    {
+        self.new_roots_with(n, O::identity)
+    }
 
-        let root_a_key = if self.nodes[id_a].is_root() {
-            id_a
-        } else {
-            self.compress(id_a)?;
-            self.nodes[id_a].parent().unwrap()
-        };
+    /// Creates `n` new tree roots with values returned by `f`, and returns the ids of these roots.
+    ///
+    /// # Panics
+    /// Panics if the forest would hold more than `Ix::MAX` nodes.
+    #[inline]
+    pub fn new_roots_with<F>(&mut self, n: usize, f: F) -> Indices<Self>
+    where
+        F: FnMut() -> V,
+    {
+        self.extend_roots(std::iter::repeat_with(f).take(n))
+    }
 
-        let root_b_key = if self.nodes[id_b].is_root() {
-            id_b
-        } else {
-            self.compress(id_b)?;
-            self.nodes[id_b].parent().unwrap()
-        };
+    /// Returns the number of nodes in the forest.
+    #[inline]
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
 
-        self.nodes[root_b_key].set_parent(root_a_key);
-        // if "node a" is not the root of it's tree
-        // need to update the value of "node b"
-        if root_a_key != id_a {
-            let new_value = O::associate(self.nodes[id_a].value(), self.nodes[root_b_key].value())?;
-            self.nodes[root_b_key].set_value(new_value);
+    /// Returns the number of trees in the forest.
+    #[inline]
+    #[must_use]
+    pub fn tree_count(&self) -> usize {
+        self.tree_count
+    }
+
+    /// Returns `true` if the forest contains no nodes.
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Returns the number of nodes the forest can hold without reallocating.
+    #[inline]
+    #[must_use]
+    pub fn capacity(&self) -> usize {
+        self.nodes.capacity()
+    }
+
+    /// Returns the counters of the operations performed by the forest since it was created or since the last reset.
+    ///
+    /// Counters are kept when values are mapped (see [`map_values`](CompressedForest::map_values)).
+    #[cfg(feature = "stats")]
+    #[inline]
+    #[must_use]
+    pub fn stats(&self) -> Stats {
+        self.stats.get()
+    }
+
+    /// Resets the counters of the operations performed by the forest, see [`stats`](CompressedForest::stats).
+    #[cfg(feature = "stats")]
+    #[inline]
+    pub fn reset_stats(&mut self) {
+        self.stats.reset();
+    }
+
+    /// Checks whether the node identified by `id` is currently the root of its tree.
+    ///
+    /// # Panics
+    /// Panics if the id does not belong to the forest.
+    #[inline]
+    #[must_use]
+    pub fn is_root(&self, id: Index<Self>) -> bool {
+        let key = self.key(id);
+        self.nodes[key].is_root()
+    }
+
+    /// Returns the value stored in the node identified by `id`.
+    ///
+    /// The value of a node is evaluated as the value of its current parent associated with its stored value.
+    /// Stored values are adjusted by path compression, so only the stored values of roots are the values given to the forest.
+    ///
+    /// # Panics
+    /// Panics if the id does not belong to the forest.
+    #[inline]
+    #[must_use]
+    pub fn stored_value(&self, id: Index<Self>) -> &V {
+        let key = self.key(id);
+        self.nodes[key].value()
+    }
+
+    /// Returns a mutable reference to the value stored in the node identified by `id`.
+    ///
+    /// Modifying the stored value of a root is the same as updating it. For other nodes, it changes the value of the
+    /// node and of the nodes currently stored below it, but not of the descendants that path compression already
+    /// linked to an ancestor: it should only be used by algorithms that control when paths are compressed.
+    ///
+    /// # Panics
+    /// Panics if the id does not belong to the forest.
+    #[inline]
+    #[must_use]
+    pub fn stored_value_mut(&mut self, id: Index<Self>) -> &mut V {
+        let key = self.key(id);
+        self.nodes[key].value_mut()
+    }
+
+    /// Returns the ids of the current ancestors of the node identified by `id`, starting with the node itself
+    /// and ending with the root of its tree.
+    ///
+    /// Paths are not compressed, but they reflect the compressions already made rather than the links.
+    ///
+    /// # Panics
+    /// Panics if the id does not belong to the forest.
+    #[must_use]
+    pub fn path_to_root(&self, id: Index<Self>) -> Vec<Index<Self>> {
+        let mut key = self.key(id);
+        let mut path = vec![id];
+        while let Some(parent_key) = self.nodes[key].parent() {
+            path.push(self.id(parent_key));
+            key = parent_key;
         }
+        path
+    }
 
-        Ok(())
+    /// Returns an iterator over the roots of the forest, i.e. over its trees.
+    pub fn roots(&self) -> impl Iterator<Item = Index<Self>> + '_ {
+        self.nodes
+            .iter()
+            .enumerate()
+            .filter(|(_, node)| node.is_root())
+            .map(|(key, _)| self.id(key))
     }
 
-    fn try_update(&mut self, id: Index<Self>, value: V) -> Result<(), O::Error> {
-        let key: usize = id.into();
-        let node = &mut self.nodes[key];
+    /// Returns an iterator over every node of the forest alongside its stored value.
+    ///
+    /// Stored values are adjusted by path compression: only the values of roots are the values given to the forest,
+    /// use [`try_eval`](EvalLinkUpdate::try_eval) to compute the value of a node.
+    pub fn iter(&self) -> impl Iterator<Item = (Index<Self>, &V)> + '_ {
+        self.nodes
+            .iter()
+            .enumerate()
+            .map(|(key, node)| (self.id(key), node.value()))
+    }
+
+    /// Returns an iterator over mutable references to the stored value of every node, in creation order.
+    ///
+    /// As with [`stored_value_mut`](CompressedForest::stored_value_mut), modifying the stored value of a node that is
+    /// not a root does not change the value of the descendants already linked to an ancestor. Modifying every stored
+    /// value with a function that maps the operation to itself (e.g. scaling sums) keeps every evaluation consistent.
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut V> + '_ {
+        self.nodes.iter_mut().map(Node::value_mut)
+    }
+
+    /// Calls `f` on every node of the forest alongside a mutable reference to its stored value,
+    /// see [`values_mut`](CompressedForest::values_mut).
+    pub fn for_each_value_mut<F>(&mut self, mut f: F)
+    where
+        F: FnMut(Index<Self>, &mut V),
+    {
+        let generation = self.generation;
+        for (key, node) in self.nodes.iter_mut().enumerate() {
+            f(Index::with_generation(key, generation), node.value_mut());
+        }
+    }
+
+    /// Returns an iterator over the nodes of the tree that contains the node identified by `id`.
+    ///
+    /// Trees are found by scanning the whole forest in linear time, without compressing paths.
+    ///
+    /// # Errors
+    /// Will return [`ForestError::InvalidId`] if the id does not belong to the forest.
+    pub fn try_tree_members(
+        &self,
+        id: Index<Self>,
+    ) -> Result<impl Iterator<Item = Index<Self>> + '_, ForestError<O::Error>> {
+        let key = self.check(id)?;
+        let roots = self.root_keys();
+        let root_key = roots[key];
+        Ok((0..self.nodes.len())
+            .filter(move |&key| roots[key] == root_key)
+            .map(|key| self.id(key)))
+    }
+
+    /// Moves the tree that contains the node identified by `id` into a new forest, and returns it with the former ids
+    /// of its nodes: the `i`-th returned id is the former id of the `i`-th node of the new forest.
+    ///
+    /// The other nodes are left untouched and keep their relative order in the forest, so the key of each of them
+    /// is shifted down by the number of moved nodes that had lower keys. Ids obtained before the split must not be
+    /// used anymore: the translation of the former ids is returned as well, its `i`-th entry is the new id of the
+    /// node whose former key is `i`, or `None` if the node was moved. With the `generations` feature, former ids
+    /// are reported as invalid.
+    ///
+    /// # Errors
+    /// Will return [`ForestError::InvalidId`] if the id does not belong to the forest.
+    #[allow(clippy::type_complexity)]
+    pub fn try_split_tree(
+        &mut self,
+        id: Index<Self>,
+    ) -> Result<(Self, Ids<Self>, Vec<Option<Index<Self>>>), ForestError<O::Error>>
+    where
+        B: Default,
+    {
+        let key = self.check(id)?;
+        let roots = self.root_keys();
+        let root_key = roots[key];
+
+        // new key of every node, in the forest it ends up in
+        let mut moved = vec![];
+        let mut new_keys = Vec::with_capacity(self.nodes.len());
+        let moved_keys: Vec<_> = roots
+            .iter()
+            .map(|&node_root_key| node_root_key == root_key)
+            .collect();
+        for (key, &node_root_key) in roots.iter().enumerate() {
+            if node_root_key == root_key {
+                new_keys.push(moved.len());
+                moved.push(self.id(key));
+            } else {
+                new_keys.push(key - moved.len());
+            }
+        }
+
+        let mut split = Self::with_capacity(moved.len());
+        let nodes = std::mem::take(&mut self.nodes);
+        let sizes = std::mem::take(&mut self.sizes);
+        for ((mut node, size), node_root_key) in nodes.into_iter().zip(sizes).zip(roots) {
+            if let Some(parent_key) = node.parent() {
+                node.set_parent(new_keys[parent_key]);
+            }
+            let forest = if node_root_key == root_key {
+                &mut split
+            } else {
+                &mut *self
+            };
+            forest.nodes.push(node);
+            forest.sizes.push(size);
+        }
+        split.tree_count = 1;
+        self.tree_count -= 1;
+        // remaining nodes may have been given new keys
+        self.generation = self.generation.next();
+
+        let remaining = new_keys
+            .into_iter()
+            .zip(&moved_keys)
+            .map(|(new_key, &is_moved)| (!is_moved).then(|| self.id(new_key)))
+            .collect();
+        Ok((split, moved, remaining))
+    }
+
+    /// Computes the value of the node identified by `id`, and returns it with the ids of the nodes whose stored values
+    /// were associated to compute it, in association order (i.e. from the root to the node).
+    ///
+    /// Ids are collected before the path is compressed, see [`path_to_root`](CompressedForest::path_to_root).
+    ///
+    /// # Errors
+    /// Will return [`ForestError::InvalidId`] if the id does not belong to the forest
+    /// and [`ForestError::Operation`] if [`O::associate`](AssociativeOperation::associate) fails.
+    pub fn try_eval_with_path(
+        &mut self,
+        id: Index<Self>,
+    ) -> Result<(V, Ids<Self>), ForestError<O::Error>> {
+        self.check(id)?;
+        let mut path = self.path_to_root(id);
+        path.reverse();
+        Ok((self.try_eval(id)?, path))
+    }
 
-        if node.is_root() {
-            node.set_value(value);
+    /// Computes the value of the node identified by `id` as [`try_eval`](EvalLinkUpdate::try_eval) does, but borrows
+    /// the stored value instead of cloning it when the node is a root.
+    ///
+    /// # Errors
+    /// Will return [`ForestError::InvalidId`] if the id does not belong to the forest
+    /// and [`ForestError::Operation`] if [`O::associate`](AssociativeOperation::associate) fails.
+    pub fn try_eval_cow(&mut self, id: Index<Self>) -> Result<Cow<'_, V>, ForestError<O::Error>> {
+        let key = self.check(id)?;
+        self.stats.evals(1);
+        self.compress(key)?;
+        #[cfg(feature = "stats")]
+        self.stats.hops(self.depth(key));
+        if self.nodes[key].is_root() {
+            Ok(Cow::Borrowed(self.nodes[key].value()))
         } else {
-            self.compress(key)?;
-            // node is not root and compress ensure parent is root
-            let parent_key = self.nodes[key].parent().unwrap();
-            let parent = &mut self.nodes[parent_key];
-            parent.set_value(value);
+            self.eval_key(key).map(Cow::Owned)
+        }
+    }
+
+    /// Compresses the path of every node, so that every node becomes a root or a child of its root
+    /// and is then evaluated in constant time.
+    ///
+    /// Paths are fully compressed whatever the compression strategy of the forest. Every node is compressed
+    /// at most once, so this runs in linear time. Forests without compression (see [`NoCompression`]) are left
+    /// untouched, so that their links can still be cut.
+    ///
+    /// # Errors
+    /// Will return [`ForestError::Operation`] if [`O::associate`](AssociativeOperation::associate) fails.
+    pub fn try_compress_all(&mut self) -> Result<(), ForestError<O::Error>> {
+        if C::KIND == Kind::None {
+            return Ok(());
         }
+        self.compress_every_path()
+    }
 
+    fn compress_every_path(&mut self) -> Result<(), ForestError<O::Error>> {
+        // paths that go through already compressed nodes stop at these nodes
+        for key in 0..self.nodes.len() {
+            self.compress_full(key)?;
+        }
         Ok(())
     }
 
-    fn try_eval(&mut self, id: Index<Self>) -> Result<V, O::Error> {
-        let id: usize = id.into();
+    /// Computes the value of every node, the value of the node of key `i` is at index `i`.
+    ///
+    /// The whole forest is compressed first, then values are computed in a single pass. With the `simd` feature,
+    /// the values of all children are associated at once with [`AssociativeOperation::associate_slices`].
+    /// Forests without compression (see [`NoCompression`]) are not compressed, each path is walked instead.
+    ///
+    /// # Errors
+    /// Will return [`ForestError::Operation`] if [`O::associate`](AssociativeOperation::associate) fails.
+    pub fn try_eval_all(&mut self) -> Result<Vec<V>, ForestError<O::Error>> {
+        // keeps the links of forests without compression, so that they can still be cut
+        if C::KIND == Kind::None {
+            return (0..self.nodes.len())
+                .map(|key| self.eval_key(key))
+                .collect();
+        }
+        self.try_compress_all()?;
+        #[cfg(feature = "simd")]
+        return self.eval_compressed_slices();
+        #[cfg(not(feature = "simd"))]
+        (0..self.nodes.len())
+            .map(|key| self.eval_key(key))
+            .collect()
+    }
+
+    /// Computes the value and the root of every node, and returns them as a read-only forest.
+    ///
+    /// # Errors
+    /// Will return [`ForestError::Operation`] if [`O::associate`](AssociativeOperation::associate) fails.
+    #[allow(clippy::type_complexity)]
+    pub fn try_freeze(mut self) -> Result<FrozenForest<V, O, Ix, C, B>, ForestError<O::Error>> {
+        // the forest is consumed, so links of forests without compression do not need to be kept
+        self.compress_every_path()?;
+        let values = self.try_eval_all()?;
+        // every node is now a root or a child of its root
+        let roots = self
+            .nodes
+            .iter()
+            .enumerate()
+            .map(|(key, node)| Ix::new(node.parent().unwrap_or(key)))
+            .collect();
+        Ok(FrozenForest::new(roots, values))
+    }
+
+    /// Returns the number of nodes of the tree that contains the node identified by `id`.
+    ///
+    /// # Errors
+    /// Will return [`ForestError::InvalidId`] if the id does not belong to the forest
+    /// and [`ForestError::Operation`] if [`O::associate`](AssociativeOperation::associate) fails.
+    pub fn try_tree_size(&mut self, id: Index<Self>) -> Result<usize, ForestError<O::Error>> {
+        let key = self.check(id)?;
+        let root_key = self.find_root_key(key)?;
+        Ok(self.sizes[root_key].index())
+    }
 
-        let node = &self.nodes[id];
-        if !node.is_root() {
-            self.compress(id)?;
+    /// Creates a forest from its parent-array representation: the node of key `i` has the parent and the value
+    /// of the `i`-th entry, and its evaluation is the evaluation of its parent associated with its value.
+    ///
+    /// # Errors
+    /// Will return [`ForestError::CapacityExceeded`] if there are more than `Ix::MAX` entries,
+    /// [`ForestError::InvalidId`] if a parent is out of range
+    /// and [`ForestError::WouldCreateCycle`] if parents do not form a forest.
+    pub fn from_parent_array(array: Vec<(Option<usize>, V)>) -> Result<Self, ForestError<O::Error>>
+    where
+        B: Default,
+    {
+        if array.len() > Ix::MAX {
+            return Err(ForestError::CapacityExceeded);
+        }
+        if array
+            .iter()
+            .any(|&(parent, _)| parent.is_some_and(|parent_key| parent_key >= array.len()))
+        {
+            return Err(ForestError::InvalidId);
         }
 
-        let node = &self.nodes[id];
-        match node.parent() {
-            None => Ok(node.value().clone()),
-            Some(parent_key) => {
-                let parent = &self.nodes[*parent_key];
-                O::associate(parent.value(), node.value())
+        #[derive(Clone, Copy, PartialEq)]
+        enum Visit {
+            Pending,
+            InProgress,
+            Done,
+        }
+        let mut visits = vec![Visit::Pending; array.len()];
+        for key in 0..array.len() {
+            let mut current = Some(key);
+            while let Some(current_key) = current {
+                match visits[current_key] {
+                    Visit::Done => break,
+                    Visit::InProgress => return Err(ForestError::WouldCreateCycle),
+                    Visit::Pending => visits[current_key] = Visit::InProgress,
+                }
+                current = array[current_key].0;
             }
+            // every node of the path leads to a root
+            let mut current = Some(key);
+            while let Some(current_key) = current.filter(|&k| visits[k] == Visit::InProgress) {
+                visits[current_key] = Visit::Done;
+                current = array[current_key].0;
+            }
+        }
+
+        let nodes = array
+            .into_iter()
+            .map(|(parent, value)| {
+                let mut node = Node::new_root(value);
+                if let Some(parent_key) = parent {
+                    node.set_parent(parent_key);
+                }
+                node
+            })
+            .collect();
+        let mut forest = Self {
+            nodes,
+            sizes: vec![],
+            tree_count: 0,
+            generation: Generation::default(),
+            stats: Counters::default(),
+            observer: B::default(),
+            _op: PhantomData,
+            _compression: PhantomData,
+        };
+        let mut sizes = vec![0; forest.nodes.len()];
+        for root_key in forest.root_keys() {
+            sizes[root_key] += 1;
         }
+        forest.tree_count = sizes.iter().filter(|&&size| size > 0).count();
+        forest.sizes = sizes.into_iter().map(Ix::new).collect();
+        Ok(forest)
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::operation::*;
+    /// Returns the parent-array representation of the forest, see [`from_parent_array`](CompressedForest::from_parent_array).
+    ///
+    /// Since paths are compressed, parents and values reflect the internal state of the forest rather than the links
+    /// that were made, but every node evaluates to the same value.
+    #[must_use]
+    pub fn to_parent_array(&self) -> Vec<(Option<usize>, V)> {
+        self.nodes
+            .iter()
+            .map(|node| (node.parent(), node.value().clone()))
+            .collect()
+    }
 
-    #[test]
-    fn add_forest() {
-        let mut forest: CompressedForest<usize, CloneAdd> = CompressedForest::with_capacity(4);
-        let v0 = forest.new_root(2);
-        let v1 = forest.new_root(3);
-        let v2 = forest.new_root(4);
-        let v3 = forest.new_root(5);
+    /// Transforms the stored value of every node with `f`, keeping the structure and the observer of the forest,
+    /// and returns the forest with the new values and the associative operation `O2`.
+    ///
+    /// Stored values are products of values given to the forest, so nodes evaluate to the transformed value of their
+    /// former evaluation only if `f` maps `O` to `O2` (i.e. `f(a ⊕ b)` is `f(a) ⊗ f(b)`).
+    pub fn map_values<W, O2, F>(self, mut f: F) -> CompressedForest<W, O2, Ix, C, B>
+    where
+        W: Clone,
+        O2: AssociativeOperation<W>,
+        F: FnMut(V) -> W,
+    {
+        CompressedForest {
+            nodes: self
+                .nodes
+                .into_iter()
+                .map(|node| node.map_value(&mut f))
+                .collect(),
+            sizes: self.sizes,
+            tree_count: self.tree_count,
+            generation: self.generation,
+            stats: self.stats,
+            observer: self.observer,
+            _op: PhantomData,
+            _compression: PhantomData,
+        }
+    }
 
-        forest.try_link(v0, v1).unwrap();
-        forest.try_link(v2, v3).unwrap();
+    /// Returns the forest with the same structure and stored values, but with the associative operation `O2`.
+    ///
+    /// Stored values are products of values given to the forest as soon as a path is compressed or a tree is linked
+    /// to a node that is not a root: nodes evaluate to their value under `O2` only if no such product was computed,
+    /// or if `O` and `O2` agree on these products.
+    #[inline]
+    pub fn into_operation<O2>(self) -> CompressedForest<V, O2, Ix, C, B>
+    where
+        O2: AssociativeOperation<V>,
+    {
+        self.map_values(|value| value)
+    }
 
-        assert_eq!(5, forest.try_eval(v1).unwrap());
-        assert_eq!(9, forest.try_eval(v3).unwrap());
+    /// Renders the forest in the Graphviz DOT format.
+    ///
+    /// Nodes are labeled with their key and their stored value, edges go from children to their parent
+    /// and roots are drawn with a double border. Since paths are compressed, stored values and parents
+    /// reflect the internal state of the forest rather than the links that were made.
+    #[must_use]
+    pub fn to_dot(&self) -> String
+    where
+        V: std::fmt::Debug,
+    {
+        let mut dot = String::from("digraph {\n");
+        for (key, node) in self.nodes.iter().enumerate() {
+            let label = format!("{key}: {:?}", node.value());
+            match node.parent() {
+                None => dot.push_str(&format!("    {key} [label={label:?}, peripheries=2];\n")),
+                Some(parent_key) => {
+                    dot.push_str(&format!("    {key} [label={label:?}];\n"));
+                    dot.push_str(&format!("    {key} -> {parent_key};\n"));
+                }
+            }
+        }
+        dot.push('}');
+        dot
+    }
 
-        forest.try_link(v3, v0).unwrap();
+    /// Renders every tree of the forest, see [`fmt_tree`](CompressedForest::fmt_tree).
+    #[must_use]
+    pub fn display_trees(&self) -> String
+    where
+        V: std::fmt::Debug,
+    {
+        let children = self.children_keys();
+        let mut trees = String::new();
+        for root_key in (0..self.nodes.len()).filter(|&key| self.nodes[key].is_root()) {
+            // writing to a string never fails
+            self.write_tree(&mut trees, &children, root_key).unwrap();
+        }
+        trees
+    }
 
-        assert_eq!(11, forest.try_eval(v0).unwrap());
-        assert_eq!(14, forest.try_eval(v1).unwrap());
+    /// Writes the tree that contains the node identified by `id` to `out`, one node per line.
+    ///
+    /// Each node is written as its key and its stored value, below its parent and indented by two spaces per depth,
+    /// and children are written in creation order. Since paths are compressed, stored values and parents reflect the
+    /// internal state of the forest rather than the links that were made.
+    ///
+    /// # Errors
+    /// Will return an error if writing to `out` fails.
+    ///
+    /// # Panics
+    /// Panics if the id does not belong to the forest.
+    pub fn fmt_tree<W>(&self, out: &mut W, id: Index<Self>) -> std::fmt::Result
+    where
+        V: std::fmt::Debug,
+        W: std::fmt::Write,
+    {
+        let root = *self.path_to_root(id).last().unwrap();
+        self.write_tree(out, &self.children_keys(), root.into())
     }
 
-    #[test]
-    fn mul_forest() {
-        let mut forest: CompressedForest<usize, CloneMul> = CompressedForest::with_capacity(4);
-        let v0 = forest.new_root(2);
-        let v1 = forest.new_root(3);
-        let v2 = forest.new_root(4);
-        let v3 = forest.new_root(5);
+    // Keys of the children of every node, in creation order.
+    fn children_keys(&self) -> Vec<Vec<usize>> {
+        let mut children = vec![vec![]; self.nodes.len()];
+        for (key, node) in self.nodes.iter().enumerate() {
+            if let Some(parent_key) = node.parent() {
+                children[parent_key].push(key);
+            }
+        }
+        children
+    }
 
-        forest.link(v0, v1);
-        forest.link(v2, v3);
+    fn write_tree<W>(
+        &self,
+        out: &mut W,
+        children: &[Vec<usize>],
+        root_key: usize,
+    ) -> std::fmt::Result
+    where
+        V: std::fmt::Debug,
+        W: std::fmt::Write,
+    {
+        let mut stack = vec![(root_key, 0)];
+        while let Some((key, depth)) = stack.pop() {
+            writeln!(
+                out,
+                "{:indent$}{key}: {:?}",
+                "",
+                self.nodes[key].value(),
+                indent = 2 * depth
+            )?;
+            stack.extend(
+                children[key]
+                    .iter()
+                    .rev()
+                    .map(|&child_key| (child_key, depth + 1)),
+            );
+        }
+        Ok(())
+    }
 
-        assert_eq!(6, forest.eval(v1));
-        assert_eq!(20, forest.eval(v3));
+    fn check(&self, id: Index<Self>) -> Result<usize, ForestError<O::Error>> {
+        let key: usize = id.into();
+        if key < self.nodes.len() && id.generation() == self.generation {
+            Ok(key)
+        } else {
+            Err(ForestError::InvalidId)
+        }
+    }
 
-        forest.link(v3, v0);
+    #[inline]
+    fn id(&self, key: usize) -> Index<Self> {
+        Index::with_generation(key, self.generation)
+    }
 
-        assert_eq!(40, forest.eval(v0));
-        assert_eq!(120, forest.eval(v1));
+    // Key of an id that is not checked, only its generation is.
+    #[inline]
+    fn key(&self, id: Index<Self>) -> usize {
+        assert!(
+            id.generation() == self.generation,
+            "the id was obtained before the forest was cleared"
+        );
+        id.into()
+    }
+
+    // Computes the root of every node, memoizing the roots of the nodes already visited.
+    fn root_keys(&self) -> Vec<usize> {
+        const UNKNOWN: usize = usize::MAX;
+        let mut roots = vec![UNKNOWN; self.nodes.len()];
+        let mut path = vec![];
+        for key in 0..self.nodes.len() {
+            let mut current = key;
+            while roots[current] == UNKNOWN {
+                match self.nodes[current].parent() {
+                    None => roots[current] = current,
+                    Some(parent_key) => {
+                        path.push(current);
+                        current = parent_key;
+                    }
+                }
+            }
+            let root_key = roots[current];
+            for key in path.drain(..) {
+                roots[key] = root_key;
+            }
+        }
+        roots
+    }
+
+    // Evaluates the node by walking its current path, without compressing it.
+    fn eval_key(&self, key: usize) -> Result<V, ForestError<O::Error>> {
+        let node = &self.nodes[key];
+        let Some(mut parent_key) = node.parent() else {
+            return Ok(node.value().clone());
+        };
+        let mut value = O::associate(self.nodes[parent_key].value(), node.value())
+            .map_err(ForestError::Operation)?;
+        while let Some(ancestor_key) = self.nodes[parent_key].parent() {
+            value = O::associate(self.nodes[ancestor_key].value(), &value)
+                .map_err(ForestError::Operation)?;
+            parent_key = ancestor_key;
+        }
+        Ok(value)
+    }
+
+    // Shortens the path of the node according to the compression strategy.
+    fn compress(&mut self, key: usize) -> Result<(), ForestError<O::Error>> {
+        match C::KIND {
+            Kind::Full => self.compress_full(key),
+            Kind::Halving => self.compress_to_grandparents(key, true),
+            Kind::Splitting => self.compress_to_grandparents(key, false),
+            Kind::None => Ok(()),
+        }
+    }
+
+    fn compress_full(&mut self, key: usize) -> Result<(), ForestError<O::Error>> {
+        let generation = self.generation;
+        let (stats, observer) = (&mut self.stats, &mut self.observer);
+        let hops = compress_path(&mut self.nodes, key, O::associate, |key, root_key| {
+            stats.compressions(1);
+            observer.on_compress(
+                Index::with_generation(key, generation),
+                Index::with_generation(root_key, generation),
+            );
+        })
+        .map_err(ForestError::Operation)?;
+        self.stats.hops(hops);
+        Ok(())
+    }
+
+    // Links the nodes of the path to their grandparent, skipping every other node if `halve` is set.
+    fn compress_to_grandparents(
+        &mut self,
+        mut key: usize,
+        halve: bool,
+    ) -> Result<(), ForestError<O::Error>> {
+        while let Some(parent_key) = self.nodes[key].parent() {
+            let Some(grandparent_key) = self.nodes[parent_key].parent() else {
+                break;
+            };
+            let merged_values =
+                O::associate(self.nodes[parent_key].value(), self.nodes[key].value())
+                    .map_err(ForestError::Operation)?;
+            self.nodes[key].set_value(merged_values);
+            self.nodes[key].set_parent(grandparent_key);
+            self.stats.hops(1);
+            self.stats.compressions(1);
+            self.observer
+                .on_compress(self.id(key), self.id(grandparent_key));
+            key = if halve { grandparent_key } else { parent_key };
+        }
+        Ok(())
+    }
+
+    fn find_root_key(&mut self, key: usize) -> Result<usize, ForestError<O::Error>> {
+        self.compress(key)?;
+        let mut key = key;
+        while let Some(parent_key) = self.nodes[key].parent() {
+            self.stats.hops(1);
+            key = parent_key;
+        }
+        Ok(key)
+    }
+
+    // Computes the value of every node, assuming that every node is a root or a child of its root.
+    #[cfg(feature = "simd")]
+    fn eval_compressed_slices(&self) -> Result<Vec<V>, ForestError<O::Error>> {
+        let mut values: Vec<V> = self.nodes.iter().map(|node| node.value().clone()).collect();
+        let (children, roots): (Vec<usize>, Vec<V>) = self
+            .nodes
+            .iter()
+            .enumerate()
+            .filter_map(|(key, node)| Some((key, values[node.parent()?].clone())))
+            .unzip();
+        let mut children_values: Vec<V> = children.iter().map(|&key| values[key].clone()).collect();
+        O::associate_slices(&roots, &mut children_values).map_err(ForestError::Operation)?;
+        for (key, value) in children.into_iter().zip(children_values) {
+            values[key] = value;
+        }
+        Ok(values)
+    }
+
+    // Links the root of "tree b" to "node a", given the roots of both trees.
+    fn link_roots(
+        &mut self,
+        id_a: usize,
+        root_a_key: usize,
+        root_b_key: usize,
+    ) -> Result<(), ForestError<O::Error>> {
+        // without compression, "node b" keeps "node a" as parent so that it can be cut (see `try_cut`)
+        let parent_key = if C::KIND == Kind::None {
+            id_a
+        } else {
+            root_a_key
+        };
+        // if "node a" is not the parent of "node b"
+        // need to update the value of "node b" with the values of the path from "node a" to its root,
+        // computed before any change so that a failed link leaves the forest untouched
+        if parent_key != id_a {
+            let mut new_value =
+                O::associate(self.nodes[id_a].value(), self.nodes[root_b_key].value())
+                    .map_err(ForestError::Operation)?;
+            let mut key = self.nodes[id_a].parent().unwrap();
+            while key != root_a_key {
+                self.stats.hops(1);
+                new_value = O::associate(self.nodes[key].value(), &new_value)
+                    .map_err(ForestError::Operation)?;
+                key = self.nodes[key].parent().unwrap();
+            }
+            self.nodes[root_b_key].set_value(new_value);
+        }
+        self.nodes[root_b_key].set_parent(parent_key);
+        let size = self.sizes[root_a_key].index() + self.sizes[root_b_key].index();
+        self.sizes[root_a_key] = Ix::new(size);
+        self.tree_count -= 1;
+        self.observer
+            .on_link(self.id(root_a_key), self.id(root_b_key));
+
+        Ok(())
+    }
+
+    // Number of parent pointers between the node and its root.
+    #[cfg(feature = "stats")]
+    fn depth(&self, key: usize) -> usize {
+        self.path_to_root(self.id(key)).len() - 1
+    }
+}
+
+impl<V, O, Ix, C, B> CompressedForest<V, O, Ix, C, B>
+where
+    V: Clone,
+    O: AssociativeOperation<V, Error = std::convert::Infallible>,
+    Ix: IndexType,
+    C: Compression,
+    B: ForestObserver<Index<Self>, V>,
+{
+    /// Infallible version of [`try_tree_members`](CompressedForest::try_tree_members).
+    ///
+    /// # Panics
+    /// Panics if the id does not belong to the forest.
+    #[inline]
+    pub fn tree_members(&self, id: Index<Self>) -> impl Iterator<Item = Index<Self>> + '_ {
+        self.try_tree_members(id).unwrap()
+    }
+
+    /// Infallible version of [`try_split_tree`](CompressedForest::try_split_tree).
+    ///
+    /// # Panics
+    /// Panics if the id does not belong to the forest.
+    #[inline]
+    #[allow(clippy::type_complexity)]
+    pub fn split_tree(&mut self, id: Index<Self>) -> (Self, Ids<Self>, Vec<Option<Index<Self>>>)
+    where
+        B: Default,
+    {
+        self.try_split_tree(id).unwrap()
+    }
+
+    /// Infallible version of [`try_eval_with_path`](CompressedForest::try_eval_with_path).
+    ///
+    /// # Panics
+    /// Panics if the id does not belong to the forest.
+    #[inline]
+    pub fn eval_with_path(&mut self, id: Index<Self>) -> (V, Vec<Index<Self>>) {
+        self.try_eval_with_path(id).unwrap()
+    }
+
+    /// Infallible version of [`try_eval_cow`](CompressedForest::try_eval_cow).
+    ///
+    /// # Panics
+    /// Panics if the id does not belong to the forest.
+    #[inline]
+    pub fn eval_cow(&mut self, id: Index<Self>) -> Cow<'_, V> {
+        self.try_eval_cow(id).unwrap()
+    }
+
+    /// Same as [`eval`](EvalLinkUpdate::eval), without checking that the id belongs to the forest.
+    ///
+    /// Roots and children of roots, i.e. nodes whose path is compressed, are evaluated without any bounds check.
+    ///
+    /// # Safety
+    /// The id must belong to the forest, i.e. it must be lower than [`len`](CompressedForest::len).
+    #[inline]
+    pub unsafe fn eval_unchecked(&mut self, id: Index<Self>) -> V {
+        let key: usize = id.into();
+        debug_assert!(
+            key < self.nodes.len(),
+            "the id does not belong to the forest"
+        );
+        // SAFETY: the caller guarantees that the node exists, and parents always do
+        let node = unsafe { self.nodes.get_unchecked(key) };
+        let value = match node.parent() {
+            None => node.value().clone(),
+            Some(parent_key) => {
+                let parent = unsafe { self.nodes.get_unchecked(parent_key) };
+                if !parent.is_root() {
+                    return self.eval(id);
+                }
+                let Ok(value) = O::associate(parent.value(), node.value());
+                value
+            }
+        };
+        self.stats.evals(1);
+        #[cfg(feature = "stats")]
+        self.stats.hops(self.depth(key));
+        value
+    }
+
+    /// Same as [`link`](EvalLinkUpdate::link), without checking that the ids belong to the forest.
+    ///
+    /// The roots of nodes whose path is compressed are found without any bounds check.
+    ///
+    /// # Safety
+    /// Both ids must belong to the forest, i.e. they must be lower than [`len`](CompressedForest::len).
+    ///
+    /// # Panics
+    /// Panics if both nodes already belong to the same tree.
+    #[inline]
+    pub unsafe fn link_unchecked(&mut self, id_a: Index<Self>, id_b: Index<Self>) {
+        let (key_a, key_b): (usize, usize) = (id_a.into(), id_b.into());
+        self.stats.link();
+        // SAFETY: the caller guarantees that both nodes exist
+        let (root_a_key, root_b_key) = unsafe {
+            (
+                self.root_key_unchecked(key_a),
+                self.root_key_unchecked(key_b),
+            )
+        };
+        assert_ne!(
+            root_a_key, root_b_key,
+            "both nodes already belong to the same tree"
+        );
+        self.link_roots(key_a, root_a_key, root_b_key).unwrap();
+    }
+
+    // Finds the root of the node, without bounds checks if the node is a root or a child of a root.
+    // Assumes that the node belongs to the forest.
+    unsafe fn root_key_unchecked(&mut self, key: usize) -> usize {
+        debug_assert!(
+            key < self.nodes.len(),
+            "the id does not belong to the forest"
+        );
+        match unsafe { self.nodes.get_unchecked(key) }.parent() {
+            None => key,
+            Some(parent_key) if unsafe { self.nodes.get_unchecked(parent_key) }.is_root() => {
+                parent_key
+            }
+            Some(_) => self.find_root_key(key).unwrap(),
+        }
+    }
+
+    /// Infallible version of [`try_compress_all`](CompressedForest::try_compress_all).
+    #[inline]
+    pub fn compress_all(&mut self) {
+        self.try_compress_all().unwrap();
+    }
+
+    /// Infallible version of [`try_eval_all`](CompressedForest::try_eval_all).
+    #[inline]
+    pub fn eval_all(&mut self) -> Vec<V> {
+        self.try_eval_all().unwrap()
+    }
+
+    /// Infallible version of [`try_freeze`](CompressedForest::try_freeze).
+    #[inline]
+    #[must_use]
+    pub fn freeze(self) -> FrozenForest<V, O, Ix, C, B> {
+        self.try_freeze().unwrap()
+    }
+
+    /// Infallible version of [`try_tree_size`](CompressedForest::try_tree_size).
+    ///
+    /// # Panics
+    /// Panics if the id does not belong to the forest.
+    #[inline]
+    pub fn tree_size(&mut self, id: Index<Self>) -> usize {
+        self.try_tree_size(id).unwrap()
+    }
+}
+
+impl<V, O, Ix, B> CompressedForest<V, O, Ix, NoCompression, B>
+where
+    V: Clone,
+    O: AssociativeOperation<V>,
+    Ix: IndexType,
+    B: ForestObserver<Index<Self>, V>,
+{
+    /// Cuts the node identified by `id` from its parent, making it the root of a new tree whose value is the
+    /// current value of the node. The nodes below it move to the new tree and the value of every node is left unchanged.
+    ///
+    /// Cutting is only available without compression: compressing a path makes its nodes children of their root and
+    /// merges the values of their former ancestors, which forgets the links they were made with. Without compression,
+    /// a node keeps the node it was linked to as parent, so cutting it separates exactly the nodes linked below it.
+    /// Does nothing if the node is already a root.
+    ///
+    /// # Errors
+    /// Will return [`ForestError::InvalidId`] if the id does not belong to the forest
+    /// and [`ForestError::Operation`] if [`O::associate`](AssociativeOperation::associate) fails.
+    pub fn try_cut(&mut self, id: Index<Self>) -> Result<(), ForestError<O::Error>> {
+        let key = self.check(id)?;
+        if self.nodes[key].is_root() {
+            return Ok(());
+        }
+        let value = self.eval_key(key)?;
+        let root_key = self.find_root_key(key)?;
+
+        let size = self.subtree_size(key);
+        self.sizes[root_key] = Ix::new(self.sizes[root_key].index() - size);
+        self.sizes[key] = Ix::new(size);
+        self.tree_count += 1;
+        let node = &mut self.nodes[key];
+        node.set_value(value);
+        node.set_root();
+        self.observer.on_cut(self.id(key), self.nodes[key].value());
+        Ok(())
+    }
+
+    /// Replaces the value `v` of every node on the path from the root of the tree to the node identified by `id`
+    /// by `v` ⊕ `delta`, see [`NaiveForest::try_update_path`](crate::NaiveForest::try_update_path).
+    ///
+    /// Only forests without compression keep the nodes of the path, see [`try_cut`](CompressedForest::try_cut).
+    ///
+    /// # Errors
+    /// Will return [`ForestError::InvalidId`] if the id does not belong to the forest
+    /// and [`ForestError::Operation`] if [`O::associate`](AssociativeOperation::associate) fails.
+    pub fn try_update_path(
+        &mut self,
+        id: Index<Self>,
+        delta: V,
+    ) -> Result<(), ForestError<O::Error>> {
+        // every value is computed before any change so that a failure leaves the forest untouched
+        let mut values = vec![];
+        let mut current = Some(self.check(id)?);
+        while let Some(key) = current {
+            let node = &self.nodes[key];
+            values.push((
+                key,
+                O::associate(node.value(), &delta).map_err(ForestError::Operation)?,
+            ));
+            current = node.parent();
+        }
+        for (key, value) in values {
+            self.nodes[key].set_value(value);
+        }
+        Ok(())
+    }
+
+    // Number of nodes whose path to the root goes through the node, memoizing the nodes already visited.
+    fn subtree_size(&self, key: usize) -> usize {
+        let mut below = vec![None; self.nodes.len()];
+        below[key] = Some(true);
+        let mut path = vec![];
+        for start in 0..self.nodes.len() {
+            let mut current = start;
+            let is_below = loop {
+                if let Some(is_below) = below[current] {
+                    break is_below;
+                }
+                path.push(current);
+                match self.nodes[current].parent() {
+                    None => break false,
+                    Some(parent_key) => current = parent_key,
+                }
+            };
+            for key in path.drain(..) {
+                below[key] = Some(is_below);
+            }
+        }
+        below
+            .into_iter()
+            .filter(|&is_below| is_below == Some(true))
+            .count()
+    }
+}
+
+impl<V, O, Ix, B> CompressedForest<V, O, Ix, NoCompression, B>
+where
+    V: Clone,
+    O: AssociativeOperation<V, Error = std::convert::Infallible>,
+    Ix: IndexType,
+    B: ForestObserver<Index<Self>, V>,
+{
+    /// Infallible version of [`try_update_path`](CompressedForest::try_update_path).
+    ///
+    /// # Panics
+    /// Panics if the id does not belong to the forest.
+    #[inline]
+    pub fn update_path(&mut self, id: Index<Self>, delta: V) {
+        self.try_update_path(id, delta).unwrap();
+    }
+
+    /// Infallible version of [`try_cut`](CompressedForest::try_cut).
+    ///
+    /// # Panics
+    /// Panics if the id does not belong to the forest.
+    #[inline]
+    pub fn cut(&mut self, id: Index<Self>) {
+        self.try_cut(id).unwrap();
+    }
+}
+
+impl<V, O, Ix, C, B> CompressedForest<Arc<V>, O, Ix, C, B>
+where
+    O: AssociativeOperation<Arc<V>>,
+    Ix: IndexType,
+    C: Compression,
+    B: ForestObserver<Index<Self>, Arc<V>>,
+{
+    /// Creates a new tree root in the forest with the given value, shared by an [`Arc`]
+    /// (see [`Shared`](crate::operation::Shared)).
+    ///
+    /// # Panics
+    /// Panics if the forest already holds `Ix::MAX` nodes.
+    #[inline]
+    #[must_use]
+    pub fn new_root_arc(&mut self, value: V) -> Index<Self> {
+        self.new_root(Arc::new(value))
+    }
+
+    /// Creates a new tree root for each value, shared by an [`Arc`], and returns the ids of these roots.
+    ///
+    /// # Panics
+    /// Panics if the forest would hold more than `Ix::MAX` nodes.
+    #[inline]
+    pub fn extend_roots_arc<I>(&mut self, values: I) -> Indices<Self>
+    where
+        I: IntoIterator<Item = V>,
+    {
+        self.extend_roots(values.into_iter().map(Arc::new))
+    }
+}
+
+impl<V, O, Ix, C, B> std::ops::Index<Index<CompressedForest<V, O, Ix, C, B>>>
+    for CompressedForest<V, O, Ix, C, B>
+where
+    V: Clone,
+    O: AssociativeOperation<V>,
+    Ix: IndexType,
+    C: Compression,
+    B: ForestObserver<Index<Self>, V>,
+{
+    type Output = V;
+
+    /// Returns the value stored in the node, see [`stored_value`](CompressedForest::stored_value).
+    #[inline]
+    fn index(&self, id: Index<Self>) -> &V {
+        self.stored_value(id)
+    }
+}
+
+impl<V, O, Ix, C, B> std::ops::IndexMut<Index<CompressedForest<V, O, Ix, C, B>>>
+    for CompressedForest<V, O, Ix, C, B>
+where
+    V: Clone,
+    O: AssociativeOperation<V>,
+    Ix: IndexType,
+    C: Compression,
+    B: ForestObserver<Index<Self>, V>,
+{
+    /// Returns the value stored in the node, see [`stored_value_mut`](CompressedForest::stored_value_mut).
+    #[inline]
+    fn index_mut(&mut self, id: Index<Self>) -> &mut V {
+        self.stored_value_mut(id)
+    }
+}
+
+impl<V, O, Ix, C, B> FromIterator<V> for CompressedForest<V, O, Ix, C, B>
+where
+    V: Clone,
+    O: 'static + AssociativeOperation<V>,
+    Ix: IndexType,
+    C: Compression,
+    B: ForestObserver<Index<Self>, V> + Default,
+{
+    /// Creates a forest with a new tree root for each value.
+    ///
+    /// # Panics
+    /// Panics if there are more than `Ix::MAX` values.
+    fn from_iter<I>(values: I) -> Self
+    where
+        I: IntoIterator<Item = V>,
+    {
+        let mut forest = Self::new();
+        forest.extend(values);
+        forest
+    }
+}
+
+impl<V, O, Ix, C, B> Extend<V> for CompressedForest<V, O, Ix, C, B>
+where
+    V: Clone,
+    O: 'static + AssociativeOperation<V>,
+    Ix: IndexType,
+    C: Compression,
+    B: ForestObserver<Index<Self>, V>,
+{
+    /// Creates a new tree root for each value.
+    ///
+    /// # Panics
+    /// Panics if the forest would hold more than `Ix::MAX` nodes.
+    #[inline]
+    fn extend<I>(&mut self, values: I)
+    where
+        I: IntoIterator<Item = V>,
+    {
+        self.extend_roots(values);
+    }
+}
+
+impl<V, O, Ix, C, B> EvalLinkUpdate for CompressedForest<V, O, Ix, C, B>
+where
+    V: Clone,
+    O: 'static + AssociativeOperation<V>,
+    Ix: IndexType,
+    C: Compression,
+    B: ForestObserver<Index<Self>, V>,
+{
+    type Id = Index<Self>;
+    type Value = V;
+    type Operation = O;
+    type Error = O::Error;
+
+    #[inline]
+    fn node_count(&self) -> Option<usize> {
+        Some(self.len())
+    }
+
+    fn try_link(
+        &mut self,
+        id_a: Index<Self>,
+        id_b: Index<Self>,
+    ) -> Result<(), ForestError<O::Error>> {
+        let id_a = self.check(id_a)?;
+        let id_b = self.check(id_b)?;
+        self.stats.link();
+
+        let root_a_key = self.find_root_key(id_a)?;
+        let root_b_key = self.find_root_key(id_b)?;
+        if root_a_key == root_b_key {
+            return Err(ForestError::WouldCreateCycle);
+        }
+        self.link_roots(id_a, root_a_key, root_b_key)
+    }
+
+    fn try_update(&mut self, id: Index<Self>, value: V) -> Result<(), ForestError<O::Error>> {
+        let key = self.check(id)?;
+        self.stats.update();
+        let root_key = self.find_root_key(key)?;
+        self.observer.on_update(self.id(root_key), &value);
+        self.nodes[root_key].set_value(value);
+        Ok(())
+    }
+
+    fn try_find_root(&mut self, id: Index<Self>) -> Result<Index<Self>, ForestError<O::Error>> {
+        let key = self.check(id)?;
+        let root_key = self.find_root_key(key)?;
+        Ok(self.id(root_key))
+    }
+
+    fn try_eval(&mut self, id: Index<Self>) -> Result<V, ForestError<O::Error>> {
+        let key = self.check(id)?;
+        self.stats.evals(1);
+        self.compress(key)?;
+        #[cfg(feature = "stats")]
+        self.stats.hops(self.depth(key));
+        self.eval_key(key)
+    }
+
+    /// Every path is compressed before any value is computed, so that nodes sharing ancestors
+    /// benefit from the compression of each other's paths.
+    fn try_eval_many(&mut self, ids: &[Index<Self>]) -> Result<Vec<V>, ForestError<O::Error>> {
+        let keys = ids
+            .iter()
+            .map(|&id| self.check(id))
+            .collect::<Result<Vec<_>, _>>()?;
+        self.stats.evals(keys.len());
+        for &key in &keys {
+            self.compress(key)?;
+            #[cfg(feature = "stats")]
+            self.stats.hops(self.depth(key));
+        }
+
+        keys.into_iter().map(|key| self.eval_key(key)).collect()
+    }
+}
+
+impl<V, O, Ix, C, B> NewRoot for CompressedForest<V, O, Ix, C, B>
+where
+    V: Clone,
+    O: 'static + AssociativeOperation<V>,
+    Ix: IndexType,
+    C: Compression,
+    B: ForestObserver<Index<Self>, V>,
+{
+    /// # Panics
+    /// Panics if the forest already holds `Ix::MAX` nodes.
+    fn new_root(&mut self, value: V) -> Index<Self> {
+        let index = self.nodes.len();
+        // `Ix::MAX` is reserved for roots
+        assert!(index < Ix::MAX, "the forest is full");
+        self.nodes.push(Node::new_root(value));
+        self.sizes.push(Ix::new(1));
+        self.tree_count += 1;
+        self.observer
+            .on_new_root(self.id(index), self.nodes[index].value());
+        self.id(index)
+    }
+}
+
+impl<V, O, Ix, C, B> SharedEval for CompressedForest<V, O, Ix, C, B>
+where
+    V: Clone,
+    O: 'static + AssociativeOperation<V>,
+    Ix: IndexType,
+    C: Compression,
+    B: ForestObserver<Index<Self>, V>,
+{
+    fn try_eval_shared(&self, id: Index<Self>) -> Option<Result<V, ForestError<O::Error>>> {
+        let key = match self.check(id) {
+            Ok(key) => key,
+            Err(e) => return Some(Err(e)),
+        };
+        // the path is already compressed if the node is a root or a child of its root,
+        // and is never compressed without compression
+        match self.nodes[key].parent() {
+            Some(parent_key) if C::KIND != Kind::None && !self.nodes[parent_key].is_root() => None,
+            _ => Some(self.eval_key(key)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+
+    use super::*;
+    use crate::operation::*;
+    use crate::{ForestObserver, NoCompression, PathHalving, PathSplitting};
+
+    #[test]
+    fn add_forest() {
+        let mut forest: CompressedForest<usize, CloneAdd> = CompressedForest::with_capacity(4);
+        let v0 = forest.new_root(2);
+        let v1 = forest.new_root(3);
+        let v2 = forest.new_root(4);
+        let v3 = forest.new_root(5);
+
+        forest.try_link(v0, v1).unwrap();
+        forest.try_link(v2, v3).unwrap();
+
+        assert_eq!(5, forest.try_eval(v1).unwrap());
+        assert_eq!(9, forest.try_eval(v3).unwrap());
+
+        forest.try_link(v3, v0).unwrap();
+
+        assert_eq!(11, forest.try_eval(v0).unwrap());
+        assert_eq!(14, forest.try_eval(v1).unwrap());
+    }
+
+    #[test]
+    fn mul_forest() {
+        let mut forest: CompressedForest<usize, CloneMul> = CompressedForest::with_capacity(4);
+        let v0 = forest.new_root(2);
+        let v1 = forest.new_root(3);
+        let v2 = forest.new_root(4);
+        let v3 = forest.new_root(5);
+
+        forest.link(v0, v1);
+        forest.link(v2, v3);
+
+        assert_eq!(6, forest.eval(v1));
+        assert_eq!(20, forest.eval(v3));
+
+        forest.link(v3, v0);
+
+        assert_eq!(40, forest.eval(v0));
+        assert_eq!(120, forest.eval(v1));
+    }
+
+    #[test]
+    fn keep_forest() {
+        let mut lhs: CompressedForest<char, KeepLhs> = CompressedForest::new();
+        let mut rhs: CompressedForest<char, KeepRhs> = CompressedForest::new();
+        let ids: Vec<_> = lhs.extend_roots("abcd".chars()).collect();
+        let rhs_ids: Vec<_> = rhs.extend_roots("abcd".chars()).collect();
+        for i in (1..4).rev() {
+            lhs.link(ids[i - 1], ids[i]);
+            rhs.link(rhs_ids[i - 1], rhs_ids[i]);
+        }
+
+        assert_eq!('a', lhs.eval(ids[3]));
+        assert_eq!('d', rhs.eval(rhs_ids[3]));
+
+        lhs.update(ids[3], 'e');
+        rhs.update(rhs_ids[3], 'e');
+
+        assert_eq!('e', lhs.eval(ids[2]));
+        assert_eq!('c', rhs.eval(rhs_ids[2]));
+        assert_eq!('e', rhs.eval(rhs_ids[0]));
+    }
+
+    #[test]
+    fn affine_forest() {
+        // x ↦ 2x, x ↦ x + 3 and x ↦ 5x - 1
+        let mut forest: CompressedForest<(i64, i64), Affine> = CompressedForest::new();
+        let ids: Vec<_> = forest.extend_roots([(2, 0), (1, 3), (5, -1)]).collect();
+        forest.link(ids[1], ids[2]);
+        forest.link(ids[0], ids[1]);
+
+        // maps are applied from the root: x ↦ 2x + 3, then x ↦ 5(2x + 3) - 1
+        assert_eq!((2, 3), forest.eval(ids[1]));
+        assert_eq!((10, 14), forest.eval(ids[2]));
+
+        forest.update(ids[2], (1, 1));
+        assert_eq!((1, 4), forest.eval(ids[1]));
+        assert_eq!((5, 19), forest.eval(ids[2]));
+    }
+
+    #[test]
+    fn tags_forest() {
+        use std::collections::BTreeSet;
+
+        let mut forest: CompressedForest<BTreeSet<&str>, CloneUnion> = CompressedForest::new();
+        let ids: Vec<_> = forest
+            .extend_roots([["root"].into(), BTreeSet::new(), ["leaf", "root"].into()])
+            .collect();
+        forest.link(ids[0], ids[1]);
+        forest.link(ids[1], ids[2]);
+
+        assert_eq!(BTreeSet::from(["root"]), forest.eval(ids[1]));
+        assert_eq!(BTreeSet::from(["leaf", "root"]), forest.eval(ids[2]));
+    }
+
+    #[test]
+    fn pair_forest() {
+        let mut forest: CompressedForest<(u32, u32), Pair<CloneAdd, CloneMin>> =
+            CompressedForest::new();
+        let ids: Vec<_> = forest
+            .extend_roots([(3, 3), (1, 1), (4, 4), (2, 2)])
+            .collect();
+        forest.link(ids[2], ids[3]);
+        forest.link(ids[1], ids[2]);
+        forest.link(ids[0], ids[1]);
+
+        // sum and minimum of the path
+        assert_eq!((10, 1), forest.eval(ids[3]));
+        assert_eq!((3, 3), forest.eval(ids[0]));
+    }
+
+    #[test]
+    fn flip_forest() {
+        let mut forest: CompressedForest<(i64, i64), Flip<Affine>> = CompressedForest::new();
+        let ids: Vec<_> = forest.extend_roots([(2, 0), (1, 3), (5, -1)]).collect();
+        forest.link(ids[1], ids[2]);
+        forest.link(ids[0], ids[1]);
+
+        // maps are applied from the node: x ↦ 5x + 2, then x ↦ 2(5x + 2)
+        assert_eq!(
+            (5, 2),
+            Flip::<Affine>::associate(&(1, 3), &(5, -1)).unwrap()
+        );
+        assert_eq!((10, 4), forest.eval(ids[2]));
+    }
+
+    #[test]
+    fn identity_roots() {
+        let mut forest: CompressedForest<u32, CheckedMul> = CompressedForest::new();
+        let v0 = forest.new_root(3);
+        let v1 = forest.new_root_identity();
+        let ids: Vec<_> = forest.new_roots_identity(2).collect();
+        let v2 = forest.new_root(5);
+
+        forest.try_link(v0, v1).unwrap();
+        forest.try_link(v1, ids[0]).unwrap();
+        forest.try_link(ids[0], v2).unwrap();
+
+        assert_eq!(Ok(3), forest.try_eval(ids[0]));
+        assert_eq!(Ok(15), forest.try_eval(v2));
+        assert_eq!(Ok(1), forest.try_eval(ids[1]));
+    }
+
+    #[test]
+    fn permutation_forest() {
+        let mut forest: CompressedForest<[usize; 3], Permutation> = CompressedForest::new();
+        let ids: Vec<_> = forest
+            .extend_roots([[1, 2, 0], [1, 0, 2], [0, 2, 1]])
+            .collect();
+        forest.link(ids[1], ids[2]);
+        forest.link(ids[0], ids[1]);
+
+        // the rotation is applied first, then both swaps
+        assert_eq!([0, 2, 1], forest.eval(ids[1]));
+        assert_eq!([0, 1, 2], forest.eval(ids[2]));
+    }
+
+    #[test]
+    fn compose_forest() {
+        use std::sync::Arc;
+
+        type Pipeline = Arc<dyn Fn(String) -> String>;
+
+        let mut forest: CompressedForest<Pipeline, Compose> = CompressedForest::new();
+        let v0 = forest.new_root(Arc::new(|s| s + "a"));
+        let v1 = forest.new_root(Arc::new(|s: String| s.to_uppercase()));
+        let v2 = forest.new_root_identity();
+        let v3 = forest.new_root(Arc::new(|s| s + "b"));
+
+        forest.link(v2, v3);
+        forest.link(v1, v2);
+        forest.link(v0, v1);
+
+        // closures are applied from the root
+        assert_eq!("XA", forest.eval(v1)(String::from("x")));
+        assert_eq!("XAb", forest.eval(v3)(String::from("x")));
+        // once the path is compressed
+        assert_eq!("XAb", forest.eval(v3)(String::from("x")));
+    }
+
+    #[test]
+    fn interval_forest() {
+        let mut forest: CompressedForest<(i32, i32), IntervalIntersection> =
+            CompressedForest::new();
+        let ids: Vec<_> = forest
+            .extend_roots([(0, 100), (10, 50), (40, 60), (70, 80)])
+            .collect();
+        forest.try_link(ids[1], ids[2]).unwrap();
+        forest.try_link(ids[0], ids[1]).unwrap();
+
+        assert_eq!(Ok((40, 50)), forest.try_eval(ids[2]));
+        assert_eq!(
+            Err(ForestError::Operation(EmptyInterval)),
+            forest.try_link(ids[2], ids[3])
+        );
+        // a failed link leaves the forest unchanged
+        assert_eq!(2, forest.tree_count());
+        assert_eq!(Ok(ids[3]), forest.try_find_root(ids[3]));
+        assert_eq!(Ok((70, 80)), forest.try_eval(ids[3]));
+        assert_eq!(Ok(()), forest.try_link(ids[0], ids[3]));
+        assert_eq!(Ok((70, 80)), forest.try_eval(ids[3]));
+    }
+
+    #[test]
+    fn failed_link() {
+        let mut forest: CompressedForest<u8, CheckedAdd> = CompressedForest::new();
+        let v0 = forest.new_root(100);
+        let v1 = forest.new_root(100);
+        let v2 = forest.new_root(160);
+        forest.try_link(v0, v1).unwrap();
+
+        assert_eq!(
+            Err(ForestError::Operation(Overflow)),
+            forest.try_link(v1, v2)
+        );
+        assert_eq!(2, forest.tree_count());
+        assert_eq!(Ok(2), forest.try_tree_size(v0));
+        assert_eq!(Ok(v2), forest.try_find_root(v2));
+        assert_eq!(Ok(160), forest.try_eval(v2));
+        assert_eq!(Ok(()), forest.try_link(v2, v0));
+        assert_eq!(1, forest.tree_count());
+        assert_eq!(Ok(160), forest.try_eval(v2));
+    }
+
+    #[cfg(feature = "primitive-default-op")]
+    #[test]
+    fn default_operation() {
+        let mut forest: CompressedForest<u8> = CompressedForest::new();
+        let v0 = forest.new_root(200);
+        let v1 = forest.new_root(100);
+        forest.link(v0, v1);
+
+        assert_eq!(44, forest.eval(v1));
+    }
+
+    #[test]
+    fn shared_forest() {
+        use std::collections::BTreeSet;
+
+        let mut forest: CompressedForest<Arc<BTreeSet<u8>>, Shared<CloneUnion>> =
+            CompressedForest::new();
+        let v0 = forest.new_root_arc([1, 2].into());
+        let ids: Vec<_> = forest
+            .extend_roots_arc([BTreeSet::new(), [3].into()])
+            .collect();
+        forest.link(v0, ids[0]);
+        forest.link(ids[0], ids[1]);
+
+        assert_eq!(BTreeSet::from([1, 2, 3]), *forest.eval(ids[1]));
+        // roots are evaluated without cloning their set
+        assert!(Arc::ptr_eq(&forest.eval(v0), &forest[v0]));
+
+        let mut forest: CompressedForest<Arc<String>, SharedSelection<CloneMax>> =
+            CompressedForest::new();
+        let ids: Vec<_> = forest
+            .extend_roots_arc(["b".into(), "a".into(), "c".into()])
+            .collect();
+        forest.link(ids[1], ids[2]);
+        forest.link(ids[0], ids[1]);
+
+        assert!(Arc::ptr_eq(&forest.eval(ids[1]), &forest[ids[0]]));
+        assert!(Arc::ptr_eq(&forest.eval(ids[2]), &forest[ids[2]]));
+    }
+
+    #[test]
+    fn eval_cow() {
+        let mut forest: CompressedForest<String, Concat> = CompressedForest::new();
+        let v0 = forest.new_root("a".into());
+        let v1 = forest.new_root("b".into());
+        forest.link(v0, v1);
+
+        assert!(matches!(forest.eval_cow(v0), Cow::Borrowed(value) if value == "a"));
+        assert!(matches!(forest.eval_cow(v1), Cow::Owned(value) if value == "ab"));
+        assert_eq!(
+            Err(ForestError::InvalidId),
+            forest.try_eval_cow(Index::new(2))
+        );
+    }
+
+    #[test]
+    fn unchecked() {
+        let mut forest: CompressedForest<usize, CloneAdd> = (1..=4).collect();
+        let ids: Vec<_> = forest.roots().collect();
+        unsafe {
+            forest.link_unchecked(ids[2], ids[3]);
+            forest.link_unchecked(ids[1], ids[2]);
+            forest.link_unchecked(ids[0], ids[1]);
+
+            assert_eq!(10, forest.eval_unchecked(ids[3]));
+            assert_eq!(10, forest.eval_unchecked(ids[3]));
+            assert_eq!(3, forest.eval_unchecked(ids[1]));
+            assert_eq!(1, forest.eval_unchecked(ids[0]));
+        }
+        assert_eq!(10, forest.eval(ids[3]));
+    }
+
+    #[test]
+    #[should_panic(expected = "both nodes already belong to the same tree")]
+    fn unchecked_cycle() {
+        let mut forest: CompressedForest<usize, CloneAdd> = (1..=2).collect();
+        let ids: Vec<_> = forest.roots().collect();
+        unsafe {
+            forest.link_unchecked(ids[0], ids[1]);
+            forest.link_unchecked(ids[1], ids[0]);
+        }
+    }
+
+    #[cfg(feature = "simd")]
+    #[test]
+    fn eval_all_slices() {
+        let mut forest: CompressedForest<u8, CheckedAdd> = (0..64).collect();
+        let ids: Vec<_> = forest.roots().collect();
+        for key in 1..64 {
+            forest.try_link(ids[key / 2], ids[key]).unwrap();
+        }
+        let expected: Vec<_> = ids.iter().map(|&id| forest.try_eval(id)).collect();
+
+        assert_eq!(
+            Ok(expected.into_iter().map(Result::unwrap).collect()),
+            forest.try_eval_all()
+        );
+        forest.try_update(ids[0], 200).unwrap();
+        assert_eq!(Err(ForestError::Operation(Overflow)), forest.try_eval_all());
+    }
+
+    #[test]
+    fn find_root() {
+        let mut forest: CompressedForest<usize, CloneAdd> = CompressedForest::with_capacity(4);
+        let v0 = forest.new_root(2);
+        let v1 = forest.new_root(3);
+        let v2 = forest.new_root(4);
+        let v3 = forest.new_root(5);
+
+        forest.link(v0, v1);
+        forest.link(v1, v2);
+
+        assert_eq!(v0, forest.find_root(v2));
+        assert_eq!(v0, forest.find_root(v0));
+        assert_eq!(v3, forest.find_root(v3));
+
+        forest.link(v3, v2);
+
+        assert_eq!(v3, forest.find_root(v1));
+        assert_eq!(14, forest.eval(v2));
+    }
+
+    #[test]
+    fn connected() {
+        let mut forest: CompressedForest<usize, CloneAdd> = CompressedForest::with_capacity(3);
+        let v0 = forest.new_root(2);
+        let v1 = forest.new_root(3);
+        let v2 = forest.new_root(4);
+
+        assert!(!forest.connected(v0, v1));
+        forest.link(v0, v1);
+        assert!(forest.connected(v0, v1));
+        assert!(!forest.connected(v1, v2));
+        forest.link(v2, v0);
+        assert!(forest.connected(v1, v2));
+        assert!(forest.connected(v2, v2));
+    }
+
+    #[test]
+    fn eval_relative() {
+        let mut forest: CompressedForest<i64, CloneAdd> = CompressedForest::with_capacity(4);
+        let v0 = forest.new_root(2);
+        let v1 = forest.new_root(3);
+        let v2 = forest.new_root(-4);
+        let v3 = forest.new_root(5);
+
+        forest.link(v0, v1);
+        forest.link(v0, v2);
+
+        assert_eq!(Some(3), forest.eval_relative(v0, v1));
+        assert_eq!(Some(-7), forest.eval_relative(v1, v2));
+        assert_eq!(Some(0), forest.eval_relative(v2, v2));
+        assert_eq!(None, forest.eval_relative(v1, v3));
+
+        forest.link(v3, v0);
+        assert_eq!(Some(5), forest.eval_relative(v3, v1));
+        assert_eq!(Ok(Some(-5)), forest.try_eval_relative(v1, v3));
+    }
+
+    #[test]
+    fn cycle() {
+        let mut forest: CompressedForest<usize, CloneAdd> = CompressedForest::with_capacity(3);
+        let v0 = forest.new_root(2);
+        let v1 = forest.new_root(3);
+        let v2 = forest.new_root(4);
+
+        forest.link(v0, v1);
+        forest.link(v1, v2);
+
+        assert_eq!(Err(ForestError::WouldCreateCycle), forest.try_link(v2, v0));
+        assert_eq!(Err(ForestError::WouldCreateCycle), forest.try_link(v1, v1));
+        assert_eq!(v0, forest.find_root(v2));
+        assert_eq!(9, forest.eval(v2));
+    }
+
+    #[test]
+    fn invalid_id() {
+        let mut small: CompressedForest<usize, CloneAdd> = CompressedForest::new();
+        let mut large: CompressedForest<usize, CloneAdd> = CompressedForest::new();
+        let v0 = small.new_root(1);
+        let _ = large.new_root(2);
+        let w1 = large.new_root(3);
+
+        // ids share the same type, but `w1` is out of range in `small`
+        let w1 = Index::new(w1.into());
+        assert_eq!(Err(ForestError::InvalidId), small.try_eval(w1));
+        assert_eq!(Err(ForestError::InvalidId), small.try_link(v0, w1));
+        assert_eq!(Err(ForestError::InvalidId), small.try_update(w1, 4));
+        assert_eq!(1, small.eval(v0));
+    }
+
+    #[test]
+    fn len() {
+        let mut forest: CompressedForest<usize, CloneAdd> = CompressedForest::with_capacity(4);
+        assert!(forest.is_empty());
+        assert!(forest.capacity() >= 4);
+
+        let v0 = forest.new_root(2);
+        let v1 = forest.new_root(3);
+        forest.link(v0, v1);
+
+        assert!(!forest.is_empty());
+        assert_eq!(2, forest.len());
+        assert_eq!(Some(2), forest.node_count());
+
+        let capacity = forest.capacity();
+        forest.clear();
+
+        assert!(forest.is_empty());
+        assert_eq!(capacity, forest.capacity());
+        assert_eq!(Err(ForestError::InvalidId), forest.try_eval(v1));
+
+        forest.reserve(64);
+        let _ = forest.new_root(1);
+        forest.shrink_to(8);
+        assert!(forest.capacity() >= 8 && forest.capacity() < 64);
+        forest.shrink_to_fit();
+        assert!(forest.capacity() >= 1);
+    }
+
+    #[cfg(feature = "generations")]
+    #[test]
+    fn stale_ids() {
+        let mut forest: CompressedForest<usize, CloneAdd> = CompressedForest::new();
+        let v0 = forest.new_root(2);
+        let v1 = forest.new_root(3);
+        forest.link(v0, v1);
+        forest.clear();
+
+        let w0 = forest.new_root(4);
+        let w1 = forest.new_root(5);
+        assert_ne!(v0, w0);
+        assert_eq!(Err(ForestError::InvalidId), forest.try_eval(v1));
+        assert_eq!(Err(ForestError::InvalidId), forest.try_link(w0, v1));
+        assert_eq!(5, forest.eval(w1));
+
+        let mut merged: CompressedForest<usize, CloneAdd> = CompressedForest::new();
+        merged.clear();
+        let offset = merged.append(forest);
+        assert_eq!(None, offset.translate(v1));
+        assert_eq!(5, merged.eval(offset.translate(w1).unwrap()));
+    }
+
+    #[test]
+    fn roots() {
+        let mut forest: CompressedForest<usize, CloneAdd> = CompressedForest::new();
+        let v0 = forest.new_root(2);
+        let v1 = forest.new_root(3);
+        let v2 = forest.new_root(4);
+        let v3 = forest.new_root(5);
+
+        forest.link(v0, v1);
+        forest.link(v3, v2);
+
+        assert_eq!(vec![v0, v3], forest.roots().collect::<Vec<_>>());
+        assert_eq!(2, forest.tree_count());
+        assert!(forest.is_root(v3) && !forest.is_root(v2));
+        assert_eq!(Err(ForestError::WouldCreateCycle), forest.try_link(v1, v0));
+        assert_eq!(2, forest.tree_count());
+    }
+
+    #[test]
+    fn id_keys() {
+        let mut forest: CompressedForest<usize, CloneAdd> = CompressedForest::new();
+        let ids: Vec<_> = (0..4).map(|i| forest.new_root(i)).collect();
+        forest.link(ids[0], ids[1]);
+        forest.link(ids[2], ids[3]);
+
+        let trees: HashMap<_, _> = ids.iter().map(|&id| (id, forest.find_root(id))).collect();
+        assert_eq!(ids[2], trees[&ids[3]]);
+        assert_eq!(2, trees.values().collect::<HashSet<_>>().len());
+        assert_eq!("3 -> 2", format!("{} -> {}", ids[3], trees[&ids[3]]));
+    }
+
+    #[test]
+    fn get_or_new_root() {
+        let mut forest: CompressedForest<usize, CloneAdd> = CompressedForest::new();
+        let mut ids = HashMap::new();
+        for (parent, child) in [("x", "y"), ("y", "z"), ("w", "x")] {
+            let parent = forest.get_or_new_root(&mut ids, parent, || parent.len());
+            let child = forest.get_or_new_root(&mut ids, child, || 2);
+            forest.link(parent, child);
+        }
+
+        assert_eq!(4, forest.len());
+        assert_eq!(ids["w"], forest.find_root(ids["z"]));
+        assert_eq!(6, forest.eval(ids["z"]));
+    }
+
+    #[test]
+    fn iter() {
+        let mut forest: CompressedForest<usize, CloneAdd> = CompressedForest::new();
+        let v0 = forest.new_root(2);
+        let v1 = forest.new_root(3);
+        let v2 = forest.new_root(4);
+
+        forest.link(v0, v1);
+        forest.link(v1, v2);
+        assert_eq!(9, forest.eval(v2));
+
+        let nodes: Vec<_> = forest.iter().map(|(id, &value)| (id, value)).collect();
+        assert_eq!(vec![(v0, 2), (v1, 3), (v2, 7)], nodes);
+    }
+
+    #[test]
+    fn values_mut() {
+        let mut forest: CompressedForest<usize, CloneAdd> = CompressedForest::new();
+        let v0 = forest.new_root(2);
+        let v1 = forest.new_root(3);
+        let v2 = forest.new_root(4);
+
+        forest.link(v0, v1);
+        forest.link(v1, v2);
+        assert_eq!(9, forest.eval(v2));
+
+        forest.values_mut().for_each(|value| *value *= 10);
+        assert_eq!(90, forest.eval(v2));
+        assert_eq!(50, forest.eval(v1));
+
+        forest.for_each_value_mut(|id, value| {
+            if id == v0 {
+                *value = 0;
+            }
+        });
+        assert_eq!(70, forest.eval(v2));
+    }
+
+    #[test]
+    fn path_to_root() {
+        let mut forest: CompressedForest<usize, CloneAdd> = CompressedForest::new();
+        let v0 = forest.new_root(2);
+        let v1 = forest.new_root(3);
+        let v2 = forest.new_root(4);
+        let v3 = forest.new_root(5);
+
+        forest.link(v2, v3);
+        forest.link(v1, v2);
+        forest.link(v0, v1);
+
+        assert_eq!(vec![v3, v2, v1, v0], forest.path_to_root(v3));
+        assert_eq!(vec![v0], forest.path_to_root(v0));
+        assert_eq!((14, vec![v0, v1, v2, v3]), forest.eval_with_path(v3));
+        assert_eq!(vec![v3, v0], forest.path_to_root(v3));
+        assert_eq!((14, vec![v0, v3]), forest.eval_with_path(v3));
+    }
+
+    #[test]
+    fn stored_value() {
+        let mut forest: CompressedForest<usize, CloneAdd> = CompressedForest::new();
+        let v0 = forest.new_root(2);
+        let v1 = forest.new_root(3);
+        let v2 = forest.new_root(4);
+
+        forest.link(v0, v1);
+        forest.link(v1, v2);
+        assert_eq!(9, forest.eval(v2));
+        assert_eq!(7, *forest.stored_value(v2));
+
+        *forest.stored_value_mut(v0) = 1;
+        assert_eq!(8, forest.eval(v2));
+        *forest.stored_value_mut(v2) = 0;
+        assert_eq!(1, forest.eval(v2));
+
+        forest[v2] = 3;
+        assert_eq!(3, forest[v2]);
+        assert_eq!(4, forest.eval(v2));
+    }
+
+    #[test]
+    fn tree_members() {
+        let mut forest: CompressedForest<usize, CloneAdd> = CompressedForest::new();
+        let ids: Vec<_> = (0..6).map(|i| forest.new_root(i)).collect();
+
+        forest.link(ids[4], ids[1]);
+        forest.link(ids[1], ids[3]);
+        forest.link(ids[2], ids[5]);
+
+        let members: Vec<_> = forest.tree_members(ids[3]).collect();
+        assert_eq!(vec![ids[1], ids[3], ids[4]], members);
+        let members: Vec<_> = forest.tree_members(ids[0]).collect();
+        assert_eq!(vec![ids[0]], members);
+        assert!(forest.try_tree_members(Index::new(6)).is_err());
+    }
+
+    #[test]
+    fn tree_size() {
+        let mut forest: CompressedForest<usize, CloneAdd, u16> = CompressedForest::new();
+        let ids: Vec<_> = (0..6).map(|i| forest.new_root(i)).collect();
+
+        forest.link(ids[4], ids[1]);
+        forest.link(ids[1], ids[3]);
+        forest.link(ids[2], ids[5]);
+        assert_eq!(
+            Err(ForestError::WouldCreateCycle),
+            forest.try_link(ids[3], ids[4])
+        );
+
+        assert_eq!(3, forest.tree_size(ids[3]));
+        assert_eq!(2, forest.tree_size(ids[2]));
+        assert_eq!(1, forest.tree_size(ids[0]));
+
+        let array = forest.to_parent_array();
+        let mut copy: CompressedForest<usize, CloneAdd> =
+            CompressedForest::from_parent_array(array).unwrap();
+        assert_eq!(3, copy.tree_count());
+        for (i, size) in [1, 3, 2, 3, 3, 2].into_iter().enumerate() {
+            assert_eq!(size, copy.tree_size(Index::new(i)));
+        }
+    }
+
+    #[test]
+    fn from_values() {
+        let forest: CompressedForest<usize, CloneAdd> = (0..4).collect();
+        assert_eq!(4, forest.tree_count());
+
+        let (mut forest, ids) = CompressedForest::<usize, CloneAdd>::from_values([2, 3, 4]);
+        let ids: Vec<_> = ids.collect();
+        forest.link(ids[0], ids[1]);
+        forest.link(ids[1], ids[2]);
+        assert_eq!(9, forest.eval(ids[2]));
+
+        forest.extend([5, 6]);
+        let ids: Vec<_> = forest.extend_roots(vec![7, 8]).collect();
+        assert_eq!(vec![Index::new(5), Index::new(6)], ids);
+        assert_eq!(7, forest.len());
+        assert_eq!(5, forest.tree_count());
+    }
+
+    #[test]
+    fn append() {
+        let mut forest: CompressedForest<usize, CloneAdd> = CompressedForest::new();
+        let v0 = forest.new_root(2);
+        let v1 = forest.new_root(3);
+        forest.link(v0, v1);
+
+        let mut other: CompressedForest<usize, CloneAdd> = CompressedForest::new();
+        let w0 = other.new_root(4);
+        let w1 = other.new_root(5);
+        let w2 = other.new_root(6);
+        other.link(w0, w1);
+        other.link(w1, w2);
+        let invalid = Index::new(3);
+
+        let offset = forest.append(other);
+        let w2 = offset.translate(w2).unwrap();
+        let w0 = offset.translate(w0).unwrap();
+
+        assert_eq!(None, offset.translate(invalid));
+        assert_eq!(5, forest.len());
+        assert_eq!(2, forest.tree_count());
+        assert_eq!(15, forest.eval(w2));
+        assert_eq!(w0, forest.find_root(w2));
+        assert_eq!(3, forest.tree_size(w2));
+
+        forest.link(v1, w0);
+        assert_eq!(20, forest.eval(w2));
+    }
+
+    #[test]
+    fn split_tree() {
+        let mut forest: CompressedForest<usize, CloneAdd> = CompressedForest::new();
+        let v0 = forest.new_root(2);
+        let v1 = forest.new_root(3);
+        let v2 = forest.new_root(4);
+        let v3 = forest.new_root(5);
+        let v4 = forest.new_root(6);
+
+        forest.link(v1, v2);
+        forest.link(v0, v3);
+        forest.link(v2, v4);
+        assert_eq!(13, forest.eval(v4));
+
+        let (mut split, moved, remaining) = forest.split_tree(v4);
+
+        assert_eq!(vec![v1, v2, v4], moved);
+        assert_eq!(3, split.len());
+        assert_eq!(1, split.tree_count());
+        assert_eq!(13, split.eval(Index::new(2)));
+        assert_eq!(Index::new(0), split.find_root(Index::new(1)));
+
+        // "v0" and "v3" are now the first two nodes
+        let (w0, w3) = (remaining[0].unwrap(), remaining[3].unwrap());
+        assert_eq!(5, remaining.len());
+        assert!(remaining[1].is_none() && remaining[2].is_none() && remaining[4].is_none());
+        assert_eq!(1, usize::from(w3));
+        assert_eq!(2, forest.len());
+        assert_eq!(1, forest.tree_count());
+        assert_eq!(7, forest.eval(w3));
+        assert_eq!(w0, forest.find_root(w3));
+        assert_eq!(2, forest.tree_size(w3));
+    }
+
+    #[cfg(feature = "generations")]
+    #[test]
+    fn split_tree_stale_ids() {
+        let mut forest: CompressedForest<usize, CloneAdd> = CompressedForest::new();
+        let v0 = forest.new_root(2);
+        let v1 = forest.new_root(3);
+        let v2 = forest.new_root(4);
+        forest.link(v0, v2);
+
+        let (_, _, remaining) = forest.split_tree(v1);
+        // "v2" has the former key of "v1"
+        assert_eq!(Err(ForestError::InvalidId), forest.try_eval(v1));
+        assert_eq!(Err(ForestError::InvalidId), forest.try_eval(v2));
+        assert_eq!(6, forest.eval(remaining[2].unwrap()));
+    }
+
+    #[test]
+    fn map_values() {
+        let mut forest: CompressedForest<u32, CloneAdd> = CompressedForest::new();
+        let v0 = forest.new_root(1);
+        let v1 = forest.new_root(2);
+        let v2 = forest.new_root(3);
+        forest.link(v0, v1);
+        forest.link(v1, v2);
+        forest.eval(v2);
+
+        // exponentiation maps addition to multiplication
+        let mut forest: CompressedForest<u64, CloneMul> = forest.map_values(|v| 2u64.pow(v));
+        assert_eq!(64, forest.eval(Index::new(2)));
+        assert_eq!(8, forest.eval(Index::new(1)));
+        assert_eq!(1, forest.tree_count());
+    }
+
+    #[test]
+    fn into_operation() {
+        let mut forest: CompressedForest<usize, CloneAdd> = CompressedForest::new();
+        let v0 = forest.new_root(2);
+        let v1 = forest.new_root(3);
+        let v2 = forest.new_root(4);
+        forest.link(v1, v2);
+        forest.link(v0, v1);
+
+        let mut forest: CompressedForest<usize, CloneMul> = forest.into_operation();
+        assert_eq!(24, forest.eval(Index::new(2)));
+        assert_eq!(6, forest.eval(Index::new(1)));
+    }
+
+    #[test]
+    fn compress_all() {
+        let mut forest: CompressedForest<usize, CloneAdd> = CompressedForest::new();
+        let ids: Vec<_> = (0..8).map(|i| forest.new_root(i)).collect();
+        for i in 1..8 {
+            forest.link(ids[i - 1], ids[i]);
+        }
+
+        forest.compress_all();
+
+        for (i, &id) in ids.iter().enumerate() {
+            assert!(forest.path_to_root(id).len() <= 2);
+            assert_eq!(i * (i + 1) / 2, forest.eval(id));
+        }
+    }
+
+    #[derive(Debug)]
+    struct Concat;
+
+    impl AssociativeOperation<String> for Concat {
+        type Error = std::convert::Infallible;
+
+        fn associate(lhs: &String, rhs: &String) -> Result<String, Self::Error> {
+            Ok(format!("{lhs}{rhs}"))
+        }
+    }
+
+    // Maintains the size of every tree, indexed by roots.
+    #[derive(Debug, Default)]
+    struct TreeSizes {
+        sizes: Vec<usize>,
+        compressions: usize,
+        updates: usize,
+    }
+
+    impl<Id: Into<usize>, V> ForestObserver<Id, V> for TreeSizes {
+        fn on_new_root(&mut self, _id: Id, _value: &V) {
+            self.sizes.push(1);
+        }
+
+        fn on_link(&mut self, root_a: Id, root_b: Id) {
+            let size_b = std::mem::take(&mut self.sizes[root_b.into()]);
+            self.sizes[root_a.into()] += size_b;
+        }
+
+        fn on_update(&mut self, _root: Id, _value: &V) {
+            self.updates += 1;
+        }
+
+        fn on_compress(&mut self, _id: Id, _ancestor: Id) {
+            self.compressions += 1;
+        }
+    }
+
+    #[test]
+    fn observer() {
+        let mut forest: CompressedForest<usize, CloneAdd, usize, FullCompression, TreeSizes> =
+            CompressedForest::with_observer(TreeSizes::default());
+        let ids: Vec<_> = forest.extend_roots(0..3).collect();
+        let v3 = forest.new_root(3);
+
+        forest.link(ids[1], ids[2]);
+        forest.link(ids[0], ids[1]);
+        assert_eq!(vec![3, 0, 0, 1], forest.observer().sizes);
+
+        assert_eq!(3, forest.eval(ids[2]));
+        forest.update(ids[2], 1);
+        forest.link(v3, ids[0]);
+
+        let observer = forest.observer_mut();
+        assert_eq!(vec![0, 0, 0, 4], observer.sizes);
+        assert_eq!(1, observer.compressions);
+        assert_eq!(1, observer.updates);
+    }
+
+    // Links a chain "a" <- "b" <- ... in an arbitrary order, then checks every evaluation.
+    fn check_compression<C: Compression + std::fmt::Debug>() {
+        let mut forest: CompressedForest<String, Concat, usize, C> = CompressedForest::new();
+        let letters = "abcdefghijklmnop";
+        let ids: Vec<_> = letters
+            .chars()
+            .map(|c| forest.new_root(c.to_string()))
+            .collect();
+        for i in [7, 3, 1, 5, 2, 6, 4, 9, 8, 15, 11, 13, 10, 12, 14] {
+            forest.link(ids[i - 1], ids[i]);
+            assert!(forest.eval(ids[i]).ends_with(&letters[i - 1..=i]));
+        }
+
+        for i in (0..16).rev() {
+            assert_eq!(letters[..=i], forest.eval(ids[i]));
+            assert_eq!(ids[0], forest.find_root(ids[i]));
+        }
+
+        forest.update(ids[9], "A".to_string());
+        assert_eq!(String::from("Abcdefghijklmnop"), forest.eval(ids[15]));
+        assert_eq!(16, forest.tree_size(ids[4]));
+    }
+
+    #[cfg(feature = "stats")]
+    #[test]
+    fn stats() {
+        let mut forest: CompressedForest<usize, CloneAdd> = CompressedForest::new();
+        let ids: Vec<_> = (0..4).map(|i| forest.new_root(i)).collect();
+        for i in (1..4).rev() {
+            forest.link(ids[i - 1], ids[i]);
+        }
+        assert_eq!(
+            Err(ForestError::WouldCreateCycle),
+            forest.try_link(ids[3], ids[0])
+        );
+        forest.update(ids[2], 5);
+
+        assert_eq!(
+            Stats {
+                evals: 0,
+                links: 4,
+                updates: 1,
+                hops: 6,
+                compressions: 2,
+            },
+            forest.stats()
+        );
+
+        forest.reset_stats();
+        assert_eq!(11, forest.eval(ids[3]));
+        assert_eq!(11, forest.eval(ids[3]));
+        assert_eq!(2, forest.stats().evals);
+        assert_eq!(4, forest.stats().hops);
+        assert_eq!(0, forest.stats().compressions);
+    }
+
+    #[test]
+    fn compression_strategies() {
+        check_compression::<FullCompression>();
+        check_compression::<PathHalving>();
+        check_compression::<PathSplitting>();
+        check_compression::<NoCompression>();
+
+        let mut forest: CompressedForest<usize, CloneAdd, usize, NoCompression> =
+            CompressedForest::new();
+        let ids: Vec<_> = (0..8).map(|i| forest.new_root(i)).collect();
+        for i in (1..8).rev() {
+            forest.link(ids[i - 1], ids[i]);
+        }
+        assert_eq!(28, forest.eval(ids[7]));
+        assert_eq!(8, forest.path_to_root(ids[7]).len());
+        assert!(forest.try_eval_shared(ids[7]).is_some());
+    }
+
+    #[test]
+    fn cut() {
+        let mut forest: CompressedForest<usize, CloneAdd, usize, NoCompression> =
+            CompressedForest::new();
+        let ids: Vec<_> = (0..6).map(|i| forest.new_root(i)).collect();
+        forest.link(ids[0], ids[1]);
+        forest.link(ids[1], ids[2]);
+        forest.link(ids[2], ids[3]);
+        // linked below a node that is not a root
+        forest.link(ids[1], ids[4]);
+        forest.link(ids[4], ids[5]);
+        let values = forest.eval_all();
+
+        forest.cut(ids[1]);
+        assert_eq!(values, forest.eval_all());
+        assert_eq!(2, forest.tree_count());
+        assert_eq!(1, forest.tree_size(ids[0]));
+        assert_eq!(5, forest.tree_size(ids[5]));
+        assert_eq!(ids[1], forest.find_root(ids[3]));
+        assert_eq!(ids[1], forest.find_root(ids[5]));
+
+        forest.update_path(ids[3], 10);
+        assert_eq!(vec![0, 11, 23, 36, 15, 20], forest.eval_all());
+        forest.update(ids[1], 0);
+        assert_eq!(9, forest.eval(ids[5]));
+        assert_eq!(0, forest.eval(ids[0]));
+        forest.cut(ids[1]);
+        assert_eq!(2, forest.tree_count());
+    }
+
+    #[test]
+    fn cut_after_compress_all() {
+        let mut forest: CompressedForest<usize, CloneAdd, usize, NoCompression> =
+            CompressedForest::new();
+        let ids: Vec<_> = (1..=3).map(|i| forest.new_root(i)).collect();
+        forest.link(ids[0], ids[1]);
+        forest.link(ids[1], ids[2]);
+
+        forest.compress_all();
+        assert_eq!(3, forest.path_to_root(ids[2]).len());
+
+        forest.cut(ids[1]);
+        assert_eq!(ids[1], forest.find_root(ids[2]));
+        assert_eq!(2, forest.tree_size(ids[2]));
+        assert_eq!(1, forest.tree_size(ids[0]));
+        assert_eq!(2, forest.tree_count());
+        assert_eq!(vec![1, 3, 6], forest.eval_all());
+    }
+
+    #[test]
+    fn new_roots() {
+        let mut forest: CompressedForest<usize, CloneAdd> = CompressedForest::new();
+        let ids: Vec<_> = forest.new_roots(3).collect();
+        assert_eq!(3, forest.len());
+        assert_eq!(0, forest.eval(ids[2]));
+
+        let mut next = 0;
+        let ids: Vec<_> = forest
+            .new_roots_with(2, || {
+                next += 1;
+                next
+            })
+            .collect();
+        assert_eq!(Index::new(3), ids[0]);
+        assert_eq!(2, forest.eval(ids[1]));
+    }
+
+    #[test]
+    fn eval_many() {
+        let mut forest: CompressedForest<usize, CloneAdd> = (1..=6).collect();
+        let ids: Vec<_> = forest.roots().collect();
+        for i in 1..ids.len() {
+            forest.link(ids[i - 1], ids[i]);
+        }
+
+        assert_eq!(
+            vec![21, 1, 10, 21],
+            forest.eval_many(&[ids[5], ids[0], ids[3], ids[5]])
+        );
+        assert_eq!(
+            Err(ForestError::InvalidId),
+            forest.try_eval_many(&[ids[0], Index::new(6)])
+        );
+        assert_eq!(vec![1, 3, 6, 10, 15, 21], forest.eval_all());
+    }
+
+    #[test]
+    fn parent_array() {
+        let mut forest: CompressedForest<usize, CloneAdd> =
+            CompressedForest::from_parent_array(vec![
+                (None, 2),
+                (Some(0), 3),
+                (Some(1), 4),
+                (None, 5),
+            ])
+            .unwrap();
+        let ids: Vec<_> = (0..4).map(Index::new).collect();
+
+        assert_eq!(9, forest.eval(ids[2]));
+        assert_eq!(ids[0], forest.find_root(ids[2]));
+        forest.link(ids[2], ids[3]);
+        assert_eq!(14, forest.eval(ids[3]));
+
+        let array = forest.to_parent_array();
+        let mut copy: CompressedForest<usize, CloneAdd> =
+            CompressedForest::from_parent_array(array).unwrap();
+        for &id in &ids {
+            assert_eq!(forest.eval(id), copy.eval(id));
+        }
+
+        assert_eq!(
+            Err(ForestError::InvalidId),
+            CompressedForest::<usize, CloneAdd>::from_parent_array(vec![(None, 2), (Some(2), 3)])
+                .map(|_| ())
+        );
+        assert_eq!(
+            Err(ForestError::WouldCreateCycle),
+            CompressedForest::<usize, CloneAdd>::from_parent_array(vec![
+                (None, 2),
+                (Some(2), 3),
+                (Some(1), 4)
+            ])
+            .map(|_| ())
+        );
+    }
+
+    #[test]
+    fn to_dot() {
+        let mut forest: CompressedForest<usize, CloneAdd> = CompressedForest::new();
+        let v0 = forest.new_root(2);
+        let v1 = forest.new_root(3);
+        forest.link(v0, v1);
+
+        let expected = concat!(
+            "digraph {\n",
+            "    0 [label=\"0: 2\", peripheries=2];\n",
+            "    1 [label=\"1: 3\"];\n",
+            "    1 -> 0;\n",
+            "}"
+        );
+        assert_eq!(expected, forest.to_dot());
+    }
+
+    #[test]
+    fn display_trees() {
+        let mut forest: CompressedForest<usize, CloneAdd> = CompressedForest::new();
+        let ids: Vec<_> = (0..6).map(|i| forest.new_root(i)).collect();
+        forest.link(ids[1], ids[2]);
+        forest.link(ids[0], ids[1]);
+        forest.link(ids[0], ids[3]);
+        forest.link(ids[4], ids[5]);
+
+        let expected = concat!(
+            "0: 0\n",
+            "  1: 1\n",
+            "    2: 2\n",
+            "  3: 3\n",
+            "4: 4\n",
+            "  5: 5\n"
+        );
+        assert_eq!(expected, forest.display_trees());
+
+        let mut tree = String::new();
+        forest.fmt_tree(&mut tree, ids[5]).unwrap();
+        assert_eq!("4: 4\n  5: 5\n", tree);
+    }
+
+    #[test]
+    fn small_index() {
+        let mut forest: CompressedForest<u16, CloneAdd, u16> = CompressedForest::new();
+        let ids: Vec<_> = (0..1024).map(|_| forest.new_root(1)).collect();
+        for i in 1..ids.len() {
+            forest.link(ids[i - 1], ids[i]);
+        }
+
+        assert_eq!(1024, forest.eval(ids[1023]));
+        assert_eq!(ids[0], forest.find_root(ids[512]));
+        assert_eq!(4, std::mem::size_of::<Node<u16, u16>>());
+    }
+
+    #[test]
+    fn capacity_exceeded() {
+        let mut forest: CompressedForest<u8, CloneAdd, u16> =
+            CompressedForest::from_values(vec![0; u16::MAX.into()]).0;
+        assert_eq!(Err(ForestError::CapacityExceeded), forest.try_new_root(0));
+        let (mut other, mut ids) = CompressedForest::from_values([1]);
+        assert!(matches!(
+            forest.try_append(&mut other),
+            Err(ForestError::CapacityExceeded)
+        ));
+        assert_eq!(1, other.len());
+        assert_eq!(Ok(1), other.try_eval(ids.next().unwrap()));
+        assert!(forest.try_append(&mut CompressedForest::new()).is_ok());
+        assert_eq!(
+            Err(ForestError::CapacityExceeded),
+            CompressedForest::<u8, CloneAdd, u16>::from_parent_array(vec![(None, 0); 1 << 16])
+                .map(|forest| forest.len())
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "the forest is full")]
+    fn index_overflow() {
+        let mut forest: CompressedForest<u8, CloneAdd, u16> = CompressedForest::new();
+        for _ in 0..=u16::MAX {
+            let _ = forest.new_root(0);
+        }
     }
 }
@@ -0,0 +1,120 @@
+use std::marker::PhantomData;
+
+use crate::index::{Index, IndexType};
+use crate::operation::DefaultOperation;
+use crate::{CompressedForest, FullCompression};
+
+/// A read-only forest in which the value and the root of every node have been computed once and for all.
+///
+/// It is obtained with [`CompressedForest::try_freeze`] and identifies nodes with the ids of the forest it was frozen from.
+/// No query mutates it, so it can be shared across threads.
+///
+/// `V` is the value type associated to nodes in the forest and `O` is the associative operation that was applied when evaluating.
+/// `Ix` is the integer type used to store root indices (see [`IndexType`]) `C` and `B` are the compression strategy and the observer type of the original forest.
+#[derive(Debug, Clone)]
+pub struct FrozenForest<V, O = DefaultOperation, Ix = usize, C = FullCompression, B = ()>
+where
+    O: 'static,
+{
+    roots: Vec<Ix>,
+    values: Vec<V>,
+    _op: PhantomData<O>,
+    _compression: PhantomData<C>,
+    _observer: PhantomData<B>,
+}
+
+impl<V, O, Ix, C, B> FrozenForest<V, O, Ix, C, B>
+where
+    Ix: IndexType,
+{
+    // Assumes there are as many roots as values.
+    #[inline]
+    pub(crate) fn new(roots: Vec<Ix>, values: Vec<V>) -> Self {
+        Self {
+            roots,
+            values,
+            _op: PhantomData,
+            _compression: PhantomData,
+            _observer: PhantomData,
+        }
+    }
+
+    /// Returns the number of nodes in the forest.
+    #[inline]
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Returns `true` if the forest contains no nodes.
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Returns the value of the node identified by `id`.
+    ///
+    /// # Panics
+    /// Panics if the id does not belong to the forest.
+    #[inline]
+    #[must_use]
+    pub fn eval(&self, id: Index<CompressedForest<V, O, Ix, C, B>>) -> &V {
+        &self.values[usize::from(id)]
+    }
+
+    /// Returns the root of the tree that contains the node identified by `id`.
+    ///
+    /// # Panics
+    /// Panics if the id does not belong to the forest.
+    #[inline]
+    #[must_use]
+    pub fn find_root(
+        &self,
+        id: Index<CompressedForest<V, O, Ix, C, B>>,
+    ) -> Index<CompressedForest<V, O, Ix, C, B>> {
+        Index::with_generation(self.roots[usize::from(id)].index(), id.generation())
+    }
+
+    /// Checks whether the nodes identified by `id_a` and `id_b` belong to the same tree.
+    ///
+    /// # Panics
+    /// Panics if an id does not belong to the forest.
+    #[inline]
+    #[must_use]
+    pub fn connected(
+        &self,
+        id_a: Index<CompressedForest<V, O, Ix, C, B>>,
+        id_b: Index<CompressedForest<V, O, Ix, C, B>>,
+    ) -> bool {
+        self.find_root(id_a) == self.find_root(id_b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::operation::*;
+    use crate::{CompressedForest, EvalLinkUpdate, NewRoot};
+
+    #[test]
+    fn shared_queries() {
+        let mut forest: CompressedForest<usize, CloneAdd, u32> = CompressedForest::new();
+        let ids: Vec<_> = (0..64).map(|i| forest.new_root(i)).collect();
+        for i in 1..64 {
+            forest.link(ids[i / 2], ids[i]);
+        }
+        let v64 = forest.new_root(7);
+
+        let frozen = forest.freeze();
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    assert_eq!(63 + 31 + 15 + 7 + 3 + 1, *frozen.eval(ids[63]));
+                    assert_eq!(ids[0], frozen.find_root(ids[42]));
+                    assert!(!frozen.connected(ids[1], v64));
+                });
+            }
+        });
+        assert_eq!(65, frozen.len());
+    }
+}
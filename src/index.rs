@@ -0,0 +1,207 @@
+use std::marker::PhantomData;
+
+// A simple safe index type for identifying nodes in a forest.
+#[derive(Debug)]
+pub struct Index<F>(usize, Generation, PhantomData<F>);
+
+impl<F> Index<F> {
+    #[inline]
+    pub(crate) fn new(key: usize) -> Self {
+        Self::with_generation(key, Generation::default())
+    }
+
+    #[inline]
+    pub(crate) fn with_generation(key: usize, generation: Generation) -> Self {
+        Self(key, generation, PhantomData)
+    }
+
+    #[inline]
+    pub(crate) fn generation(self) -> Generation {
+        self.1
+    }
+}
+
+// The number of times a forest has been cleared, so that ids obtained before are detected as stale.
+// It is a zero-sized type without the `generations` feature.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) struct Generation(#[cfg(feature = "generations")] u32);
+
+impl Generation {
+    #[inline]
+    pub(crate) fn next(self) -> Self {
+        #[cfg(feature = "generations")]
+        return Self(self.0.wrapping_add(1));
+        #[cfg(not(feature = "generations"))]
+        self
+    }
+}
+
+impl<F> From<Index<F>> for usize {
+    fn from(i: Index<F>) -> usize {
+        i.0
+    }
+}
+
+impl<F> Clone for Index<F> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<F> Copy for Index<F> {}
+
+impl<F> PartialEq for Index<F> {
+    fn eq(&self, other: &Self) -> bool {
+        (self.0, self.1) == (other.0, other.1)
+    }
+}
+impl<F> Eq for Index<F> {}
+
+impl<F> PartialOrd for Index<F> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl<F> Ord for Index<F> {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (self.0, self.1).cmp(&(other.0, other.1))
+    }
+}
+
+impl<F> std::hash::Hash for Index<F> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        (self.0, self.1).hash(state);
+    }
+}
+
+// Ids are displayed as their key, the position of the node in the forest.
+impl<F> std::fmt::Display for Index<F> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// An unsigned integer type used to store node indices inside a forest.
+///
+/// Smaller types reduce the memory used by each node but bound the number of nodes a forest can hold.
+pub trait IndexType: Copy + Eq + Ord + std::fmt::Debug + 'static {
+    /// The largest index that can be stored.
+    const MAX: usize;
+
+    /// Converts a `usize` key to an index, assumes `key <= MAX`.
+    fn new(key: usize) -> Self;
+    /// Converts the index back to a `usize` key.
+    fn index(self) -> usize;
+}
+
+macro_rules! impl_index_type {
+    ($($t:ty),*) => {
+        $(
+            impl IndexType for $t {
+                const MAX: usize = <$t>::MAX as usize;
+
+                #[inline]
+                fn new(key: usize) -> Self {
+                    key as $t
+                }
+
+                #[inline]
+                fn index(self) -> usize {
+                    self as usize
+                }
+            }
+        )*
+    };
+}
+
+impl_index_type!(u16, u32, usize);
+
+/// An iterator over the ids of nodes created together, in creation order.
+#[derive(Debug, Clone)]
+pub struct Indices<F> {
+    keys: std::ops::Range<usize>,
+    generation: Generation,
+    _forest: PhantomData<F>,
+}
+
+impl<F> Indices<F> {
+    #[inline]
+    pub(crate) fn with_generation(keys: std::ops::Range<usize>, generation: Generation) -> Self {
+        Self {
+            keys,
+            generation,
+            _forest: PhantomData,
+        }
+    }
+}
+
+impl<F> Iterator for Indices<F> {
+    type Item = Index<F>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Index<F>> {
+        let generation = self.generation;
+        self.keys
+            .next()
+            .map(|key| Index::with_generation(key, generation))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.keys.size_hint()
+    }
+}
+
+impl<F> DoubleEndedIterator for Indices<F> {
+    #[inline]
+    fn next_back(&mut self) -> Option<Index<F>> {
+        let generation = self.generation;
+        self.keys
+            .next_back()
+            .map(|key| Index::with_generation(key, generation))
+    }
+}
+
+impl<F> ExactSizeIterator for Indices<F> {}
+
+impl<F> std::iter::FusedIterator for Indices<F> {}
+
+/// A translation of the ids of a forest whose nodes have been moved into another forest.
+#[derive(Debug)]
+pub struct IndexOffset<F> {
+    offset: usize,
+    len: usize,
+    // generations of the moved forest and of the forest it has been moved into
+    generations: (Generation, Generation),
+    _forest: PhantomData<F>,
+}
+
+impl<F> Clone for IndexOffset<F> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<F> Copy for IndexOffset<F> {}
+
+impl<F> IndexOffset<F> {
+    #[inline]
+    pub(crate) fn new(offset: usize, len: usize, generations: (Generation, Generation)) -> Self {
+        Self {
+            offset,
+            len,
+            generations,
+            _forest: PhantomData,
+        }
+    }
+
+    /// Translates the id of a moved node into its id in the forest it has been moved into.
+    ///
+    /// Returns `None` if the id did not belong to the moved forest.
+    #[inline]
+    #[must_use]
+    pub fn translate(&self, id: Index<F>) -> Option<Index<F>> {
+        let key: usize = id.into();
+        let (from, to) = self.generations;
+        (key < self.len && id.generation() == from)
+            .then(|| Index::with_generation(key + self.offset, to))
+    }
+}
@@ -0,0 +1,241 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use crate::index::Index;
+use crate::operation::{AssociativeOperation, DefaultOperation};
+use crate::{CompressedForest, EvalLinkUpdate, ForestError, NewRoot};
+
+/// An EVAL-LINK-UPDATE forest structure whose nodes are identified by user-provided keys.
+///
+/// Keys are mapped to the nodes of an inner [`CompressedForest`], so nodes are created with
+/// [`insert`](KeyedForest::insert) instead of [`NewRoot::new_root`].
+///
+/// `K` is the key type, `V` is the value type associated to nodes in the forest and `O` is the associative operation applied when evaluating.
+#[derive(Debug, Clone)]
+pub struct KeyedForest<K, V, O = DefaultOperation>
+where
+    O: 'static,
+{
+    forest: CompressedForest<V, O>,
+    indices: HashMap<K, usize>,
+    keys: Vec<K>,
+}
+
+impl<K, V, O> Default for KeyedForest<K, V, O>
+where
+    K: Clone + Eq + Hash,
+    V: Clone,
+    O: AssociativeOperation<V>,
+{
+    #[inline]
+    fn default() -> Self {
+        Self {
+            forest: CompressedForest::new(),
+            indices: HashMap::new(),
+            keys: vec![],
+        }
+    }
+}
+
+impl<K, V, O> KeyedForest<K, V, O>
+where
+    K: Clone + Eq + Hash,
+    V: Clone,
+    O: AssociativeOperation<V>,
+{
+    /// Creates a new empty forest.
+    #[inline]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new empty forest with a given capacity.
+    #[inline]
+    #[must_use]
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            forest: CompressedForest::with_capacity(capacity),
+            indices: HashMap::with_capacity(capacity),
+            keys: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Reserve enough space for a given number of nodes.
+    #[inline]
+    pub fn reserve(&mut self, additional: usize) {
+        self.forest.reserve(additional);
+        self.indices.reserve(additional);
+        self.keys.reserve(additional);
+    }
+
+    /// Creates a new tree root identified by `key` with the given value.
+    ///
+    /// Returns `false` and leaves the forest unchanged if the key already identifies a node.
+    pub fn insert(&mut self, key: K, value: V) -> bool {
+        if self.indices.contains_key(&key) {
+            return false;
+        }
+        let index = self.forest.new_root(value);
+        self.indices.insert(key.clone(), index.into());
+        self.keys.push(key);
+        true
+    }
+
+    /// Checks whether the key identifies a node of the forest.
+    #[inline]
+    #[must_use]
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.indices.contains_key(key)
+    }
+
+    /// Gets the entry of the given key, to create a tree root identified by the key if it does not identify a node yet.
+    #[inline]
+    pub fn entry(&mut self, key: K) -> KeyedEntry<'_, K, V, O> {
+        KeyedEntry { forest: self, key }
+    }
+
+    fn index(&self, key: &K) -> Result<Index<CompressedForest<V, O>>, ForestError<O::Error>> {
+        self.indices
+            .get(key)
+            .map(|&key| Index::new(key))
+            .ok_or(ForestError::InvalidId)
+    }
+}
+
+/// The entry of a key in a [`KeyedForest`], see [`entry`](KeyedForest::entry).
+#[derive(Debug)]
+pub struct KeyedEntry<'a, K, V, O = DefaultOperation>
+where
+    O: 'static,
+{
+    forest: &'a mut KeyedForest<K, V, O>,
+    key: K,
+}
+
+impl<K, V, O> KeyedEntry<'_, K, V, O>
+where
+    K: Clone + Eq + Hash,
+    V: Clone,
+    O: AssociativeOperation<V>,
+{
+    /// Returns the key of the entry.
+    #[inline]
+    #[must_use]
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Checks whether the key already identifies a node of the forest.
+    #[inline]
+    #[must_use]
+    pub fn is_occupied(&self) -> bool {
+        self.forest.contains_key(&self.key)
+    }
+
+    /// Creates a tree root with the given value if the key does not identify a node yet, and returns the key.
+    #[inline]
+    pub fn or_insert(self, value: V) -> K {
+        self.or_insert_with(|| value)
+    }
+
+    /// Creates a tree root with the value computed by `f` if the key does not identify a node yet,
+    /// and returns the key. `f` is only called if the root is created.
+    pub fn or_insert_with<F>(self, f: F) -> K
+    where
+        F: FnOnce() -> V,
+    {
+        if !self.is_occupied() {
+            self.forest.insert(self.key.clone(), f());
+        }
+        self.key
+    }
+}
+
+impl<K, V, O> EvalLinkUpdate for KeyedForest<K, V, O>
+where
+    K: Clone + Eq + Hash,
+    V: Clone,
+    O: 'static + AssociativeOperation<V>,
+{
+    type Id = K;
+    type Value = V;
+    type Operation = O;
+    type Error = O::Error;
+
+    #[inline]
+    fn node_count(&self) -> Option<usize> {
+        Some(self.keys.len())
+    }
+
+    fn try_find_root(&mut self, id: K) -> Result<K, ForestError<O::Error>> {
+        let index = self.index(&id)?;
+        let root: usize = self.forest.try_find_root(index)?.into();
+        Ok(self.keys[root].clone())
+    }
+
+    fn try_link(&mut self, id_a: K, id_b: K) -> Result<(), ForestError<O::Error>> {
+        let index_a = self.index(&id_a)?;
+        let index_b = self.index(&id_b)?;
+        self.forest.try_link(index_a, index_b)
+    }
+
+    fn try_update(&mut self, id: K, value: V) -> Result<(), ForestError<O::Error>> {
+        let index = self.index(&id)?;
+        self.forest.try_update(index, value)
+    }
+
+    fn try_eval(&mut self, id: K) -> Result<V, ForestError<O::Error>> {
+        let index = self.index(&id)?;
+        self.forest.try_eval(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::operation::*;
+
+    #[test]
+    fn add_forest() {
+        let mut forest: KeyedForest<&str, usize, CloneAdd> = KeyedForest::with_capacity(4);
+        assert!(forest.insert("a", 2));
+        assert!(forest.insert("b", 3));
+        assert!(forest.insert("c", 4));
+        assert!(forest.insert("d", 5));
+        assert!(!forest.insert("a", 6));
+
+        forest.link("a", "b");
+        forest.link("c", "d");
+
+        assert_eq!(5, forest.eval("b"));
+        assert_eq!(9, forest.eval("d"));
+
+        forest.link("d", "a");
+
+        assert_eq!(11, forest.eval("a"));
+        assert_eq!(14, forest.eval("b"));
+        assert_eq!("c", forest.find_root("b"));
+
+        forest.update("b", 1);
+
+        assert_eq!(11, forest.eval("b"));
+        assert_eq!(Err(ForestError::InvalidId), forest.try_eval("e"));
+    }
+
+    #[test]
+    fn entry() {
+        let mut forest: KeyedForest<String, usize, CloneAdd> = KeyedForest::new();
+        let edges = [("a", "b"), ("b", "c"), ("d", "e"), ("c", "d")];
+        for (parent, child) in edges {
+            let parent = forest.entry(parent.to_owned()).or_insert(1);
+            let child = forest.entry(child.to_owned()).or_insert_with(|| 2);
+            forest.link(parent, child);
+        }
+
+        assert!(forest.entry("e".to_owned()).is_occupied());
+        assert_eq!("f", forest.entry("f".to_owned()).key());
+        assert!(!forest.contains_key(&"f".to_owned()));
+        assert_eq!(8, forest.eval("e".to_owned()));
+    }
+}
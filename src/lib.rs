@@ -1,7 +1,8 @@
 #![warn(missing_docs)]
 
 //! This crate provides traits to describe operations on EVAL-LINK-UPDATE data structures (similar to operations defined by Tarjan in ["Applications of Path Compression on Balanced Trees.”](https://doi.org/10.1145/322154.322161)).  
-//! It also provides implementations of basic EVAL-LINK-UPDATE structures such as forest with path compression on evaluation (see [`CompressedForest`]).
+//! It also provides implementations of basic EVAL-LINK-UPDATE structures such as forest with path compression on evaluation (see [`CompressedForest`])
+//! and forest with path compression and linking by rank for group operations (see [`BalancedForest`]).
 //!
 //! ## EVAL-LINK-UPDATE Operations
 //! Suppose we have an associative operation ⊕. The three operations made available on forests are:
@@ -9,14 +10,99 @@
 //! - [`LINK`](EvalLinkUpdate::try_link)`(n, m)`: find the root of the tree that contains the node `m`, let say `r`, and link it to the node `n` (i.e `r` becomes a child of `n`)
 //! - [`UPDATE`](EvalLinkUpdate::try_update)`(n, v)`: find the root of the tree that contains the node `n`, let say `r`, and replace its value by `v`
 
+mod arg;
+pub use arg::ArgForest;
+
+mod atomic;
+pub use atomic::AtomicForest;
+
+mod balanced;
+pub use balanced::BalancedForest;
+
+mod builder;
+pub use builder::ForestBuilder;
+
+mod compression;
+pub use compression::{Compression, FullCompression, NoCompression, PathHalving, PathSplitting};
+
+/// Differential testing of forests against a naive model.
+pub mod consistency;
+
+mod concurrent;
+pub use concurrent::ConcurrentForest;
+
+mod edge;
+pub use edge::EdgeForest;
+
+mod error;
+pub use error::{EluError, ForestError};
+
+mod euler_tour;
+pub use euler_tour::EulerTourForest;
+
 mod forest;
 pub use forest::CompressedForest;
 
+mod frozen;
+pub use frozen::FrozenForest;
+
+mod index;
+pub use index::{IndexOffset, IndexType, Indices};
+
 mod node;
 
+mod keyed;
+pub use keyed::{KeyedEntry, KeyedForest};
+
+mod link_cut;
+pub use link_cut::LinkCutForest;
+
+mod naive;
+pub use naive::NaiveForest;
+
+mod observer;
+pub use observer::ForestObserver;
+
+/// Batch processing of EVAL-LINK-UPDATE operation sequences known in advance.
+pub mod offline;
+
+mod persistent;
+pub use persistent::{PersistentForest, Version};
+
+mod profiled;
+pub use profiled::{OperationProfile, Profile, ProfiledForest};
+
+mod rollback;
+pub use rollback::{Checkpoint, RollbackForest};
+
+mod soa;
+pub use soa::SoaForest;
+
+mod slot;
+pub use slot::{SlotForest, SlotIndex};
+
+mod stats;
+#[cfg(feature = "stats")]
+pub use stats::Stats;
+
+mod stateful;
+pub use stateful::StatefulForest;
+
+mod subtree;
+pub use subtree::SubtreeForest;
+
+mod sync;
+pub use sync::SyncForest;
+
 /// Collection of basic types that define standard associative operations.
 pub mod operation;
-pub use operation::AssociativeOperation;
+pub use operation::{
+    AssociativeOperation, AssociativeOperationRef, CommutativeOperation, GroupOperation,
+    IdempotentOperation, MonoidOperation, SelectionOperation,
+};
+
+/// The error type of the associative operation used by the EVAL-LINK-UPDATE structure `F`.
+pub type OperationError<F> = <F as EvalLinkUpdate>::Error;
 
 /// An EVAL-LINK-UPDATE structure.
 pub trait EvalLinkUpdate {
@@ -24,60 +110,237 @@ pub trait EvalLinkUpdate {
     type Id;
     /// The value type associated to nodes.
     type Value;
-    /// The associative operation used by [`try_eval`](EvalLinkUpdate::try_eval) and [`eval`](EvalLinkUpdate::eval).
-    type Operation: AssociativeOperation<Self::Value>;
+    /// The associative operation used by [`try_eval`](EvalLinkUpdate::try_eval) and [`eval`](EvalLinkUpdate::eval):
+    /// an [`AssociativeOperation`], or an [`AssociativeOperationRef`] for structures that hold an instance of it.
+    type Operation;
+    /// The error type of the operation.
+    type Error;
 
-    /// Creates a new tree root in the forest with the given value.
-    fn new_root(&mut self, value: Self::Value) -> Self::Id;
+    /// Returns the number of nodes in the forest, or `None` if the structure does not keep track of it.
+    #[inline]
+    fn node_count(&self) -> Option<usize> {
+        None
+    }
 
-    /// Computes the value of the node identified by `id`.
+    /// Finds the root of the tree that contains the node identified by `id`.
     ///
     /// # Errors
-    /// Will return `Err` if [`Operation::associate`](AssociativeOperation::associate) fails.
-    fn try_eval(
+    /// Will return [`ForestError::InvalidId`] if an id does not belong to the forest
+    /// and [`ForestError::Operation`] if [`Operation::associate`](AssociativeOperation::associate) fails.
+    fn try_find_root(
         &mut self,
         id: Self::Id,
-    ) -> Result<Self::Value, <Self::Operation as AssociativeOperation<Self::Value>>::Error>;
-    /// Infallible version of [`try_eval`](EvalLinkUpdate::try_eval). Requires [`Operation::Error`](EvalLinkUpdate::Operation) to be [`Infallible`](std::convert::Infallible).
+    ) -> Result<Self::Id, ForestError<OperationError<Self>>>;
+    /// Infallible version of [`try_find_root`](EvalLinkUpdate::try_find_root). Requires [`Error`](EvalLinkUpdate::Error) to be [`Infallible`](std::convert::Infallible).
+    ///
+    /// # Panics
+    /// Panics if an id does not belong to the forest.
+    #[inline]
+    fn find_root(&mut self, id: Self::Id) -> Self::Id
+    where
+        Self: EvalLinkUpdate<Error = std::convert::Infallible>,
+    {
+        self.try_find_root(id).unwrap()
+    }
+    /// Checks whether the nodes identified by `id_a` and `id_b` belong to the same tree.
+    ///
+    /// # Errors
+    /// Will return [`ForestError::InvalidId`] if an id does not belong to the forest
+    /// and [`ForestError::Operation`] if [`Operation::associate`](AssociativeOperation::associate) fails.
+    #[inline]
+    fn try_connected(
+        &mut self,
+        id_a: Self::Id,
+        id_b: Self::Id,
+    ) -> Result<bool, ForestError<OperationError<Self>>>
+    where
+        Self::Id: PartialEq,
+    {
+        let root_a = self.try_find_root(id_a)?;
+        let root_b = self.try_find_root(id_b)?;
+        Ok(root_a == root_b)
+    }
+    /// Infallible version of [`try_connected`](EvalLinkUpdate::try_connected). Requires [`Error`](EvalLinkUpdate::Error) to be [`Infallible`](std::convert::Infallible).
+    ///
+    /// # Panics
+    /// Panics if an id does not belong to the forest.
+    #[inline]
+    fn connected(&mut self, id_a: Self::Id, id_b: Self::Id) -> bool
+    where
+        Self::Id: PartialEq,
+        Self: EvalLinkUpdate<Error = std::convert::Infallible>,
+    {
+        self.try_connected(id_a, id_b).unwrap()
+    }
+
+    /// Computes the value `r` such that `eval(id_a)` ⊕ `r` is `eval(id_b)`, i.e. the inverse of the value
+    /// of the node identified by `id_a` associated with the value of the node identified by `id_b`.
+    ///
+    /// Returns `None` if both nodes do not belong to the same tree.
+    ///
+    /// # Errors
+    /// Will return [`ForestError::InvalidId`] if an id does not belong to the forest
+    /// and [`ForestError::Operation`] if [`Operation::associate`](AssociativeOperation::associate)
+    /// or [`Operation::inverse`](GroupOperation::inverse) fails.
+    fn try_eval_relative(
+        &mut self,
+        id_a: Self::Id,
+        id_b: Self::Id,
+    ) -> Result<Option<Self::Value>, ForestError<OperationError<Self>>>
+    where
+        Self::Id: Clone + PartialEq,
+        Self::Operation: GroupOperation<Self::Value, Error = Self::Error>,
+    {
+        if !self.try_connected(id_a.clone(), id_b.clone())? {
+            return Ok(None);
+        }
+        let value_a = self.try_eval(id_a)?;
+        let value_b = self.try_eval(id_b)?;
+        let inverse_a = Self::Operation::inverse(&value_a).map_err(ForestError::Operation)?;
+        Self::Operation::associate(&inverse_a, &value_b)
+            .map(Some)
+            .map_err(ForestError::Operation)
+    }
+    /// Infallible version of [`try_eval_relative`](EvalLinkUpdate::try_eval_relative). Requires [`Error`](EvalLinkUpdate::Error) to be [`Infallible`](std::convert::Infallible).
+    ///
+    /// # Panics
+    /// Panics if an id does not belong to the forest.
+    #[inline]
+    fn eval_relative(&mut self, id_a: Self::Id, id_b: Self::Id) -> Option<Self::Value>
+    where
+        Self::Id: Clone + PartialEq,
+        Self::Operation: GroupOperation<Self::Value, Error = std::convert::Infallible>,
+        Self: EvalLinkUpdate<Error = std::convert::Infallible>,
+    {
+        self.try_eval_relative(id_a, id_b).unwrap()
+    }
+
+    /// Computes the value of the node identified by `id`.
+    ///
+    /// # Errors
+    /// Will return [`ForestError::InvalidId`] if an id does not belong to the forest
+    /// and [`ForestError::Operation`] if [`Operation::associate`](AssociativeOperation::associate) fails.
+    fn try_eval(&mut self, id: Self::Id) -> Result<Self::Value, ForestError<OperationError<Self>>>;
+    /// Infallible version of [`try_eval`](EvalLinkUpdate::try_eval). Requires [`Error`](EvalLinkUpdate::Error) to be [`Infallible`](std::convert::Infallible).
+    ///
+    /// # Panics
+    /// Panics if an id does not belong to the forest.
     #[inline]
     fn eval(&mut self, id: Self::Id) -> Self::Value
     where
-        Self::Operation: AssociativeOperation<Self::Value, Error = std::convert::Infallible>,
+        Self: EvalLinkUpdate<Error = std::convert::Infallible>,
     {
         self.try_eval(id).unwrap()
     }
+    /// Computes the values of all the nodes identified by `ids`, in order.
+    ///
+    /// # Errors
+    /// Will return [`ForestError::InvalidId`] if an id does not belong to the forest
+    /// and [`ForestError::Operation`] if [`Operation::associate`](AssociativeOperation::associate) fails.
+    #[inline]
+    fn try_eval_many(
+        &mut self,
+        ids: &[Self::Id],
+    ) -> Result<Vec<Self::Value>, ForestError<OperationError<Self>>>
+    where
+        Self::Id: Clone,
+    {
+        ids.iter().map(|id| self.try_eval(id.clone())).collect()
+    }
+    /// Infallible version of [`try_eval_many`](EvalLinkUpdate::try_eval_many). Requires [`Error`](EvalLinkUpdate::Error) to be [`Infallible`](std::convert::Infallible).
+    ///
+    /// # Panics
+    /// Panics if an id does not belong to the forest.
+    #[inline]
+    fn eval_many(&mut self, ids: &[Self::Id]) -> Vec<Self::Value>
+    where
+        Self::Id: Clone,
+        Self: EvalLinkUpdate<Error = std::convert::Infallible>,
+    {
+        self.try_eval_many(ids).unwrap()
+    }
     /// Links the root of the tree that contains the node identified by `id_b` to the node identified by `id_a`.
     ///
     /// # Errors
-    /// Will return `Err` if [`Operation::associate`](AssociativeOperation::associate) fails.
+    /// Will return [`ForestError::InvalidId`] if an id does not belong to the forest,
+    /// [`ForestError::WouldCreateCycle`] if both nodes already belong to the same tree
+    /// and [`ForestError::Operation`] if [`Operation::associate`](AssociativeOperation::associate) fails.
     fn try_link(
         &mut self,
         id_a: Self::Id,
         id_b: Self::Id,
-    ) -> Result<(), <Self::Operation as AssociativeOperation<Self::Value>>::Error>;
-    /// Infallible version of [`try_link`](EvalLinkUpdate::try_link). Requires [`Operation::Error`](EvalLinkUpdate::Operation) to be [`Infallible`](std::convert::Infallible).
+    ) -> Result<(), ForestError<OperationError<Self>>>;
+    /// Infallible version of [`try_link`](EvalLinkUpdate::try_link). Requires [`Error`](EvalLinkUpdate::Error) to be [`Infallible`](std::convert::Infallible).
+    ///
+    /// # Panics
+    /// Panics if an id does not belong to the forest or if both nodes already belong to the same tree.
     #[inline]
     fn link(&mut self, id_a: Self::Id, id_b: Self::Id)
     where
-        Self::Operation: AssociativeOperation<Self::Value, Error = std::convert::Infallible>,
+        Self: EvalLinkUpdate<Error = std::convert::Infallible>,
     {
         self.try_link(id_a, id_b).unwrap();
     }
     /// Updates the value of the root of the tree that contains the node identified by `id`.
     ///
     /// # Errors
-    /// Will return `Err` if [`Operation::associate`](AssociativeOperation::associate) fails.
+    /// Will return [`ForestError::InvalidId`] if an id does not belong to the forest
+    /// and [`ForestError::Operation`] if [`Operation::associate`](AssociativeOperation::associate) fails.
     fn try_update(
         &mut self,
         id: Self::Id,
         value: Self::Value,
-    ) -> Result<(), <Self::Operation as AssociativeOperation<Self::Value>>::Error>;
-    /// Infallible version of [`try_update`](EvalLinkUpdate::try_update). Requires [`Operation::Error`](EvalLinkUpdate::Operation) to be [`Infallible`](std::convert::Infallible).
+    ) -> Result<(), ForestError<OperationError<Self>>>;
+    /// Infallible version of [`try_update`](EvalLinkUpdate::try_update). Requires [`Error`](EvalLinkUpdate::Error) to be [`Infallible`](std::convert::Infallible).
+    ///
+    /// # Panics
+    /// Panics if an id does not belong to the forest.
     #[inline]
     fn update(&mut self, id: Self::Id, value: Self::Value)
     where
-        Self::Operation: AssociativeOperation<Self::Value, Error = std::convert::Infallible>,
+        Self: EvalLinkUpdate<Error = std::convert::Infallible>,
     {
         self.try_update(id, value).unwrap();
     }
 }
+
+/// An EVAL-LINK-UPDATE structure that generates the ids of its nodes.
+pub trait NewRoot: EvalLinkUpdate {
+    /// Creates a new tree root in the forest with the given value.
+    #[must_use]
+    fn new_root(&mut self, value: Self::Value) -> Self::Id;
+
+    /// Returns the id mapped to `key` in `ids`, or creates a new tree root with the value computed by `value`
+    /// and maps `key` to its id.
+    ///
+    /// This builds a forest incrementally from external keys, see [`KeyedForest`] for a forest identified by keys.
+    fn get_or_new_root<K, F>(
+        &mut self,
+        ids: &mut std::collections::HashMap<K, Self::Id>,
+        key: K,
+        value: F,
+    ) -> Self::Id
+    where
+        K: Eq + std::hash::Hash,
+        Self::Id: Clone,
+        F: FnOnce() -> Self::Value,
+    {
+        ids.entry(key)
+            .or_insert_with(|| self.new_root(value()))
+            .clone()
+    }
+}
+
+/// An EVAL-LINK-UPDATE structure that can evaluate some nodes without being modified.
+pub trait SharedEval: EvalLinkUpdate {
+    /// Computes the value of the node identified by `id` if it can be done without modifying the structure
+    /// (e.g. without compressing a path), returns `None` otherwise.
+    ///
+    /// # Errors
+    /// Will return `Some(Err(_))` with [`ForestError::InvalidId`] if the id does not belong to the forest
+    /// and [`ForestError::Operation`] if [`Operation::associate`](AssociativeOperation::associate) fails.
+    fn try_eval_shared(
+        &self,
+        id: Self::Id,
+    ) -> Option<Result<Self::Value, ForestError<OperationError<Self>>>>;
+}
@@ -0,0 +1,500 @@
+use std::convert::Infallible;
+use std::marker::PhantomData;
+
+use crate::index::Index;
+use crate::operation::{AssociativeOperation, DefaultOperation};
+use crate::{EvalLinkUpdate, ForestError, NewRoot};
+
+#[derive(Debug, Clone)]
+struct LinkCutNode<V> {
+    // parent in the splay tree, or path-parent for the root of a splay tree
+    parent: Option<usize>,
+    left: Option<usize>,
+    right: Option<usize>,
+    value: V,
+    // product of the values of the splay subtree, in order
+    aggregate: V,
+    // product of the values of the splay subtree, in reverse order
+    reverse_aggregate: V,
+    // the children of the node are still to be swapped, aggregates already account for it
+    reversed: bool,
+}
+
+/// A link-cut tree (Sleator and Tarjan's dynamic trees) forest structure.
+///
+/// Each tree is decomposed into preferred paths stored in splay trees that maintain the product of their values.
+/// All operations run in amortized `O(log n)` time and, unlike path compression based structures, edges can be removed
+/// with [`try_cut`](LinkCutForest::try_cut) and trees can be re-rooted with [`try_evert`](LinkCutForest::try_evert).
+///
+/// Aggregates are recomputed while splay trees are restructured, so only infallible operations are supported.
+///
+/// `V` is the value type associated to nodes in the forest and `O` is the associative operation applied when evaluating.
+#[derive(Debug, Clone)]
+pub struct LinkCutForest<V, O = DefaultOperation>
+where
+    O: 'static,
+{
+    nodes: Vec<LinkCutNode<V>>,
+    _op: PhantomData<O>,
+}
+
+impl<V, O> Default for LinkCutForest<V, O>
+where
+    V: Clone,
+    O: AssociativeOperation<V, Error = Infallible>,
+{
+    #[inline]
+    fn default() -> Self {
+        Self {
+            nodes: vec![],
+            _op: PhantomData,
+        }
+    }
+}
+
+impl<V, O> LinkCutForest<V, O>
+where
+    V: Clone,
+    O: AssociativeOperation<V, Error = Infallible>,
+{
+    /// Creates a new empty forest.
+    #[inline]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new empty forest with a given capacity.
+    #[inline]
+    #[must_use]
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            nodes: Vec::with_capacity(capacity),
+            _op: PhantomData,
+        }
+    }
+
+    /// Reserve enough space for a given number of nodes.
+    #[inline]
+    pub fn reserve(&mut self, additional: usize) {
+        self.nodes.reserve(additional);
+    }
+
+    /// Cuts the node identified by `id` from its parent, making it the root of a new tree.
+    ///
+    /// Values are left untouched, so nodes of the new tree are no longer evaluated with the values of their former ancestors.
+    /// Does nothing if the node is already a root.
+    ///
+    /// # Errors
+    /// Will return [`ForestError::InvalidId`] if the id does not belong to the forest.
+    pub fn try_cut(&mut self, id: Index<Self>) -> Result<(), ForestError<Infallible>> {
+        let key = self.check(id)?;
+        self.access(key);
+        if let Some(left_key) = self.nodes[key].left.take() {
+            self.nodes[left_key].parent = None;
+            self.pull(key);
+        }
+        Ok(())
+    }
+
+    /// Infallible version of [`try_cut`](LinkCutForest::try_cut).
+    ///
+    /// # Panics
+    /// Panics if the id does not belong to the forest.
+    #[inline]
+    pub fn cut(&mut self, id: Index<Self>) {
+        self.try_cut(id).unwrap();
+    }
+
+    /// Makes the node identified by `id` the root of its tree, reversing the path from the former root to the node.
+    ///
+    /// Values stay attached to their nodes, so nodes are then evaluated along the paths from the new root.
+    ///
+    /// # Errors
+    /// Will return [`ForestError::InvalidId`] if the id does not belong to the forest.
+    pub fn try_evert(&mut self, id: Index<Self>) -> Result<(), ForestError<Infallible>> {
+        let key = self.check(id)?;
+        self.access(key);
+        self.toggle(key);
+        Ok(())
+    }
+
+    /// Infallible version of [`try_evert`](LinkCutForest::try_evert).
+    ///
+    /// # Panics
+    /// Panics if the id does not belong to the forest.
+    #[inline]
+    pub fn evert(&mut self, id: Index<Self>) {
+        self.try_evert(id).unwrap();
+    }
+
+    fn check(&self, id: Index<Self>) -> Result<usize, ForestError<Infallible>> {
+        let key: usize = id.into();
+        if key < self.nodes.len() {
+            Ok(key)
+        } else {
+            Err(ForestError::InvalidId)
+        }
+    }
+
+    fn associate(lhs: &V, rhs: &V) -> V {
+        match O::associate(lhs, rhs) {
+            Ok(value) => value,
+            Err(e) => match e {},
+        }
+    }
+
+    fn pull(&mut self, key: usize) {
+        let node = &self.nodes[key];
+        let mut aggregate = node.value.clone();
+        let mut reverse_aggregate = node.value.clone();
+        if let Some(left_key) = node.left {
+            let left = &self.nodes[left_key];
+            aggregate = Self::associate(&left.aggregate, &aggregate);
+            reverse_aggregate = Self::associate(&reverse_aggregate, &left.reverse_aggregate);
+        }
+        if let Some(right_key) = node.right {
+            let right = &self.nodes[right_key];
+            aggregate = Self::associate(&aggregate, &right.aggregate);
+            reverse_aggregate = Self::associate(&right.reverse_aggregate, &reverse_aggregate);
+        }
+        let node = &mut self.nodes[key];
+        node.aggregate = aggregate;
+        node.reverse_aggregate = reverse_aggregate;
+    }
+
+    // Reverses the order of the splay subtree.
+    fn toggle(&mut self, key: usize) {
+        let node = &mut self.nodes[key];
+        std::mem::swap(&mut node.aggregate, &mut node.reverse_aggregate);
+        node.reversed = !node.reversed;
+    }
+
+    // Swaps the children of the node if they are still to be swapped.
+    fn push(&mut self, key: usize) {
+        let node = &mut self.nodes[key];
+        if node.reversed {
+            node.reversed = false;
+            std::mem::swap(&mut node.left, &mut node.right);
+            let (left, right) = (node.left, node.right);
+            for child_key in [left, right].into_iter().flatten() {
+                self.toggle(child_key);
+            }
+        }
+    }
+
+    fn is_splay_root(&self, key: usize) -> bool {
+        match self.nodes[key].parent {
+            None => true,
+            Some(parent_key) => {
+                let parent = &self.nodes[parent_key];
+                parent.left != Some(key) && parent.right != Some(key)
+            }
+        }
+    }
+
+    fn rotate(&mut self, key: usize) {
+        // assume it's not the root of its splay tree
+        let parent_key = self.nodes[key].parent.unwrap();
+        let grand_parent_key = self.nodes[parent_key].parent;
+        let parent_is_root = self.is_splay_root(parent_key);
+
+        if self.nodes[parent_key].left == Some(key) {
+            let moved = self.nodes[key].right;
+            self.nodes[parent_key].left = moved;
+            if let Some(moved_key) = moved {
+                self.nodes[moved_key].parent = Some(parent_key);
+            }
+            self.nodes[key].right = Some(parent_key);
+        } else {
+            let moved = self.nodes[key].left;
+            self.nodes[parent_key].right = moved;
+            if let Some(moved_key) = moved {
+                self.nodes[moved_key].parent = Some(parent_key);
+            }
+            self.nodes[key].left = Some(parent_key);
+        }
+        self.nodes[parent_key].parent = Some(key);
+        // keep the path-parent pointer if the parent was the root of its splay tree
+        self.nodes[key].parent = grand_parent_key;
+        if !parent_is_root {
+            let grand_parent = &mut self.nodes[grand_parent_key.unwrap()];
+            if grand_parent.left == Some(parent_key) {
+                grand_parent.left = Some(key);
+            } else {
+                grand_parent.right = Some(key);
+            }
+        }
+
+        self.pull(parent_key);
+        self.pull(key);
+    }
+
+    fn splay(&mut self, key: usize) {
+        // pending reversals are pushed from the root of the splay tree down to the node
+        let mut path = vec![key];
+        let mut current = key;
+        while !self.is_splay_root(current) {
+            current = self.nodes[current].parent.unwrap();
+            path.push(current);
+        }
+        for &path_key in path.iter().rev() {
+            self.push(path_key);
+        }
+
+        while !self.is_splay_root(key) {
+            let parent_key = self.nodes[key].parent.unwrap();
+            if !self.is_splay_root(parent_key) {
+                let grand_parent_key = self.nodes[parent_key].parent.unwrap();
+                let zig_zig = (self.nodes[grand_parent_key].left == Some(parent_key))
+                    == (self.nodes[parent_key].left == Some(key));
+                if zig_zig {
+                    self.rotate(parent_key);
+                } else {
+                    self.rotate(key);
+                }
+            }
+            self.rotate(key);
+        }
+    }
+
+    // Makes the path from the root to `key` preferred, `key` ends up at the root of its splay tree with no right child.
+    fn access(&mut self, key: usize) {
+        let mut last = None;
+        let mut current = Some(key);
+        while let Some(current_key) = current {
+            self.splay(current_key);
+            self.nodes[current_key].right = last;
+            self.pull(current_key);
+            last = Some(current_key);
+            current = self.nodes[current_key].parent;
+        }
+        self.splay(key);
+    }
+
+    fn root_key(&mut self, key: usize) -> usize {
+        self.access(key);
+        let mut root_key = key;
+        while let Some(left_key) = self.nodes[root_key].left {
+            root_key = left_key;
+            self.push(root_key);
+        }
+        self.splay(root_key);
+        root_key
+    }
+}
+
+impl<V, O> EvalLinkUpdate for LinkCutForest<V, O>
+where
+    V: Clone,
+    O: 'static + AssociativeOperation<V, Error = Infallible>,
+{
+    type Id = Index<Self>;
+    type Value = V;
+    type Operation = O;
+    type Error = O::Error;
+
+    #[inline]
+    fn node_count(&self) -> Option<usize> {
+        Some(self.nodes.len())
+    }
+
+    fn try_find_root(&mut self, id: Index<Self>) -> Result<Index<Self>, ForestError<Infallible>> {
+        let key = self.check(id)?;
+        Ok(Index::new(self.root_key(key)))
+    }
+
+    fn try_link(
+        &mut self,
+        id_a: Index<Self>,
+        id_b: Index<Self>,
+    ) -> Result<(), ForestError<Infallible>> {
+        let id_a = self.check(id_a)?;
+        let id_b = self.check(id_b)?;
+
+        let root_b_key = self.root_key(id_b);
+        if self.root_key(id_a) == root_b_key {
+            return Err(ForestError::WouldCreateCycle);
+        }
+
+        // the root is alone in its splay tree after an access: turn it into a path-parent pointer
+        self.access(root_b_key);
+        self.nodes[root_b_key].parent = Some(id_a);
+        Ok(())
+    }
+
+    fn try_update(&mut self, id: Index<Self>, value: V) -> Result<(), ForestError<Infallible>> {
+        let key = self.check(id)?;
+        // the root is splayed, so no other aggregate depends on its value
+        let root_key = self.root_key(key);
+        self.nodes[root_key].value = value;
+        self.pull(root_key);
+        Ok(())
+    }
+
+    fn try_eval(&mut self, id: Index<Self>) -> Result<V, ForestError<Infallible>> {
+        let key = self.check(id)?;
+        self.access(key);
+        Ok(self.nodes[key].aggregate.clone())
+    }
+}
+
+impl<V, O> NewRoot for LinkCutForest<V, O>
+where
+    V: Clone,
+    O: 'static + AssociativeOperation<V, Error = Infallible>,
+{
+    fn new_root(&mut self, value: V) -> Index<Self> {
+        let index = self.nodes.len();
+        self.nodes.push(LinkCutNode {
+            parent: None,
+            left: None,
+            right: None,
+            aggregate: value.clone(),
+            reverse_aggregate: value.clone(),
+            reversed: false,
+            value,
+        });
+        Index::new(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::operation::*;
+
+    // Concatenation of byte strings, a non-commutative operation.
+    #[derive(Debug)]
+    struct Concat;
+
+    impl AssociativeOperation<Vec<u8>> for Concat {
+        type Error = Infallible;
+
+        fn associate(lhs: &Vec<u8>, rhs: &Vec<u8>) -> Result<Vec<u8>, Self::Error> {
+            Ok([lhs.as_slice(), rhs.as_slice()].concat())
+        }
+    }
+
+    #[test]
+    fn add_forest() {
+        let mut forest: LinkCutForest<usize, CloneAdd> = LinkCutForest::with_capacity(4);
+        let v0 = forest.new_root(2);
+        let v1 = forest.new_root(3);
+        let v2 = forest.new_root(4);
+        let v3 = forest.new_root(5);
+
+        forest.link(v0, v1);
+        forest.link(v2, v3);
+
+        assert_eq!(5, forest.eval(v1));
+        assert_eq!(9, forest.eval(v3));
+
+        forest.link(v3, v0);
+
+        assert_eq!(11, forest.eval(v0));
+        assert_eq!(14, forest.eval(v1));
+
+        forest.cut(v0);
+
+        assert_eq!(v0, forest.find_root(v1));
+        assert_eq!(5, forest.eval(v1));
+        assert_eq!(9, forest.eval(v3));
+        assert!(!forest.connected(v1, v3));
+    }
+
+    #[test]
+    fn evert() {
+        let mut forest: LinkCutForest<Vec<u8>, Concat> = LinkCutForest::with_capacity(4);
+        let v0 = forest.new_root(b"a".to_vec());
+        let v1 = forest.new_root(b"b".to_vec());
+        let v2 = forest.new_root(b"c".to_vec());
+        let v3 = forest.new_root(b"d".to_vec());
+
+        forest.link(v0, v1);
+        forest.link(v1, v2);
+        forest.link(v1, v3);
+
+        assert_eq!(b"abc".to_vec(), forest.eval(v2));
+
+        forest.evert(v2);
+
+        assert_eq!(v2, forest.find_root(v0));
+        assert_eq!(b"cba".to_vec(), forest.eval(v0));
+        assert_eq!(b"cbd".to_vec(), forest.eval(v3));
+
+        forest.cut(v1);
+
+        assert_eq!(b"bd".to_vec(), forest.eval(v3));
+        assert_eq!(b"ba".to_vec(), forest.eval(v0));
+    }
+
+    #[test]
+    fn same_as_parent_array() {
+        let n = 48;
+        let mut forest: LinkCutForest<Vec<u8>, Concat> = LinkCutForest::new();
+        let ids: Vec<_> = (0..n).map(|i| forest.new_root(vec![i as u8])).collect();
+        let mut parents: Vec<Option<usize>> = vec![None; n];
+        let mut values: Vec<Vec<u8>> = (0..n).map(|i| vec![i as u8]).collect();
+
+        let root = |parents: &[Option<usize>], mut key: usize| {
+            while let Some(parent_key) = parents[key] {
+                key = parent_key;
+            }
+            key
+        };
+
+        let mut state: usize = 3;
+        let mut next = |bound: usize| {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 33) % bound
+        };
+
+        for _ in 0..1024 {
+            let a = next(n);
+            let b = next(n);
+            match next(5) {
+                0 => {
+                    let linked = forest.try_link(ids[a], ids[b]);
+                    let root_b = root(&parents, b);
+                    if root(&parents, a) == root_b {
+                        assert_eq!(Err(ForestError::WouldCreateCycle), linked);
+                    } else {
+                        assert_eq!(Ok(()), linked);
+                        parents[root_b] = Some(a);
+                    }
+                }
+                1 => {
+                    forest.cut(ids[a]);
+                    parents[a] = None;
+                }
+                2 => {
+                    forest.evert(ids[a]);
+                    let (mut previous, mut current) = (None, Some(a));
+                    while let Some(key) = current {
+                        current = parents[key];
+                        parents[key] = previous;
+                        previous = Some(key);
+                    }
+                }
+                3 => {
+                    let value = vec![b as u8, b as u8];
+                    forest.update(ids[a], value.clone());
+                    values[root(&parents, a)] = value;
+                }
+                _ => {}
+            }
+
+            let mut key = a;
+            let mut expected = values[key].clone();
+            while let Some(parent_key) = parents[key] {
+                expected = [values[parent_key].as_slice(), expected.as_slice()].concat();
+                key = parent_key;
+            }
+            assert_eq!(expected, forest.eval(ids[a]));
+            assert_eq!(key, usize::from(forest.find_root(ids[a])));
+        }
+    }
+}
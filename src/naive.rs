@@ -0,0 +1,339 @@
+use std::marker::PhantomData;
+
+use crate::index::Index;
+use crate::node::Node;
+use crate::operation::{AssociativeOperation, DefaultOperation};
+use crate::{EvalLinkUpdate, ForestError, NewRoot, SharedEval};
+
+/// A reference EVAL-LINK-UPDATE forest structure that performs no path compression.
+///
+/// Nodes keep their actual parent and their own value: every evaluation walks the whole path from the root,
+/// which makes this structure slow but obviously correct. It is mostly useful for debugging and testing other implementations.
+///
+/// `V` is the value type associated to nodes in the forest and `O` is the associative operation applied when evaluating.
+#[derive(Debug, Clone)]
+pub struct NaiveForest<V, O = DefaultOperation>
+where
+    O: 'static,
+{
+    nodes: Vec<Node<V>>,
+    _op: PhantomData<O>,
+}
+
+impl<V, O> Default for NaiveForest<V, O>
+where
+    V: Clone,
+    O: AssociativeOperation<V>,
+{
+    #[inline]
+    fn default() -> Self {
+        Self {
+            nodes: vec![],
+            _op: PhantomData,
+        }
+    }
+}
+
+impl<V, O> NaiveForest<V, O>
+where
+    V: Clone,
+    O: AssociativeOperation<V>,
+{
+    /// Creates a new empty forest.
+    #[inline]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new empty forest with a given capacity.
+    #[inline]
+    #[must_use]
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            nodes: Vec::with_capacity(capacity),
+            _op: PhantomData,
+        }
+    }
+
+    /// Reserve enough space for a given number of nodes.
+    #[inline]
+    pub fn reserve(&mut self, additional: usize) {
+        self.nodes.reserve(additional);
+    }
+
+    /// Replaces the value `v` of every node on the path from the root of the tree to the node identified by `id`
+    /// by `v` ⊕ `delta`.
+    ///
+    /// Unlike [`try_update`](EvalLinkUpdate::try_update), this changes the evaluation of every node below the path.
+    /// Forests with path compression do not keep the nodes of the path, which is why this operation is only available
+    /// here and on forests without compression (see [`CompressedForest::try_update_path`](crate::CompressedForest::try_update_path)).
+    ///
+    /// # Errors
+    /// Will return [`ForestError::InvalidId`] if the id does not belong to the forest
+    /// and [`ForestError::Operation`] if [`O::associate`](AssociativeOperation::associate) fails.
+    pub fn try_update_path(
+        &mut self,
+        id: Index<Self>,
+        delta: V,
+    ) -> Result<(), ForestError<O::Error>> {
+        // every value is computed before any change so that a failure leaves the forest untouched
+        let mut values = vec![];
+        let mut current = Some(self.check(id)?);
+        while let Some(key) = current {
+            let node = &self.nodes[key];
+            values.push((
+                key,
+                O::associate(node.value(), &delta).map_err(ForestError::Operation)?,
+            ));
+            current = node.parent();
+        }
+        for (key, value) in values {
+            self.nodes[key].set_value(value);
+        }
+        Ok(())
+    }
+
+    /// Cuts the node identified by `id` from its parent, making it the root of a new tree whose value is the
+    /// current value of the node. The value of every node is left unchanged.
+    ///
+    /// Does nothing if the node is already a root.
+    ///
+    /// # Errors
+    /// Will return [`ForestError::InvalidId`] if the id does not belong to the forest
+    /// and [`ForestError::Operation`] if [`O::associate`](AssociativeOperation::associate) fails.
+    pub fn try_cut(&mut self, id: Index<Self>) -> Result<(), ForestError<O::Error>> {
+        let key = self.check(id)?;
+        if self.nodes[key].is_root() {
+            return Ok(());
+        }
+        let value = self.eval_key(key)?;
+        let node = &mut self.nodes[key];
+        node.set_value(value);
+        node.set_root();
+        Ok(())
+    }
+
+    fn check(&self, id: Index<Self>) -> Result<usize, ForestError<O::Error>> {
+        let key: usize = id.into();
+        if key < self.nodes.len() {
+            Ok(key)
+        } else {
+            Err(ForestError::InvalidId)
+        }
+    }
+
+    fn root_key(&self, mut key: usize) -> usize {
+        while let Some(parent_key) = self.nodes[key].parent() {
+            key = parent_key;
+        }
+        key
+    }
+
+    fn eval_key(&self, mut key: usize) -> Result<V, ForestError<O::Error>> {
+        let mut path = vec![];
+        while let Some(parent_key) = self.nodes[key].parent() {
+            path.push(key);
+            key = parent_key;
+        }
+
+        path.iter()
+            .rev()
+            .try_fold(self.nodes[key].value().clone(), |value, &child_key| {
+                O::associate(&value, self.nodes[child_key].value()).map_err(ForestError::Operation)
+            })
+    }
+}
+
+impl<V, O> NaiveForest<V, O>
+where
+    V: Clone,
+    O: AssociativeOperation<V, Error = std::convert::Infallible>,
+{
+    /// Infallible version of [`try_update_path`](NaiveForest::try_update_path).
+    ///
+    /// # Panics
+    /// Panics if the id does not belong to the forest.
+    #[inline]
+    pub fn update_path(&mut self, id: Index<Self>, delta: V) {
+        self.try_update_path(id, delta).unwrap();
+    }
+
+    /// Infallible version of [`try_cut`](NaiveForest::try_cut).
+    ///
+    /// # Panics
+    /// Panics if the id does not belong to the forest.
+    #[inline]
+    pub fn cut(&mut self, id: Index<Self>) {
+        self.try_cut(id).unwrap();
+    }
+}
+
+impl<V, O> EvalLinkUpdate for NaiveForest<V, O>
+where
+    V: Clone,
+    O: 'static + AssociativeOperation<V>,
+{
+    type Id = Index<Self>;
+    type Value = V;
+    type Operation = O;
+    type Error = O::Error;
+
+    #[inline]
+    fn node_count(&self) -> Option<usize> {
+        Some(self.nodes.len())
+    }
+
+    fn try_find_root(&mut self, id: Index<Self>) -> Result<Index<Self>, ForestError<O::Error>> {
+        let key = self.check(id)?;
+        Ok(Index::new(self.root_key(key)))
+    }
+
+    fn try_link(
+        &mut self,
+        id_a: Index<Self>,
+        id_b: Index<Self>,
+    ) -> Result<(), ForestError<O::Error>> {
+        let id_a = self.check(id_a)?;
+        let id_b = self.check(id_b)?;
+
+        let root_b_key = self.root_key(id_b);
+        if self.root_key(id_a) == root_b_key {
+            return Err(ForestError::WouldCreateCycle);
+        }
+
+        self.nodes[root_b_key].set_parent(id_a);
+        Ok(())
+    }
+
+    fn try_update(&mut self, id: Index<Self>, value: V) -> Result<(), ForestError<O::Error>> {
+        let key = self.check(id)?;
+        let root_key = self.root_key(key);
+        self.nodes[root_key].set_value(value);
+        Ok(())
+    }
+
+    fn try_eval(&mut self, id: Index<Self>) -> Result<V, ForestError<O::Error>> {
+        self.eval_key(self.check(id)?)
+    }
+}
+
+impl<V, O> SharedEval for NaiveForest<V, O>
+where
+    V: Clone,
+    O: 'static + AssociativeOperation<V>,
+{
+    #[inline]
+    fn try_eval_shared(&self, id: Index<Self>) -> Option<Result<V, ForestError<O::Error>>> {
+        Some(self.check(id).and_then(|key| self.eval_key(key)))
+    }
+}
+
+impl<V, O> NewRoot for NaiveForest<V, O>
+where
+    V: Clone,
+    O: 'static + AssociativeOperation<V>,
+{
+    fn new_root(&mut self, value: V) -> Index<Self> {
+        let index = self.nodes.len();
+        self.nodes.push(Node::new_root(value));
+        Index::new(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::operation::*;
+
+    #[test]
+    fn add_forest() {
+        let mut forest: NaiveForest<usize, CloneAdd> = NaiveForest::with_capacity(4);
+        let v0 = forest.new_root(2);
+        let v1 = forest.new_root(3);
+        let v2 = forest.new_root(4);
+        let v3 = forest.new_root(5);
+
+        forest.link(v0, v1);
+        forest.link(v2, v3);
+
+        assert_eq!(5, forest.eval(v1));
+        assert_eq!(9, forest.eval(v3));
+
+        forest.link(v3, v0);
+
+        assert_eq!(11, forest.eval(v0));
+        assert_eq!(14, forest.eval(v1));
+        assert_eq!(v2, forest.find_root(v1));
+
+        forest.update(v1, 1);
+
+        assert_eq!(11, forest.eval(v1));
+        assert_eq!(Err(ForestError::WouldCreateCycle), forest.try_link(v1, v2));
+    }
+
+    #[test]
+    fn update_path() {
+        let mut forest: NaiveForest<usize, CloneAdd> = NaiveForest::with_capacity(4);
+        let v0 = forest.new_root(2);
+        let v1 = forest.new_root(3);
+        let v2 = forest.new_root(4);
+        let v3 = forest.new_root(5);
+
+        forest.link(v0, v1);
+        forest.link(v1, v2);
+        forest.link(v0, v3);
+
+        forest.update_path(v1, 10);
+
+        assert_eq!(12, forest.eval(v0));
+        assert_eq!(25, forest.eval(v1));
+        assert_eq!(29, forest.eval(v2));
+        assert_eq!(17, forest.eval(v3));
+        assert_eq!(
+            Err(ForestError::InvalidId),
+            forest.try_update_path(Index::new(4), 1)
+        );
+    }
+
+    #[test]
+    fn failed_update_path() {
+        let mut forest: NaiveForest<u8, CheckedAdd> = NaiveForest::new();
+        let v0 = forest.new_root(200);
+        let v1 = forest.new_root(10);
+        forest.try_link(v0, v1).unwrap();
+
+        // "v1" can be updated, not "v0"
+        assert_eq!(
+            Err(ForestError::Operation(Overflow)),
+            forest.try_update_path(v1, 100)
+        );
+        assert_eq!(Ok(200), forest.try_eval(v0));
+        assert_eq!(Ok(210), forest.try_eval(v1));
+    }
+
+    #[test]
+    fn cut() {
+        let mut forest: NaiveForest<usize, CloneAdd> = NaiveForest::with_capacity(4);
+        let v0 = forest.new_root(2);
+        let v1 = forest.new_root(3);
+        let v2 = forest.new_root(4);
+        let v3 = forest.new_root(5);
+
+        forest.link(v0, v1);
+        forest.link(v1, v2);
+        forest.link(v0, v3);
+
+        forest.cut(v1);
+        forest.cut(v0);
+
+        assert_eq!(v1, forest.find_root(v2));
+        assert_eq!(5, forest.eval(v1));
+        assert_eq!(9, forest.eval(v2));
+        assert_eq!(7, forest.eval(v3));
+        assert!(!forest.connected(v0, v2));
+
+        forest.update(v2, 1);
+        assert_eq!(5, forest.eval(v2));
+    }
+}
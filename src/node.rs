@@ -1,34 +1,97 @@
+use crate::index::IndexType;
+
+// The parent of a root is encoded as `Ix::MAX`, so that nodes don't pay for the discriminant of an `Option`.
 #[derive(Debug, Clone)]
-pub(crate) struct Node<V> {
-    parent: Option<usize>,
+pub(crate) struct Node<V, Ix = usize> {
+    parent: Ix,
     value: V,
 }
 
-impl<V> Node<V> {
+impl<V, Ix> Node<V, Ix>
+where
+    Ix: IndexType,
+{
     pub(crate) fn new_root(value: V) -> Self {
         Self {
-            parent: None,
+            parent: Ix::new(Ix::MAX),
             value,
         }
     }
 
+    // Assumes `parent < Ix::MAX`.
     pub(crate) fn set_parent(&mut self, parent: usize) {
-        self.parent = Some(parent);
+        self.parent = Ix::new(parent);
+    }
+
+    pub(crate) fn set_root(&mut self) {
+        self.parent = Ix::new(Ix::MAX);
     }
 
     pub(crate) fn set_value(&mut self, value: V) {
         self.value = value;
     }
 
-    pub(crate) fn parent(&self) -> &Option<usize> {
-        &self.parent
+    pub(crate) fn parent(&self) -> Option<usize> {
+        let parent = self.parent.index();
+        (parent != Ix::MAX).then_some(parent)
     }
 
     pub(crate) fn value(&self) -> &V {
         &self.value
     }
 
+    pub(crate) fn value_mut(&mut self) -> &mut V {
+        &mut self.value
+    }
+
     pub(crate) fn is_root(&self) -> bool {
-        self.parent.is_none()
+        self.parent.index() == Ix::MAX
+    }
+
+    pub(crate) fn map_value<W, F>(self, f: F) -> Node<W, Ix>
+    where
+        F: FnOnce(V) -> W,
+    {
+        Node {
+            parent: self.parent,
+            value: f(self.value),
+        }
+    }
+}
+
+// Links every node of the path of `key` below the children of its root to the root, so that the node becomes a root or
+// a child of its root. `associate` merges the value of a parent with the value of its child, `on_compress` is called
+// with each node linked to the root and the root. Returns the number of parents walked to find the root.
+pub(crate) fn compress_path<V, Ix, E>(
+    nodes: &mut [Node<V, Ix>],
+    key: usize,
+    mut associate: impl FnMut(&V, &V) -> Result<V, E>,
+    mut on_compress: impl FnMut(usize, usize),
+) -> Result<usize, E>
+where
+    Ix: IndexType,
+{
+    // first pass: find the nodes of the path below the children of the root
+    let mut path = vec![];
+    let mut current = key;
+    let root_key = loop {
+        match nodes[current].parent() {
+            None => return Ok(0),
+            Some(parent_key) if nodes[parent_key].is_root() => break parent_key,
+            Some(parent_key) => {
+                path.push(current);
+                current = parent_key;
+            }
+        }
+    };
+
+    // second pass: link them to the root from the top, so that each parent is already compressed
+    for &key in path.iter().rev() {
+        let parent_key = nodes[key].parent().unwrap();
+        let merged_values = associate(nodes[parent_key].value(), nodes[key].value())?;
+        nodes[key].set_value(merged_values);
+        nodes[key].set_parent(root_key);
+        on_compress(key, root_key);
     }
+    Ok(path.len() + 1)
 }
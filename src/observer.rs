@@ -0,0 +1,43 @@
+/// Hooks called by a [`CompressedForest`](crate::CompressedForest) on every structural change, see
+/// [`CompressedForest::with_observer`](crate::CompressedForest::with_observer).
+///
+/// Observers let downstream code maintain data derived from the forest (e.g. the size of every tree, or the set of
+/// nodes modified since some point) in lockstep with it. Every hook does nothing by default, and `()` is the observer
+/// that ignores every change.
+///
+/// `Id` is the type of the ids of the observed forest and `V` is the type of its values.
+pub trait ForestObserver<Id, V> {
+    /// Called when a new tree root identified by `id` is created with the given value.
+    #[inline]
+    fn on_new_root(&mut self, id: Id, value: &V) {
+        let _ = (id, value);
+    }
+
+    /// Called when the root identified by `root_b` becomes a child of the root identified by `root_a`,
+    /// i.e. when their trees are merged.
+    #[inline]
+    fn on_link(&mut self, root_a: Id, root_b: Id) {
+        let _ = (root_a, root_b);
+    }
+
+    /// Called when the value of the root identified by `root` is replaced by the given value.
+    #[inline]
+    fn on_update(&mut self, root: Id, value: &V) {
+        let _ = (root, value);
+    }
+
+    /// Called when the node identified by `id` is cut from its parent and becomes the root of a new tree
+    /// with the given value, see [`CompressedForest::try_cut`](crate::CompressedForest::try_cut).
+    #[inline]
+    fn on_cut(&mut self, id: Id, value: &V) {
+        let _ = (id, value);
+    }
+
+    /// Called when path compression makes the node identified by `id` a child of its ancestor identified by `ancestor`.
+    #[inline]
+    fn on_compress(&mut self, id: Id, ancestor: Id) {
+        let _ = (id, ancestor);
+    }
+}
+
+impl<Id, V> ForestObserver<Id, V> for () {}
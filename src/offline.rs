@@ -0,0 +1,607 @@
+use crate::operation::{AssociativeOperation, GroupOperation};
+use crate::{CompressedForest, ForestError, NewRoot, OperationError};
+
+/// An EVAL-LINK-UPDATE operation of an offline sequence.
+///
+/// Nodes are referred to by their creation order: the node created by the `n`-th [`Op::NewRoot`] of the sequence is node `n`.
+/// Sequences do not depend on the ids of a particular forest, so they can be recorded once and replayed on any forest
+/// (see [`apply`]).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Op<V> {
+    /// Creates a new tree root with the given value.
+    NewRoot(V),
+    /// Links the root of the tree that contains the second node to the first node.
+    Link(usize, usize),
+    /// Replaces the value of the root of the tree that contains the node.
+    Update(usize, V),
+    /// Evaluates the node.
+    Eval(usize),
+}
+
+/// Replays a whole sequence of operations on a new [`CompressedForest`] with the operation `O`, and returns
+/// the answers of every [`Op::Eval`], in order.
+///
+/// The sequence is answered by path compression, one operation at a time: its running time is the one of the
+/// operations of [`CompressedForest`]. Sequences of group operations are answered in linear time by [`solve`].
+///
+/// # Errors
+/// Will return [`ForestError::InvalidId`] if an operation refers to a node that has not been created yet,
+/// [`ForestError::WouldCreateCycle`] if a link would create a cycle
+/// and [`ForestError::Operation`] if [`O::associate`](AssociativeOperation::associate) fails.
+pub fn replay<V, O>(operations: &[Op<V>]) -> Result<Vec<V>, ForestError<O::Error>>
+where
+    V: Clone,
+    O: 'static + AssociativeOperation<V>,
+{
+    let mut forest: CompressedForest<V, O> = CompressedForest::new();
+    apply(&mut forest, operations)
+}
+
+/// Answers a whole sequence of operations in linear time, and returns the answers of every [`Op::Eval`], in order.
+///
+/// The sequence is read in advance, so that the shape of the final forest is known. Roots are then found with the
+/// micro/macro-tree technique of Gabow and Tarjan: the final forest is split into micro sets of at most 64 nodes, in
+/// which roots are found with bitwise operations, and the micro sets are handled by a disjoint-set forest small enough
+/// to take constant amortized time per operation. Each [`Op::Eval`] is finally answered with the evaluations of the
+/// nodes in the final forest and an inverse, in constant time. Operations that are not groups need [`replay`]: for them,
+/// path compression is already optimal.
+///
+/// The evaluations of the final forest are computed for the evaluated nodes and their roots, so `O` must not fail
+/// (e.g. overflow) on these even when the evaluations of the sequence do not.
+///
+/// # Errors
+/// Will return [`ForestError::InvalidId`] if an operation refers to a node that has not been created yet,
+/// [`ForestError::WouldCreateCycle`] if a link would create a cycle and [`ForestError::Operation`] if
+/// [`O::associate`](AssociativeOperation::associate) or [`O::inverse`](GroupOperation::inverse) fails.
+/// The whole sequence is checked for invalid ids and cycles before any evaluation.
+pub fn solve<V, O>(operations: &[Op<V>]) -> Result<Vec<V>, ForestError<O::Error>>
+where
+    V: Clone,
+    O: GroupOperation<V>,
+{
+    let mut node_count = 0;
+    let mut edges = vec![];
+    let check = |node_count: usize, key: usize| {
+        if key < node_count {
+            Ok(key)
+        } else {
+            Err(ForestError::InvalidId)
+        }
+    };
+    for operation in operations {
+        match *operation {
+            Op::NewRoot(_) => node_count += 1,
+            Op::Link(a, b) => edges.push((check(node_count, a)?, check(node_count, b)?)),
+            Op::Update(a, _) | Op::Eval(a) => {
+                check(node_count, a)?;
+            }
+        }
+    }
+    let mut sets = StaticSets::new(node_count, &edges).ok_or(ForestError::WouldCreateCycle)?;
+
+    let mut values = Vec::with_capacity(node_count);
+    let mut parents = vec![None; node_count];
+    // the root of the tree of each set, by top node of the set
+    let mut roots: Vec<_> = (0..node_count).collect();
+    let mut evals = vec![];
+    for operation in operations {
+        match operation {
+            Op::NewRoot(value) => values.push(value.clone()),
+            Op::Link(a, b) => {
+                let (top_a, top_b) = (sets.find(*a), sets.find(*b));
+                parents[roots[top_b]] = Some(*a);
+                // the merged set keeps the top of the set of the parent
+                let top = if sets.link(*a, *b) == *a {
+                    top_b
+                } else {
+                    top_a
+                };
+                roots[top] = roots[top_a];
+            }
+            Op::Update(a, value) => values[roots[sets.find(*a)]] = value.clone(),
+            Op::Eval(a) => {
+                let root = roots[sets.find(*a)];
+                evals.push((*a, root, values[root].clone()));
+            }
+        }
+    }
+
+    // values of nodes that are not roots anymore do not change after they are linked, so they are the values of the
+    // final forest, in which the evaluation of a node is the evaluation of its root associated with the values below
+    let mut prefixes = vec![None; node_count];
+    evals
+        .into_iter()
+        .map(|(key, root, value)| {
+            if key == root {
+                return Ok(value);
+            }
+            let root_prefix = prefix::<V, O>(&mut prefixes, &parents, &values, root)?;
+            let key_prefix = prefix::<V, O>(&mut prefixes, &parents, &values, key)?;
+            O::associate(
+                &value,
+                &O::associate(&O::inverse(&root_prefix)?, &key_prefix)?,
+            )
+        })
+        .collect::<Result<_, _>>()
+        .map_err(ForestError::Operation)
+}
+
+// Computes the evaluation of a node in the final forest, and of every node of its path that was not computed yet.
+fn prefix<V, O>(
+    prefixes: &mut [Option<V>],
+    parents: &[Option<usize>],
+    values: &[V],
+    key: usize,
+) -> Result<V, O::Error>
+where
+    V: Clone,
+    O: AssociativeOperation<V>,
+{
+    let mut path = vec![];
+    let mut current = Some(key);
+    while let Some(current_key) = current.filter(|&k| prefixes[k].is_none()) {
+        path.push(current_key);
+        current = parents[current_key];
+    }
+
+    // from the top, so that the evaluation of each parent is already computed
+    for &key in path.iter().rev() {
+        let prefix = match parents[key] {
+            None => values[key].clone(),
+            Some(parent_key) => O::associate(prefixes[parent_key].as_ref().unwrap(), &values[key])?,
+        };
+        prefixes[key] = Some(prefix);
+    }
+    Ok(prefixes[key].clone().unwrap())
+}
+
+// Returns the mask of `len` bits from bit `start`.
+fn bit_range(start: usize, len: usize) -> u64 {
+    if len == 64 {
+        u64::MAX
+    } else {
+        ((1 << len) - 1) << start
+    }
+}
+
+/// Disjoint sets of the nodes of a forest known in advance, that are merged along the edges of the forest.
+///
+/// Each set is a subtree, named after its top node. The forest is split into micro sets of at most 64 nodes, made of
+/// subtrees that hang from the same node: a micro set stores which of its nodes are not linked to their parent yet,
+/// so that the nearest of these among the ancestors of a node is found with bitwise operations. Nodes from which micro
+/// sets hang are macro nodes, that are merged in a disjoint-set forest once the path to the node they hang from is
+/// linked. Most micro sets are more than half full and each hangs from a single node, so there are few enough macro
+/// nodes for the disjoint-set forest to take constant amortized time per operation.
+struct StaticSets {
+    parents: Vec<Option<usize>>,
+    micros: Vec<usize>,
+    locals: Vec<usize>,
+    // the nodes of the micro set that are the node or its ancestors, by local index
+    ancestors: Vec<u64>,
+    // the nodes of the micro set that are the node or its descendants, by local index
+    descendants: Vec<u64>,
+    // the nodes of every micro set, by local index
+    members: Vec<usize>,
+    starts: Vec<usize>,
+    // the nodes of the micro set that are not linked to their parent, for every micro set
+    unlinked: Vec<u64>,
+    // the node every micro set hangs from, if any
+    hangs: Vec<Option<usize>>,
+    // the macro nodes of the micro set whose path is not linked, for every micro set
+    pending: Vec<u64>,
+    macros: Vec<Option<usize>>,
+    macro_nodes: Vec<usize>,
+    macro_parents: Vec<usize>,
+    macro_ranks: Vec<u8>,
+    // the top macro node, by representative of each disjoint set of macro nodes
+    macro_tops: Vec<usize>,
+}
+
+impl StaticSets {
+    const MICRO_SIZE: usize = u64::BITS as usize;
+
+    // Returns `None` if edges do not form a forest.
+    fn new(node_count: usize, edges: &[(usize, usize)]) -> Option<Self> {
+        let mut offsets = vec![0; node_count + 1];
+        for &(a, b) in edges {
+            offsets[a + 1] += 1;
+            offsets[b + 1] += 1;
+        }
+        for key in 0..node_count {
+            offsets[key + 1] += offsets[key];
+        }
+        let mut adjacency = vec![(0, 0); 2 * edges.len()];
+        let mut cursors = offsets.clone();
+        for (edge, &(a, b)) in edges.iter().enumerate() {
+            adjacency[cursors[a]] = (b, edge);
+            cursors[a] += 1;
+            adjacency[cursors[b]] = (a, edge);
+            cursors[b] += 1;
+        }
+
+        // a depth-first order, in which each subtree is contiguous
+        let mut parents = vec![None; node_count];
+        let mut parent_edges = vec![None; node_count];
+        let mut visited = vec![false; node_count];
+        let mut order = Vec::with_capacity(node_count);
+        let mut stack = vec![];
+        for root in 0..node_count {
+            if visited[root] {
+                continue;
+            }
+            visited[root] = true;
+            stack.push(root);
+            while let Some(key) = stack.pop() {
+                order.push(key);
+                for &(next, edge) in &adjacency[offsets[key]..offsets[key + 1]] {
+                    if parent_edges[key] == Some(edge) {
+                        continue;
+                    }
+                    if visited[next] {
+                        return None;
+                    }
+                    visited[next] = true;
+                    parents[next] = Some(key);
+                    parent_edges[next] = Some(edge);
+                    stack.push(next);
+                }
+            }
+        }
+
+        // from the leaves, subtrees are gathered while they fit in a micro set, the subtrees that hang from a node
+        // that does not fit are then packed into micro sets
+        let mut sizes = vec![1; node_count];
+        let mut heads = vec![None; node_count];
+        let mut hangs = vec![];
+        let mut children = vec![vec![]; node_count];
+        for &key in order.iter().rev() {
+            let size = 1 + children[key]
+                .iter()
+                .map(|&child| sizes[child])
+                .sum::<usize>();
+            if size <= Self::MICRO_SIZE {
+                sizes[key] = size;
+            } else {
+                let mut micro_size = Self::MICRO_SIZE;
+                for &child in &children[key] {
+                    if micro_size + sizes[child] > Self::MICRO_SIZE {
+                        hangs.push(Some(key));
+                        micro_size = 0;
+                    }
+                    heads[child] = Some(hangs.len() - 1);
+                    micro_size += sizes[child];
+                }
+            }
+            match parents[key] {
+                Some(parent_key) => children[parent_key].push(key),
+                None => {
+                    heads[key] = Some(hangs.len());
+                    hangs.push(None);
+                }
+            }
+        }
+
+        let mut micros = vec![0; node_count];
+        let mut locals = vec![0; node_count];
+        let mut ancestors = vec![0; node_count];
+        let mut counts = vec![0; hangs.len()];
+        for &key in &order {
+            micros[key] = match heads[key] {
+                Some(micro) => micro,
+                None => micros[parents[key].unwrap()],
+            };
+            locals[key] = counts[micros[key]];
+            counts[micros[key]] += 1;
+            ancestors[key] = 1 << locals[key];
+            if heads[key].is_none() {
+                ancestors[key] |= ancestors[parents[key].unwrap()];
+            }
+        }
+        let mut descendants = vec![0; node_count];
+        let mut micro_sizes = vec![1; node_count];
+        for &key in order.iter().rev() {
+            descendants[key] = bit_range(locals[key], micro_sizes[key]);
+            if heads[key].is_none() {
+                micro_sizes[parents[key].unwrap()] += micro_sizes[key];
+            }
+        }
+
+        let mut starts = vec![0; hangs.len() + 1];
+        for micro in 0..hangs.len() {
+            starts[micro + 1] = starts[micro] + counts[micro];
+        }
+        let mut members = vec![0; node_count];
+        for key in 0..node_count {
+            members[starts[micros[key]] + locals[key]] = key;
+        }
+        let unlinked = counts.iter().map(|&count| bit_range(0, count)).collect();
+
+        let mut pending = vec![0; hangs.len()];
+        let mut macros = vec![None; node_count];
+        let mut macro_nodes = vec![];
+        for &key in hangs.iter().flatten() {
+            if macros[key].is_none() {
+                macros[key] = Some(macro_nodes.len());
+                macro_nodes.push(key);
+                pending[micros[key]] |= 1 << locals[key];
+            }
+        }
+
+        Some(Self {
+            parents,
+            micros,
+            locals,
+            ancestors,
+            descendants,
+            members,
+            starts,
+            unlinked,
+            hangs,
+            pending,
+            macros,
+            macro_parents: (0..macro_nodes.len()).collect(),
+            macro_ranks: vec![0; macro_nodes.len()],
+            macro_tops: (0..macro_nodes.len()).collect(),
+            macro_nodes,
+        })
+    }
+
+    // Returns the top node of the set of the node.
+    fn find(&mut self, key: usize) -> usize {
+        self.find_in_micro(key).unwrap_or_else(|| {
+            // the set goes on above the node the micro set hangs from
+            let hang = self.hangs[self.micros[key]].unwrap();
+            let top = self.macro_top(self.macros[hang].unwrap());
+            self.find_in_micro(self.macro_nodes[top]).unwrap()
+        })
+    }
+
+    // Returns the nearest ancestor of the node that is not linked to its parent, if it is in the same micro set.
+    fn find_in_micro(&self, key: usize) -> Option<usize> {
+        let micro = self.micros[key];
+        let candidates = self.ancestors[key] & self.unlinked[micro];
+        (candidates != 0).then(|| self.members[self.starts[micro] + candidates.ilog2() as usize])
+    }
+
+    // Merges the sets of the nodes of an edge of the forest, and returns the child of the edge.
+    fn link(&mut self, a: usize, b: usize) -> usize {
+        let child = if self.parents[a] == Some(b) { a } else { b };
+        let micro = self.micros[child];
+        self.unlinked[micro] &= !(1 << self.locals[child]);
+
+        // macro nodes below the child whose whole path in the micro set may now be linked
+        let mut candidates = self.pending[micro] & self.descendants[child];
+        while candidates != 0 {
+            let local = candidates.trailing_zeros();
+            candidates &= candidates - 1;
+            let key = self.members[self.starts[micro] + local as usize];
+            if self.ancestors[key] & self.unlinked[micro] == 0 {
+                self.pending[micro] &= !(1 << local);
+                let hang = self.hangs[micro].unwrap();
+                self.macro_union(self.macros[key].unwrap(), self.macros[hang].unwrap());
+            }
+        }
+        child
+    }
+
+    fn macro_representative(&mut self, mut index: usize) -> usize {
+        // path halving
+        while self.macro_parents[index] != index {
+            self.macro_parents[index] = self.macro_parents[self.macro_parents[index]];
+            index = self.macro_parents[index];
+        }
+        index
+    }
+
+    fn macro_top(&mut self, index: usize) -> usize {
+        let representative = self.macro_representative(index);
+        self.macro_tops[representative]
+    }
+
+    // Merges the set of a macro node with the set of the macro node above it, the top of which is kept.
+    fn macro_union(&mut self, lower: usize, upper: usize) {
+        let lower = self.macro_representative(lower);
+        let upper = self.macro_representative(upper);
+        let top = self.macro_tops[upper];
+        let representative = match self.macro_ranks[lower].cmp(&self.macro_ranks[upper]) {
+            std::cmp::Ordering::Less => upper,
+            std::cmp::Ordering::Greater => lower,
+            std::cmp::Ordering::Equal => {
+                self.macro_ranks[upper] += 1;
+                upper
+            }
+        };
+        self.macro_parents[lower] = representative;
+        self.macro_parents[upper] = representative;
+        self.macro_tops[representative] = top;
+    }
+}
+
+/// Replays a whole sequence of operations on `forest` and returns the answers of every [`Op::Eval`], in order.
+///
+/// Node `n` of the sequence is the node created by its `n`-th [`Op::NewRoot`], the nodes already in the forest
+/// cannot be referred to. Operations that come before an error are not undone.
+///
+/// # Errors
+/// Will return [`ForestError::InvalidId`] if an operation refers to a node that has not been created yet,
+/// and the error of the first operation of `forest` that fails otherwise.
+pub fn apply<F>(
+    forest: &mut F,
+    operations: &[Op<F::Value>],
+) -> Result<Vec<F::Value>, ForestError<OperationError<F>>>
+where
+    F: NewRoot,
+    F::Id: Clone,
+    F::Value: Clone,
+{
+    let mut ids = vec![];
+    let mut answers = vec![];
+
+    let id = |ids: &[F::Id], key: usize| ids.get(key).cloned().ok_or(ForestError::InvalidId);
+    for operation in operations {
+        match operation {
+            Op::NewRoot(value) => ids.push(forest.new_root(value.clone())),
+            Op::Link(a, b) => forest.try_link(id(&ids, *a)?, id(&ids, *b)?)?,
+            Op::Update(a, value) => forest.try_update(id(&ids, *a)?, value.clone())?,
+            Op::Eval(a) => answers.push(forest.try_eval(id(&ids, *a)?)?),
+        }
+    }
+
+    Ok(answers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::Index;
+    use crate::operation::*;
+    use crate::{EvalLinkUpdate, NaiveForest};
+
+    #[test]
+    fn add_sequence() {
+        let operations = [
+            Op::NewRoot(2),
+            Op::NewRoot(3),
+            Op::NewRoot(4),
+            Op::NewRoot(5),
+            Op::Link(0, 1),
+            Op::Link(2, 3),
+            Op::Eval(1),
+            Op::Link(3, 0),
+            Op::Eval(1),
+            Op::Update(1, 1),
+            Op::Eval(1),
+        ];
+
+        assert_eq!(Ok(vec![5, 14, 11]), replay::<usize, CloneAdd>(&operations));
+        let signed: Vec<_> = operations
+            .iter()
+            .map(|operation| match *operation {
+                Op::NewRoot(value) => Op::NewRoot(value as i64),
+                Op::Update(a, value) => Op::Update(a, value as i64),
+                Op::Link(a, b) => Op::Link(a, b),
+                Op::Eval(a) => Op::Eval(a),
+            })
+            .collect();
+        assert_eq!(Ok(vec![5, 14, 11]), solve::<i64, CloneAdd>(&signed));
+        let mut forest: NaiveForest<usize, CloneAdd> = NaiveForest::new();
+        let _ = forest.new_root(7);
+        assert_eq!(Ok(vec![5, 14, 11]), apply(&mut forest, &operations));
+        assert_eq!(Some(5), forest.node_count());
+        assert_eq!(
+            Err(ForestError::WouldCreateCycle),
+            apply(
+                &mut forest,
+                &[
+                    Op::NewRoot(0),
+                    Op::NewRoot(1),
+                    Op::Link(0, 1),
+                    Op::Link(1, 0)
+                ]
+            )
+        );
+        assert_eq!(
+            Err(ForestError::InvalidId),
+            replay::<usize, CloneAdd>(&[Op::NewRoot(0), Op::Eval(1)])
+        );
+    }
+
+    #[test]
+    fn solve_errors() {
+        assert_eq!(
+            Err(ForestError::InvalidId),
+            solve::<i64, CloneAdd>(&[Op::NewRoot(0), Op::Eval(0), Op::Link(0, 1)])
+        );
+        assert_eq!(
+            Err(ForestError::WouldCreateCycle),
+            solve::<i64, CloneAdd>(&[Op::NewRoot(0), Op::Link(0, 0)])
+        );
+        assert_eq!(
+            Err(ForestError::WouldCreateCycle),
+            solve::<i64, CloneAdd>(&[
+                Op::NewRoot(0),
+                Op::NewRoot(1),
+                Op::NewRoot(2),
+                Op::Link(0, 1),
+                Op::Link(1, 2),
+                Op::Link(2, 0)
+            ])
+        );
+        assert_eq!(
+            Err(LengthMismatch),
+            solve::<Vec<usize>, Permutation>(&[
+                Op::NewRoot(vec![0]),
+                Op::NewRoot(vec![1, 0]),
+                Op::Link(0, 1),
+                Op::Eval(1)
+            ])
+            .map_err(|error| match error {
+                ForestError::Operation(error) => error,
+                _ => unreachable!(),
+            })
+        );
+    }
+
+    #[test]
+    fn solve_star_and_chain() {
+        let n = 2000;
+        let mut operations: Vec<_> = (0..n).map(|i| Op::NewRoot(i as i64)).collect();
+        // more children than a micro set can hold
+        for i in 1..200 {
+            operations.push(Op::Link(0, i));
+            operations.push(Op::Eval(i));
+        }
+        for i in 200..n {
+            operations.push(Op::Link(i - 1, i));
+            if i % 7 == 0 {
+                operations.push(Op::Update(i - 100, i as i64));
+            }
+            operations.push(Op::Eval(i));
+            operations.push(Op::Eval(i / 2));
+        }
+
+        assert_eq!(
+            replay::<i64, CloneAdd>(&operations),
+            solve::<i64, CloneAdd>(&operations)
+        );
+    }
+
+    #[test]
+    fn solve_same_as_replay() {
+        let n = 3000;
+        let mut state: usize = 11;
+        let mut next = |bound: usize| {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 33) % bound
+        };
+        let permutations = [[1, 0, 2, 3], [0, 2, 3, 1], [3, 2, 1, 0], [0, 1, 2, 3]];
+
+        // only links that do not create a cycle are kept
+        let mut forest: CompressedForest<[usize; 4], Permutation> = CompressedForest::new();
+        let mut operations = vec![];
+        for _ in 0..n {
+            let value = permutations[next(permutations.len())];
+            let _ = forest.new_root(value);
+            operations.push(Op::NewRoot(value));
+        }
+        for _ in 0..4 * n {
+            let a = next(n);
+            let b = next(n);
+            match next(4) {
+                0 | 1 => {
+                    if forest.try_link(Index::new(a), Index::new(b)).is_ok() {
+                        operations.push(Op::Link(a, b));
+                    }
+                }
+                2 => operations.push(Op::Update(a, permutations[next(permutations.len())])),
+                _ => operations.push(Op::Eval(a)),
+            }
+        }
+
+        let answers = replay::<[usize; 4], Permutation>(&operations).unwrap();
+        assert!(answers.len() > n / 2);
+        assert_eq!(Ok(answers), solve::<[usize; 4], Permutation>(&operations));
+    }
+}
@@ -1,3 +1,7 @@
+use std::collections::{BTreeSet, HashSet};
+use std::marker::PhantomData;
+use std::sync::Arc;
+
 /// A trait for types that have a default associative operation.
 pub trait WithDefaultOperation: Sized {
     /// The type returned in the event of an association error.
@@ -20,6 +24,111 @@ pub trait AssociativeOperation<V> {
     /// # Errors
     /// Should return `Err` if the operation fails.
     fn associate(lhs: &V, rhs: &V) -> Result<V, Self::Error>;
+
+    /// Associates `lhs[i]` with `rhs[i]` and stores the result in `rhs[i]`, for every index `i`.
+    ///
+    /// Operations on primitive values override it so that slices are processed with vector instructions.
+    /// The content of `rhs` is unspecified if the operation fails.
+    ///
+    /// # Errors
+    /// Should return `Err` if the association of a pair fails.
+    ///
+    /// # Panics
+    /// Panics if the slices do not have the same length.
+    #[cfg(feature = "simd")]
+    #[inline]
+    fn associate_slices(lhs: &[V], rhs: &mut [V]) -> Result<(), Self::Error> {
+        assert_eq!(lhs.len(), rhs.len(), "slices of different lengths");
+        for (lhs, rhs) in lhs.iter().zip(rhs) {
+            *rhs = Self::associate(lhs, rhs)?;
+        }
+        Ok(())
+    }
+}
+
+/// A fallible associative operation that depends on runtime parameters (e.g. a modulus or a closure).
+///
+/// Unlike [`AssociativeOperation`], the association is computed by an instance of the operation, see
+/// [`StatefulForest`](crate::StatefulForest). Operations without parameters should implement [`AssociativeOperation`]
+/// instead, so that they can be used by every forest.
+pub trait AssociativeOperationRef<V> {
+    /// The type returned in the event of an association error.
+    type Error;
+
+    /// Computes the association.
+    ///
+    /// # Errors
+    /// Should return `Err` if the operation fails.
+    fn associate_ref(&self, lhs: &V, rhs: &V) -> Result<V, Self::Error>;
+}
+
+/// An infallible associative operation defined by a closure.
+#[derive(Debug, Clone, Copy)]
+pub struct FnOperation<F>(pub F);
+
+impl<V, F> AssociativeOperationRef<V> for FnOperation<F>
+where
+    F: Fn(&V, &V) -> V,
+{
+    type Error = std::convert::Infallible;
+
+    #[inline]
+    fn associate_ref(&self, lhs: &V, rhs: &V) -> Result<V, Self::Error> {
+        Ok((self.0)(lhs, rhs))
+    }
+}
+
+/// An associative operation for which every value has an inverse.
+///
+/// The inverse `inv` of a value `v` must satisfy `associate(inv, associate(v, x)) == x` and
+/// `associate(associate(x, v), inv) == x` for every value `x`.
+pub trait GroupOperation<V>: AssociativeOperation<V> {
+    /// Computes the inverse of a value.
+    ///
+    /// # Errors
+    /// Should return `Err` if the operation fails.
+    fn inverse(value: &V) -> Result<V, Self::Error>;
+}
+
+/// An associative operation with an identity element.
+///
+/// The identity `e` must satisfy `associate(e, x) == x` and `associate(x, e) == x` for every value `x`.
+///
+/// Forests use the identity to create roots (see [`CompressedForest::new_root_identity`](crate::CompressedForest::new_root_identity)),
+/// then associate it as any other value: they cannot tell whether a root still holds the identity without comparing
+/// values, so links and compressions do not skip it.
+pub trait MonoidOperation<V>: AssociativeOperation<V> {
+    /// Returns the identity element.
+    fn identity() -> V;
+}
+
+/// A marker trait for commutative associative operations (e.g. addition or minimum).
+///
+/// The operands must commute, i.e. `associate(a, b) == associate(b, a)` for all values `a` and `b`, so that forests
+/// are free to reorder them.
+/// It is required by the forests shared across threads, [`ConcurrentForest`](crate::ConcurrentForest) and
+/// [`AtomicForest`](crate::AtomicForest).
+pub trait CommutativeOperation<V>: AssociativeOperation<V> {}
+
+/// A marker trait for idempotent associative operations (e.g. minimum or bitwise and).
+///
+/// The association of a value with itself must be the value itself, i.e. `associate(v, v) == v` for every value `v`.
+/// It can be verified with [`laws::check_idempotence`].
+///
+/// Forests compress idempotent operations as any other operation: each compression merges the values of disjoint
+/// parts of a path, so none of the merges is redundant.
+pub trait IdempotentOperation<V>: AssociativeOperation<V> {}
+
+/// An associative operation that always returns one of its operands (e.g minimum or maximum).
+///
+/// [`associate`](AssociativeOperation::associate) must return `lhs` whenever [`selects_lhs`](SelectionOperation::selects_lhs)
+/// returns `true` and `rhs` otherwise.
+pub trait SelectionOperation<V>: AssociativeOperation<V> {
+    /// Tells whether the association of `lhs` and `rhs` is `lhs`.
+    ///
+    /// # Errors
+    /// Should return `Err` if the operation fails.
+    fn selects_lhs(lhs: &V, rhs: &V) -> Result<bool, Self::Error>;
 }
 
 /// A default associative operation for types that implements the [`WithDefaultOperation`] trait.
@@ -36,6 +145,26 @@ where
     }
 }
 
+// wrapping addition is used so that forests of primitive integers have infallible operations
+#[cfg(feature = "primitive-default-op")]
+macro_rules! impl_default_wrapping_add {
+    ($($int:ty),*) => {
+        $(
+            impl WithDefaultOperation for $int {
+                type Error = std::convert::Infallible;
+
+                #[inline]
+                fn default_associate(&self, rhs: &Self) -> Result<Self, Self::Error> {
+                    Ok(self.wrapping_add(*rhs))
+                }
+            }
+        )*
+    };
+}
+
+#[cfg(feature = "primitive-default-op")]
+impl_default_wrapping_add!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize);
+
 macro_rules! impl_infallible_clone_binop {
     ($name:ident, $trait:ident, $func:ident, $doc:expr) => {
         #[doc=$doc]
@@ -122,3 +251,1134 @@ impl_fallible_clone_binop!(
     bitxor,
     "Fallible version of [`CloneBitXor`]."
 );
+
+impl<V> CommutativeOperation<V> for CloneAdd where V: Clone + std::ops::Add<V, Output = V> {}
+
+impl<V> CommutativeOperation<V> for CloneBitOr where V: Clone + std::ops::BitOr<V, Output = V> {}
+
+impl<V> CommutativeOperation<V> for CloneBitAnd where V: Clone + std::ops::BitAnd<V, Output = V> {}
+
+impl<V> CommutativeOperation<V> for CloneBitXor where V: Clone + std::ops::BitXor<V, Output = V> {}
+
+impl<V> IdempotentOperation<V> for CloneBitOr where V: Clone + std::ops::BitOr<V, Output = V> {}
+
+impl<V> IdempotentOperation<V> for CloneBitAnd where V: Clone + std::ops::BitAnd<V, Output = V> {}
+
+impl<V> GroupOperation<V> for CloneAdd
+where
+    V: Clone + std::ops::Add<V, Output = V> + std::ops::Neg<Output = V>,
+{
+    #[inline]
+    fn inverse(value: &V) -> Result<V, Self::Error> {
+        Ok(-value.clone())
+    }
+}
+
+impl<V> GroupOperation<V> for CloneBitXor
+where
+    V: Clone + std::ops::BitXor<V, Output = V>,
+{
+    #[inline]
+    fn inverse(value: &V) -> Result<V, Self::Error> {
+        Ok(value.clone())
+    }
+}
+
+/// Clone and minimum operation, the left operand is selected when both operands are equal.
+#[derive(Debug)]
+pub struct CloneMin;
+
+impl<V> AssociativeOperation<V> for CloneMin
+where
+    V: Clone + Ord,
+{
+    type Error = std::convert::Infallible;
+
+    #[inline]
+    fn associate(lhs: &V, rhs: &V) -> Result<V, Self::Error> {
+        Ok(if rhs < lhs { rhs.clone() } else { lhs.clone() })
+    }
+}
+
+impl<V> SelectionOperation<V> for CloneMin
+where
+    V: Clone + Ord,
+{
+    #[inline]
+    fn selects_lhs(lhs: &V, rhs: &V) -> Result<bool, Self::Error> {
+        Ok(lhs <= rhs)
+    }
+}
+
+impl<V> IdempotentOperation<V> for CloneMin where V: Clone + Ord {}
+
+impl<V> CommutativeOperation<V> for CloneMin where V: Clone + Ord {}
+
+/// Clone and maximum operation, the left operand is selected when both operands are equal.
+#[derive(Debug)]
+pub struct CloneMax;
+
+impl<V> AssociativeOperation<V> for CloneMax
+where
+    V: Clone + Ord,
+{
+    type Error = std::convert::Infallible;
+
+    #[inline]
+    fn associate(lhs: &V, rhs: &V) -> Result<V, Self::Error> {
+        Ok(if rhs > lhs { rhs.clone() } else { lhs.clone() })
+    }
+}
+
+impl<V> SelectionOperation<V> for CloneMax
+where
+    V: Clone + Ord,
+{
+    #[inline]
+    fn selects_lhs(lhs: &V, rhs: &V) -> Result<bool, Self::Error> {
+        Ok(lhs >= rhs)
+    }
+}
+
+impl<V> IdempotentOperation<V> for CloneMax where V: Clone + Ord {}
+
+impl<V> CommutativeOperation<V> for CloneMax where V: Clone + Ord {}
+
+/// Operation that keeps its left operand: nodes evaluate to the value of the root of their tree.
+#[derive(Debug)]
+pub struct KeepLhs;
+
+impl<V> AssociativeOperation<V> for KeepLhs
+where
+    V: Clone,
+{
+    type Error = std::convert::Infallible;
+
+    #[inline]
+    fn associate(lhs: &V, _rhs: &V) -> Result<V, Self::Error> {
+        Ok(lhs.clone())
+    }
+}
+
+impl<V> SelectionOperation<V> for KeepLhs
+where
+    V: Clone,
+{
+    #[inline]
+    fn selects_lhs(_lhs: &V, _rhs: &V) -> Result<bool, Self::Error> {
+        Ok(true)
+    }
+}
+
+impl<V> IdempotentOperation<V> for KeepLhs where V: Clone {}
+
+/// Operation that keeps its right operand: nodes evaluate to their own value, i.e. the value they had
+/// when they were linked (when they stopped being roots).
+#[derive(Debug)]
+pub struct KeepRhs;
+
+impl<V> AssociativeOperation<V> for KeepRhs
+where
+    V: Clone,
+{
+    type Error = std::convert::Infallible;
+
+    #[inline]
+    fn associate(_lhs: &V, rhs: &V) -> Result<V, Self::Error> {
+        Ok(rhs.clone())
+    }
+}
+
+impl<V> SelectionOperation<V> for KeepRhs
+where
+    V: Clone,
+{
+    #[inline]
+    fn selects_lhs(_lhs: &V, _rhs: &V) -> Result<bool, Self::Error> {
+        Ok(false)
+    }
+}
+
+impl<V> IdempotentOperation<V> for KeepRhs where V: Clone {}
+
+/// Composition of affine maps, a value `(a, b)` represents the map `x ↦ a·x + b`.
+///
+/// The association of `lhs` and `rhs` applies `lhs` first: nodes evaluate to the composition of the maps of the path
+/// applied from the root to the node.
+#[derive(Debug)]
+pub struct Affine;
+
+impl<V> AssociativeOperation<(V, V)> for Affine
+where
+    V: Clone + std::ops::Add<V, Output = V> + std::ops::Mul<V, Output = V>,
+{
+    type Error = std::convert::Infallible;
+
+    #[inline]
+    fn associate(lhs: &(V, V), rhs: &(V, V)) -> Result<(V, V), Self::Error> {
+        let ((a1, b1), (a2, b2)) = (lhs.clone(), rhs.clone());
+        Ok((a2.clone() * a1, a2 * b1 + b2))
+    }
+}
+
+/// Intersection of closed intervals, a value `(lo, hi)` represents the interval `lo..=hi`.
+///
+/// Fails with [`EmptyInterval`] when the intersection is empty, so that nodes evaluate to the intersection of the
+/// intervals of their path or report that the constraints of the path are inconsistent.
+#[derive(Debug)]
+pub struct IntervalIntersection;
+
+impl<V> AssociativeOperation<(V, V)> for IntervalIntersection
+where
+    V: Clone + Ord,
+{
+    type Error = EmptyInterval;
+
+    #[inline]
+    fn associate(lhs: &(V, V), rhs: &(V, V)) -> Result<(V, V), Self::Error> {
+        let lo = (&lhs.0).max(&rhs.0);
+        let hi = (&lhs.1).min(&rhs.1);
+        if lo > hi {
+            return Err(EmptyInterval);
+        }
+        Ok((lo.clone(), hi.clone()))
+    }
+}
+
+impl<V> IdempotentOperation<(V, V)> for IntervalIntersection where V: Clone + Ord {}
+
+impl<V> CommutativeOperation<(V, V)> for IntervalIntersection where V: Clone + Ord {}
+
+/// Convex hull of closed intervals, a value `(lo, hi)` represents the interval `lo..=hi`.
+///
+/// The hull is the smallest interval that contains both operands, i.e. their union when they overlap.
+#[derive(Debug)]
+pub struct IntervalHull;
+
+impl<V> AssociativeOperation<(V, V)> for IntervalHull
+where
+    V: Clone + Ord,
+{
+    type Error = std::convert::Infallible;
+
+    #[inline]
+    fn associate(lhs: &(V, V), rhs: &(V, V)) -> Result<(V, V), Self::Error> {
+        let lo = (&lhs.0).min(&rhs.0);
+        let hi = (&lhs.1).max(&rhs.1);
+        Ok((lo.clone(), hi.clone()))
+    }
+}
+
+impl<V> IdempotentOperation<(V, V)> for IntervalHull where V: Clone + Ord {}
+
+impl<V> CommutativeOperation<(V, V)> for IntervalHull where V: Clone + Ord {}
+
+/// Composition of permutations of `0..n`, a value `p` represents the permutation `i ↦ p[i]`.
+///
+/// As for [`Affine`], the association of `lhs` and `rhs` applies `lhs` first (i.e. it is `i ↦ rhs[lhs[i]]`): nodes
+/// evaluate to the permutation of the path applied from the root to the node. Values are not checked to be
+/// permutations, and vectors of different lengths fail with [`LengthMismatch`].
+#[derive(Debug)]
+pub struct Permutation;
+
+impl AssociativeOperation<Vec<usize>> for Permutation {
+    type Error = LengthMismatch;
+
+    #[inline]
+    fn associate(lhs: &Vec<usize>, rhs: &Vec<usize>) -> Result<Vec<usize>, Self::Error> {
+        if lhs.len() != rhs.len() {
+            return Err(LengthMismatch);
+        }
+        Ok(lhs.iter().map(|&i| rhs[i]).collect())
+    }
+}
+
+impl GroupOperation<Vec<usize>> for Permutation {
+    #[inline]
+    fn inverse(value: &Vec<usize>) -> Result<Vec<usize>, Self::Error> {
+        let mut inverse = vec![0; value.len()];
+        for (i, &j) in value.iter().enumerate() {
+            inverse[j] = i;
+        }
+        Ok(inverse)
+    }
+}
+
+impl<const N: usize> AssociativeOperation<[usize; N]> for Permutation {
+    type Error = std::convert::Infallible;
+
+    #[inline]
+    fn associate(lhs: &[usize; N], rhs: &[usize; N]) -> Result<[usize; N], Self::Error> {
+        Ok(lhs.map(|i| rhs[i]))
+    }
+}
+
+impl<const N: usize> MonoidOperation<[usize; N]> for Permutation {
+    #[inline]
+    fn identity() -> [usize; N] {
+        std::array::from_fn(|i| i)
+    }
+}
+
+impl<const N: usize> GroupOperation<[usize; N]> for Permutation {
+    #[inline]
+    fn inverse(value: &[usize; N]) -> Result<[usize; N], Self::Error> {
+        let mut inverse = [0; N];
+        for (i, &j) in value.iter().enumerate() {
+            inverse[j] = i;
+        }
+        Ok(inverse)
+    }
+}
+
+/// Composition of shared closures, for values of type `Arc<dyn Fn(T) -> T>` (optionally `Send + Sync`).
+///
+/// As for [`Affine`], the association of `lhs` and `rhs` applies `lhs` first (i.e. it is `x ↦ rhs(lhs(x))`): nodes
+/// evaluate to the pipeline of the closures of the path, from the root to the node. Each association allocates a
+/// closure that calls both operands, so evaluations become slower as values are merged by compression.
+#[derive(Debug)]
+pub struct Compose;
+
+impl<T> AssociativeOperation<Arc<dyn Fn(T) -> T>> for Compose
+where
+    T: 'static,
+{
+    type Error = std::convert::Infallible;
+
+    #[inline]
+    fn associate(
+        lhs: &Arc<dyn Fn(T) -> T>,
+        rhs: &Arc<dyn Fn(T) -> T>,
+    ) -> Result<Arc<dyn Fn(T) -> T>, Self::Error> {
+        let (lhs, rhs) = (Arc::clone(lhs), Arc::clone(rhs));
+        Ok(Arc::new(move |x| rhs(lhs(x))))
+    }
+}
+
+impl<T> MonoidOperation<Arc<dyn Fn(T) -> T>> for Compose
+where
+    T: 'static,
+{
+    #[inline]
+    fn identity() -> Arc<dyn Fn(T) -> T> {
+        Arc::new(|x| x)
+    }
+}
+
+impl<T> AssociativeOperation<Arc<dyn Fn(T) -> T + Send + Sync>> for Compose
+where
+    T: 'static,
+{
+    type Error = std::convert::Infallible;
+
+    #[inline]
+    fn associate(
+        lhs: &Arc<dyn Fn(T) -> T + Send + Sync>,
+        rhs: &Arc<dyn Fn(T) -> T + Send + Sync>,
+    ) -> Result<Arc<dyn Fn(T) -> T + Send + Sync>, Self::Error> {
+        let (lhs, rhs) = (Arc::clone(lhs), Arc::clone(rhs));
+        Ok(Arc::new(move |x| rhs(lhs(x))))
+    }
+}
+
+impl<T> MonoidOperation<Arc<dyn Fn(T) -> T + Send + Sync>> for Compose
+where
+    T: 'static,
+{
+    #[inline]
+    fn identity() -> Arc<dyn Fn(T) -> T + Send + Sync> {
+        Arc::new(|x| x)
+    }
+}
+
+/// Clone and union operation on sets, the identity is the empty set.
+#[derive(Debug)]
+pub struct CloneUnion;
+
+/// Clone and intersection operation on sets.
+#[derive(Debug)]
+pub struct CloneIntersection;
+
+impl<T, S> AssociativeOperation<HashSet<T, S>> for CloneUnion
+where
+    T: Clone + Eq + std::hash::Hash,
+    S: Clone + std::hash::BuildHasher,
+{
+    type Error = std::convert::Infallible;
+
+    #[inline]
+    fn associate(lhs: &HashSet<T, S>, rhs: &HashSet<T, S>) -> Result<HashSet<T, S>, Self::Error> {
+        let mut union = lhs.clone();
+        union.extend(rhs.iter().cloned());
+        Ok(union)
+    }
+}
+
+impl<T, S> CommutativeOperation<HashSet<T, S>> for CloneUnion
+where
+    T: Clone + Eq + std::hash::Hash,
+    S: Clone + std::hash::BuildHasher,
+{
+}
+
+impl<T, S> IdempotentOperation<HashSet<T, S>> for CloneUnion
+where
+    T: Clone + Eq + std::hash::Hash,
+    S: Clone + std::hash::BuildHasher,
+{
+}
+
+impl<T, S> MonoidOperation<HashSet<T, S>> for CloneUnion
+where
+    T: Clone + Eq + std::hash::Hash,
+    S: Clone + Default + std::hash::BuildHasher,
+{
+    #[inline]
+    fn identity() -> HashSet<T, S> {
+        HashSet::default()
+    }
+}
+
+impl<T, S> AssociativeOperation<HashSet<T, S>> for CloneIntersection
+where
+    T: Clone + Eq + std::hash::Hash,
+    S: Clone + std::hash::BuildHasher,
+{
+    type Error = std::convert::Infallible;
+
+    #[inline]
+    fn associate(lhs: &HashSet<T, S>, rhs: &HashSet<T, S>) -> Result<HashSet<T, S>, Self::Error> {
+        let mut intersection = lhs.clone();
+        intersection.retain(|value| rhs.contains(value));
+        Ok(intersection)
+    }
+}
+
+impl<T, S> CommutativeOperation<HashSet<T, S>> for CloneIntersection
+where
+    T: Clone + Eq + std::hash::Hash,
+    S: Clone + std::hash::BuildHasher,
+{
+}
+
+impl<T, S> IdempotentOperation<HashSet<T, S>> for CloneIntersection
+where
+    T: Clone + Eq + std::hash::Hash,
+    S: Clone + std::hash::BuildHasher,
+{
+}
+
+impl<T> AssociativeOperation<BTreeSet<T>> for CloneUnion
+where
+    T: Clone + Ord,
+{
+    type Error = std::convert::Infallible;
+
+    #[inline]
+    fn associate(lhs: &BTreeSet<T>, rhs: &BTreeSet<T>) -> Result<BTreeSet<T>, Self::Error> {
+        Ok(lhs | rhs)
+    }
+}
+
+impl<T> IdempotentOperation<BTreeSet<T>> for CloneUnion where T: Clone + Ord {}
+
+impl<T> CommutativeOperation<BTreeSet<T>> for CloneUnion where T: Clone + Ord {}
+
+impl<T> MonoidOperation<BTreeSet<T>> for CloneUnion
+where
+    T: Clone + Ord,
+{
+    #[inline]
+    fn identity() -> BTreeSet<T> {
+        BTreeSet::new()
+    }
+}
+
+impl<T> AssociativeOperation<BTreeSet<T>> for CloneIntersection
+where
+    T: Clone + Ord,
+{
+    type Error = std::convert::Infallible;
+
+    #[inline]
+    fn associate(lhs: &BTreeSet<T>, rhs: &BTreeSet<T>) -> Result<BTreeSet<T>, Self::Error> {
+        Ok(lhs & rhs)
+    }
+}
+
+impl<T> IdempotentOperation<BTreeSet<T>> for CloneIntersection where T: Clone + Ord {}
+
+impl<T> CommutativeOperation<BTreeSet<T>> for CloneIntersection where T: Clone + Ord {}
+
+/// Operation on pairs that associates the first components with `O1` and the second components with `O2`, so that
+/// a forest can maintain several aggregates at once.
+///
+/// The error of `O2` is converted into the error of `O1`, so that infallible operations can be paired with fallible
+/// ones when the fallible operation is `O1`.
+#[derive(Debug)]
+pub struct Pair<O1, O2>(PhantomData<(O1, O2)>);
+
+impl<O1, O2> Clone for Pair<O1, O2> {
+    fn clone(&self) -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<A, B, O1, O2> AssociativeOperation<(A, B)> for Pair<O1, O2>
+where
+    O1: AssociativeOperation<A>,
+    O2: AssociativeOperation<B>,
+    O1::Error: From<O2::Error>,
+{
+    type Error = O1::Error;
+
+    #[inline]
+    fn associate(lhs: &(A, B), rhs: &(A, B)) -> Result<(A, B), Self::Error> {
+        Ok((
+            O1::associate(&lhs.0, &rhs.0)?,
+            O2::associate(&lhs.1, &rhs.1)?,
+        ))
+    }
+}
+
+impl<A, B, O1, O2> MonoidOperation<(A, B)> for Pair<O1, O2>
+where
+    O1: MonoidOperation<A>,
+    O2: MonoidOperation<B>,
+    O1::Error: From<O2::Error>,
+{
+    #[inline]
+    fn identity() -> (A, B) {
+        (O1::identity(), O2::identity())
+    }
+}
+
+impl<A, B, O1, O2> CommutativeOperation<(A, B)> for Pair<O1, O2>
+where
+    O1: CommutativeOperation<A>,
+    O2: CommutativeOperation<B>,
+    O1::Error: From<O2::Error>,
+{
+}
+
+impl<A, B, O1, O2> IdempotentOperation<(A, B)> for Pair<O1, O2>
+where
+    O1: IdempotentOperation<A>,
+    O2: IdempotentOperation<B>,
+    O1::Error: From<O2::Error>,
+{
+}
+
+/// Operation that swaps the operands of `O`, i.e. `lhs` ⊕ `rhs` is `O::associate(rhs, lhs)`.
+///
+/// Nodes evaluate to the product of the values of the path from the node to the root with `O`, which reverses the
+/// accumulation direction of non-commutative operations.
+#[derive(Debug)]
+pub struct Flip<O>(PhantomData<O>);
+
+impl<O> Clone for Flip<O> {
+    fn clone(&self) -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<V, O> AssociativeOperation<V> for Flip<O>
+where
+    O: AssociativeOperation<V>,
+{
+    type Error = O::Error;
+
+    #[inline]
+    fn associate(lhs: &V, rhs: &V) -> Result<V, Self::Error> {
+        O::associate(rhs, lhs)
+    }
+}
+
+impl<V, O> MonoidOperation<V> for Flip<O>
+where
+    O: MonoidOperation<V>,
+{
+    #[inline]
+    fn identity() -> V {
+        O::identity()
+    }
+}
+
+impl<V, O> IdempotentOperation<V> for Flip<O> where O: IdempotentOperation<V> {}
+
+impl<V, O> CommutativeOperation<V> for Flip<O> where O: CommutativeOperation<V> {}
+
+impl<V, O> GroupOperation<V> for Flip<O>
+where
+    O: GroupOperation<V>,
+{
+    #[inline]
+    fn inverse(value: &V) -> Result<V, Self::Error> {
+        O::inverse(value)
+    }
+}
+
+impl<V, O> SelectionOperation<V> for Flip<O>
+where
+    O: SelectionOperation<V>,
+{
+    #[inline]
+    fn selects_lhs(lhs: &V, rhs: &V) -> Result<bool, Self::Error> {
+        O::selects_lhs(rhs, lhs).map(|selects_rhs| !selects_rhs)
+    }
+}
+
+/// Operation on optional values that associates values with `O`, `None` is the identity.
+///
+/// Nodes with no value yet can be added to a forest of `Option<V>`, without a sentinel value of `V`.
+#[derive(Debug)]
+pub struct Lift<O>(PhantomData<O>);
+
+impl<O> Clone for Lift<O> {
+    fn clone(&self) -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<V, O> AssociativeOperation<Option<V>> for Lift<O>
+where
+    V: Clone,
+    O: AssociativeOperation<V>,
+{
+    type Error = O::Error;
+
+    #[inline]
+    fn associate(lhs: &Option<V>, rhs: &Option<V>) -> Result<Option<V>, Self::Error> {
+        match (lhs, rhs) {
+            (Some(lhs), Some(rhs)) => O::associate(lhs, rhs).map(Some),
+            (Some(value), None) | (None, Some(value)) => Ok(Some(value.clone())),
+            (None, None) => Ok(None),
+        }
+    }
+}
+
+impl<V, O> MonoidOperation<Option<V>> for Lift<O>
+where
+    V: Clone,
+    O: AssociativeOperation<V>,
+{
+    #[inline]
+    fn identity() -> Option<V> {
+        None
+    }
+}
+
+impl<V, O> CommutativeOperation<Option<V>> for Lift<O>
+where
+    V: Clone,
+    O: CommutativeOperation<V>,
+{
+}
+
+impl<V, O> IdempotentOperation<Option<V>> for Lift<O>
+where
+    V: Clone,
+    O: IdempotentOperation<V>,
+{
+}
+
+impl<V, O> SelectionOperation<Option<V>> for Lift<O>
+where
+    V: Clone,
+    O: SelectionOperation<V>,
+{
+    #[inline]
+    fn selects_lhs(lhs: &Option<V>, rhs: &Option<V>) -> Result<bool, Self::Error> {
+        match (lhs, rhs) {
+            (Some(lhs), Some(rhs)) => O::selects_lhs(lhs, rhs),
+            (None, Some(_)) => Ok(false),
+            (_, None) => Ok(true),
+        }
+    }
+}
+
+/// Operation on shared values `Arc<V>` that associates the values with `O`.
+///
+/// Forests clone values when they evaluate and compress paths: with shared values these clones only increment a
+/// reference count, and a value is only allocated when `O` computes a new one. Selection operations should use
+/// [`SharedSelection`] instead, which never allocates.
+#[derive(Debug)]
+pub struct Shared<O>(PhantomData<O>);
+
+impl<O> Clone for Shared<O> {
+    fn clone(&self) -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<V, O> AssociativeOperation<Arc<V>> for Shared<O>
+where
+    O: AssociativeOperation<V>,
+{
+    type Error = O::Error;
+
+    #[inline]
+    fn associate(lhs: &Arc<V>, rhs: &Arc<V>) -> Result<Arc<V>, Self::Error> {
+        O::associate(lhs, rhs).map(Arc::new)
+    }
+}
+
+impl<V, O> MonoidOperation<Arc<V>> for Shared<O>
+where
+    O: MonoidOperation<V>,
+{
+    #[inline]
+    fn identity() -> Arc<V> {
+        Arc::new(O::identity())
+    }
+}
+
+impl<V, O> GroupOperation<Arc<V>> for Shared<O>
+where
+    O: GroupOperation<V>,
+{
+    #[inline]
+    fn inverse(value: &Arc<V>) -> Result<Arc<V>, Self::Error> {
+        O::inverse(value).map(Arc::new)
+    }
+}
+
+impl<V, O> IdempotentOperation<Arc<V>> for Shared<O> where O: IdempotentOperation<V> {}
+
+impl<V, O> CommutativeOperation<Arc<V>> for Shared<O> where O: CommutativeOperation<V> {}
+
+/// Operation on shared values `Arc<V>` that selects one of the operands with the [`SelectionOperation`] `O`.
+///
+/// The selected operand is shared, so that associations never allocate nor clone a value `V`.
+#[derive(Debug)]
+pub struct SharedSelection<O>(PhantomData<O>);
+
+impl<O> Clone for SharedSelection<O> {
+    fn clone(&self) -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<V, O> AssociativeOperation<Arc<V>> for SharedSelection<O>
+where
+    O: SelectionOperation<V>,
+{
+    type Error = O::Error;
+
+    #[inline]
+    fn associate(lhs: &Arc<V>, rhs: &Arc<V>) -> Result<Arc<V>, Self::Error> {
+        if O::selects_lhs(lhs, rhs)? {
+            Ok(Arc::clone(lhs))
+        } else {
+            Ok(Arc::clone(rhs))
+        }
+    }
+}
+
+impl<V, O> SelectionOperation<Arc<V>> for SharedSelection<O>
+where
+    O: SelectionOperation<V>,
+{
+    #[inline]
+    fn selects_lhs(lhs: &Arc<V>, rhs: &Arc<V>) -> Result<bool, Self::Error> {
+        O::selects_lhs(lhs, rhs)
+    }
+}
+
+impl<V, O> IdempotentOperation<Arc<V>> for SharedSelection<O> where O: SelectionOperation<V> {}
+
+impl<V, O> CommutativeOperation<Arc<V>> for SharedSelection<O> where
+    O: SelectionOperation<V> + CommutativeOperation<V>
+{
+}
+
+/// The error of an operation whose result does not fit in the value type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Overflow;
+
+impl std::fmt::Display for Overflow {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "arithmetic overflow")
+    }
+}
+
+impl std::error::Error for Overflow {}
+
+impl From<std::convert::Infallible> for Overflow {
+    fn from(infallible: std::convert::Infallible) -> Self {
+        match infallible {}
+    }
+}
+
+/// The error of an operation whose operands do not have the same length.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LengthMismatch;
+
+impl std::fmt::Display for LengthMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "operands of different lengths")
+    }
+}
+
+impl std::error::Error for LengthMismatch {}
+
+/// The error of a floating-point operation whose result is infinite or NaN.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NonFinite;
+
+impl std::fmt::Display for NonFinite {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "non-finite floating-point result")
+    }
+}
+
+impl std::error::Error for NonFinite {}
+
+impl From<std::convert::Infallible> for NonFinite {
+    fn from(infallible: std::convert::Infallible) -> Self {
+        match infallible {}
+    }
+}
+
+/// The error of an interval operation whose result is empty.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EmptyInterval;
+
+impl std::fmt::Display for EmptyInterval {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "empty interval")
+    }
+}
+
+impl std::error::Error for EmptyInterval {}
+
+macro_rules! impl_primitive_op {
+    (@slices $ty:ty, {}) => {};
+    (@slices $ty:ty, { |$lhs:ident, $rhs:ident| $body:expr }) => {
+        #[cfg(feature = "simd")]
+        #[inline]
+        fn associate_slices($lhs: &[$ty], $rhs: &mut [$ty]) -> Result<(), Self::Error> {
+            assert_eq!($lhs.len(), $rhs.len(), "slices of different lengths");
+            $body
+        }
+    };
+    (
+        $name:ident, $doc:expr, $error:ty, $identity:literal, |$lhs:ident, $rhs:ident| $body:expr, slices $slices:tt;
+        $($ty:ty),*
+    ) => {
+        #[doc=$doc]
+        #[derive(Debug)]
+        pub struct $name;
+
+        $(
+            impl AssociativeOperation<$ty> for $name {
+                type Error = $error;
+
+                #[inline]
+                fn associate($lhs: &$ty, $rhs: &$ty) -> Result<$ty, Self::Error> {
+                    $body
+                }
+
+                impl_primitive_op!(@slices $ty, $slices);
+            }
+
+            impl MonoidOperation<$ty> for $name {
+                #[inline]
+                fn identity() -> $ty {
+                    $identity
+                }
+            }
+
+            impl CommutativeOperation<$ty> for $name {}
+        )*
+    };
+    ($name:ident, $doc:expr, $error:ty, $identity:literal, |$lhs:ident, $rhs:ident| $body:expr; $($ty:ty),*) => {
+        impl_primitive_op!($name, $doc, $error, $identity, |$lhs, $rhs| $body, slices {}; $($ty),*);
+    };
+}
+
+impl_primitive_op!(
+    CheckedAdd,
+    "Integer addition operation that fails with [`Overflow`] instead of overflowing.",
+    Overflow,
+    0,
+    |lhs, rhs| lhs.checked_add(*rhs).ok_or(Overflow),
+    slices {
+        |lhs, rhs| {
+            let mut overflow = false;
+            for (lhs, rhs) in lhs.iter().zip(rhs) {
+                let (value, overflows) = lhs.overflowing_add(*rhs);
+                (*rhs, overflow) = (value, overflow | overflows);
+            }
+            if overflow { Err(Overflow) } else { Ok(()) }
+        }
+    };
+    u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize
+);
+impl_primitive_op!(
+    CheckedMul,
+    "Integer multiplication operation that fails with [`Overflow`] instead of overflowing.",
+    Overflow,
+    1,
+    |lhs, rhs| lhs.checked_mul(*rhs).ok_or(Overflow),
+    slices {
+        |lhs, rhs| {
+            let mut overflow = false;
+            for (lhs, rhs) in lhs.iter().zip(rhs) {
+                let (value, overflows) = lhs.overflowing_mul(*rhs);
+                (*rhs, overflow) = (value, overflow | overflows);
+            }
+            if overflow { Err(Overflow) } else { Ok(()) }
+        }
+    };
+    u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize
+);
+// saturating arithmetic is not associative on signed integers, e.g. `(100 + 100) - 100` saturates on `i8`
+impl_primitive_op!(
+    SaturatingAdd,
+    "Unsigned integer addition operation that saturates at the maximum value instead of overflowing.",
+    std::convert::Infallible,
+    0,
+    |lhs, rhs| Ok(lhs.saturating_add(*rhs));
+    u8, u16, u32, u64, u128, usize
+);
+impl_primitive_op!(
+    SaturatingMul,
+    "Unsigned integer multiplication operation that saturates at the maximum value instead of overflowing.",
+    std::convert::Infallible,
+    1,
+    |lhs, rhs| Ok(lhs.saturating_mul(*rhs));
+    u8, u16, u32, u64, u128, usize
+);
+impl_primitive_op!(
+    WrappingAdd,
+    "Integer addition operation that wraps around at the bounds of the integer type.",
+    std::convert::Infallible,
+    0,
+    |lhs, rhs| Ok(lhs.wrapping_add(*rhs));
+    u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize
+);
+impl_primitive_op!(
+    WrappingMul,
+    "Integer multiplication operation that wraps around at the bounds of the integer type.",
+    std::convert::Infallible,
+    1,
+    |lhs, rhs| Ok(lhs.wrapping_mul(*rhs));
+    u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize
+);
+
+impl_primitive_op!(
+    FiniteAdd,
+    "Floating-point addition operation that fails with [`NonFinite`] instead of returning an infinite or NaN value.\n\n\
+     The operation is only associative up to rounding errors.",
+    NonFinite,
+    0.0,
+    |lhs, rhs| Some(lhs + rhs).filter(|sum| sum.is_finite()).ok_or(NonFinite),
+    slices {
+        |lhs, rhs| {
+            for (lhs, rhs) in lhs.iter().zip(rhs.iter_mut()) {
+                *rhs += lhs;
+            }
+            if rhs.iter().all(|value| value.is_finite()) { Ok(()) } else { Err(NonFinite) }
+        }
+    };
+    f32, f64
+);
+impl_primitive_op!(
+    FiniteMul,
+    "Floating-point multiplication operation that fails with [`NonFinite`] instead of returning an infinite or NaN value.\n\n\
+     The operation is only associative up to rounding errors.",
+    NonFinite,
+    1.0,
+    |lhs, rhs| Some(lhs * rhs).filter(|product| product.is_finite()).ok_or(NonFinite),
+    slices {
+        |lhs, rhs| {
+            for (lhs, rhs) in lhs.iter().zip(rhs.iter_mut()) {
+                *rhs *= lhs;
+            }
+            if rhs.iter().all(|value| value.is_finite()) { Ok(()) } else { Err(NonFinite) }
+        }
+    };
+    f32, f64
+);
+
+/// Greatest common divisor operation on unsigned integers, `0` is its identity.
+#[derive(Debug)]
+pub struct CloneGcd;
+
+/// Least common multiple operation on unsigned integers, `1` is its identity.
+///
+/// Fails with [`Overflow`] if the least common multiple does not fit in the integer type.
+#[derive(Debug)]
+pub struct CloneLcm;
+
+macro_rules! impl_gcd_lcm {
+    ($($int:ty),*) => {
+        $(
+            impl AssociativeOperation<$int> for CloneGcd {
+                type Error = std::convert::Infallible;
+
+                #[inline]
+                fn associate(lhs: &$int, rhs: &$int) -> Result<$int, Self::Error> {
+                    let (mut a, mut b) = (*lhs, *rhs);
+                    while b != 0 {
+                        (a, b) = (b, a % b);
+                    }
+                    Ok(a)
+                }
+            }
+
+            impl AssociativeOperation<$int> for CloneLcm {
+                type Error = Overflow;
+
+                #[inline]
+                fn associate(lhs: &$int, rhs: &$int) -> Result<$int, Self::Error> {
+                    if *lhs == 0 || *rhs == 0 {
+                        return Ok(0);
+                    }
+                    let Ok(gcd) = CloneGcd::associate(lhs, rhs);
+                    (lhs / gcd).checked_mul(*rhs).ok_or(Overflow)
+                }
+            }
+
+            impl MonoidOperation<$int> for CloneGcd {
+                #[inline]
+                fn identity() -> $int {
+                    0
+                }
+            }
+
+            impl MonoidOperation<$int> for CloneLcm {
+                #[inline]
+                fn identity() -> $int {
+                    1
+                }
+            }
+
+            impl IdempotentOperation<$int> for CloneGcd {}
+
+            impl IdempotentOperation<$int> for CloneLcm {}
+
+            impl CommutativeOperation<$int> for CloneGcd {}
+
+            impl CommutativeOperation<$int> for CloneLcm {}
+        )*
+    };
+}
+
+impl_gcd_lcm!(u8, u16, u32, u64, u128, usize);
+
+/// Addition modulo `M` on `u64`, the result is always reduced in `0..M`.
+///
+/// Operands are not required to be reduced, but [`identity`](MonoidOperation::identity) and
+/// [`inverse`](GroupOperation::inverse) only satisfy their laws for reduced values. Using a modulus of `0` fails to compile.
+#[derive(Debug)]
+pub struct ModAdd<const M: u64>;
+
+impl<const M: u64> AssociativeOperation<u64> for ModAdd<M> {
+    type Error = std::convert::Infallible;
+
+    #[inline]
+    fn associate(lhs: &u64, rhs: &u64) -> Result<u64, Self::Error> {
+        const { assert!(M != 0, "the modulus must not be zero") };
+        Ok(((u128::from(*lhs) + u128::from(*rhs)) % u128::from(M)) as u64)
+    }
+}
+
+impl<const M: u64> MonoidOperation<u64> for ModAdd<M> {
+    #[inline]
+    fn identity() -> u64 {
+        0
+    }
+}
+
+impl<const M: u64> GroupOperation<u64> for ModAdd<M> {
+    #[inline]
+    fn inverse(value: &u64) -> Result<u64, Self::Error> {
+        Ok((M - value % M) % M)
+    }
+}
+
+impl<const M: u64> CommutativeOperation<u64> for ModAdd<M> {}
+
+/// Multiplication modulo `M` on `u64`, the result is always reduced in `0..M`.
+///
+/// Operands are not required to be reduced, but [`identity`](MonoidOperation::identity) only satisfies its law for
+/// reduced values and a modulus greater than `1`. Using a modulus of `0` fails to compile.
+#[derive(Debug)]
+pub struct ModMul<const M: u64>;
+
+impl<const M: u64> AssociativeOperation<u64> for ModMul<M> {
+    type Error = std::convert::Infallible;
+
+    #[inline]
+    fn associate(lhs: &u64, rhs: &u64) -> Result<u64, Self::Error> {
+        const { assert!(M != 0, "the modulus must not be zero") };
+        Ok(((u128::from(*lhs) * u128::from(*rhs)) % u128::from(M)) as u64)
+    }
+}
+
+impl<const M: u64> MonoidOperation<u64> for ModMul<M> {
+    #[inline]
+    fn identity() -> u64 {
+        1
+    }
+}
+
+impl<const M: u64> CommutativeOperation<u64> for ModMul<M> {}
+
+/// Defines an associative operation from a closure-like expression, without writing the [`AssociativeOperation`]
+/// implementation by hand.
+///
+/// The operation is a unit struct implementing [`AssociativeOperation`] for the given value type: the operands are bound
+/// by reference to the given names, and the expression computes the association. It is infallible unless an error type is
+/// given, in which case the expression returns a `Result`. Attributes and documentation comments are forwarded to the struct.
+///
+/// ```
+/// use elu::operation::AssociativeOperation;
+///
+/// elu::define_operation! {
+///     /// Concatenates strings.
+///     pub Concat, String, |lhs, rhs| format!("{lhs}{rhs}")
+/// }
+///
+/// elu::define_operation! {
+///     CheckedSub, u32, Error = &'static str, |lhs, rhs| lhs.checked_sub(*rhs).ok_or("overflow")
+/// }
+///
+/// assert_eq!(Ok(String::from("ab")), Concat::associate(&"a".into(), &"b".into()));
+/// assert_eq!(Err("overflow"), CheckedSub::associate(&1, &2));
+/// ```
+///
+/// The expression is not checked to be associative, see [`laws`] for tests.
+#[macro_export]
+macro_rules! define_operation {
+    ($(#[$attr:meta])* $vis:vis $name:ident, $value:ty, |$lhs:ident, $rhs:ident| $body:expr) => {
+        $crate::define_operation! {
+            $(#[$attr])* $vis $name, $value, Error = ::std::convert::Infallible, |$lhs, $rhs| Ok($body)
+        }
+    };
+    ($(#[$attr:meta])* $vis:vis $name:ident, $value:ty, Error = $error:ty, |$lhs:ident, $rhs:ident| $body:expr) => {
+        $(#[$attr])*
+        #[derive(Debug)]
+        $vis struct $name;
+
+        impl $crate::operation::AssociativeOperation<$value> for $name {
+            type Error = $error;
+
+            #[inline]
+            fn associate($lhs: &$value, $rhs: &$value) -> Result<$value, Self::Error> {
+                $body
+            }
+        }
+    };
+}
+
+/// Functions that check the laws of operations on sampled values.
+///
+/// Forests do not group the associations of the values of a path from left to right (e.g. after path compression),
+/// so an operation that is not associative silently produces wrong evaluations. These functions check that an operation
+/// satisfies its laws on every combination of some sample values, they are meant to be used in tests.
+///
+/// Checking a law on `n` values associates up to `n³` combinations (`n²` for laws on pairs).
+pub mod laws;
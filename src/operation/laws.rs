@@ -0,0 +1,459 @@
+use super::{AssociativeOperation, GroupOperation, IdempotentOperation, MonoidOperation};
+
+/// A combination of values for which a law does not hold.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Violation<V, E> {
+    /// `(a ⊕ b) ⊕ c` differs from `a ⊕ (b ⊕ c)`.
+    Associativity(V, V, V),
+    /// `a ⊕ b` differs from `b ⊕ a`.
+    Commutativity(V, V),
+    /// `a ⊕ a` differs from `a`.
+    Idempotence(V),
+    /// `e ⊕ a` or `a ⊕ e` differs from `a`, where `e` is the claimed identity.
+    Identity(V),
+    /// `inverse(v) ⊕ (v ⊕ a)` or `(a ⊕ v) ⊕ inverse(v)` differs from `a`, given as `(v, a)`.
+    Inverse(V, V),
+    /// The operation failed.
+    Operation(E),
+}
+
+/// Checks that `(a ⊕ b) ⊕ c` is `a ⊕ (b ⊕ c)` for all values `a`, `b` and `c` of the sample.
+///
+/// # Errors
+/// Will return the first [`Violation::Associativity`] found, or [`Violation::Operation`] if the operation fails.
+pub fn check_associativity<V, O>(values: &[V]) -> Result<(), Violation<V, O::Error>>
+where
+    V: Clone + PartialEq,
+    O: AssociativeOperation<V>,
+{
+    for a in values {
+        for b in values {
+            let ab = O::associate(a, b).map_err(Violation::Operation)?;
+            for c in values {
+                let bc = O::associate(b, c).map_err(Violation::Operation)?;
+                let lhs = O::associate(&ab, c).map_err(Violation::Operation)?;
+                let rhs = O::associate(a, &bc).map_err(Violation::Operation)?;
+                if lhs != rhs {
+                    return Err(Violation::Associativity(a.clone(), b.clone(), c.clone()));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Checks that `a ⊕ b` is `b ⊕ a` for all values `a` and `b` of the sample, e.g. to test an implementation of
+/// [`CommutativeOperation`](super::CommutativeOperation).
+///
+/// # Errors
+/// Will return the first [`Violation::Commutativity`] found, or [`Violation::Operation`] if the operation fails.
+pub fn check_commutativity<V, O>(values: &[V]) -> Result<(), Violation<V, O::Error>>
+where
+    V: Clone + PartialEq,
+    O: AssociativeOperation<V>,
+{
+    for (i, a) in values.iter().enumerate() {
+        for b in &values[i + 1..] {
+            let ab = O::associate(a, b).map_err(Violation::Operation)?;
+            let ba = O::associate(b, a).map_err(Violation::Operation)?;
+            if ab != ba {
+                return Err(Violation::Commutativity(a.clone(), b.clone()));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Checks that `identity` ⊕ `a` and `a` ⊕ `identity` are `a` for every value `a` of the sample.
+///
+/// # Errors
+/// Will return the first [`Violation::Identity`] found, or [`Violation::Operation`] if the operation fails.
+pub fn check_identity<V, O>(identity: &V, values: &[V]) -> Result<(), Violation<V, O::Error>>
+where
+    V: Clone + PartialEq,
+    O: AssociativeOperation<V>,
+{
+    for a in values {
+        let lhs = O::associate(identity, a).map_err(Violation::Operation)?;
+        let rhs = O::associate(a, identity).map_err(Violation::Operation)?;
+        if lhs != *a || rhs != *a {
+            return Err(Violation::Identity(a.clone()));
+        }
+    }
+    Ok(())
+}
+
+/// Checks that `a ⊕ a` is `a` for every value `a` of the sample, as claimed by [`IdempotentOperation`].
+///
+/// # Errors
+/// Will return the first [`Violation::Idempotence`] found, or [`Violation::Operation`] if the operation fails.
+pub fn check_idempotence<V, O>(values: &[V]) -> Result<(), Violation<V, O::Error>>
+where
+    V: Clone + PartialEq,
+    O: IdempotentOperation<V>,
+{
+    for a in values {
+        if O::associate(a, a).map_err(Violation::Operation)? != *a {
+            return Err(Violation::Idempotence(a.clone()));
+        }
+    }
+    Ok(())
+}
+
+/// Checks that [`identity`](MonoidOperation::identity) is the identity of `O` on the values of the sample,
+/// see [`check_identity`].
+///
+/// # Errors
+/// Will return the first [`Violation::Identity`] found, or [`Violation::Operation`] if the operation fails.
+pub fn check_monoid<V, O>(values: &[V]) -> Result<(), Violation<V, O::Error>>
+where
+    V: Clone + PartialEq,
+    O: MonoidOperation<V>,
+{
+    check_identity::<V, O>(&O::identity(), values)
+}
+
+/// Checks that [`inverse`](GroupOperation::inverse) satisfies the laws of [`GroupOperation`] for all values `v` and `a`
+/// of the sample.
+///
+/// # Errors
+/// Will return the first [`Violation::Inverse`] found, or [`Violation::Operation`] if the operation fails.
+pub fn check_inverse<V, O>(values: &[V]) -> Result<(), Violation<V, O::Error>>
+where
+    V: Clone + PartialEq,
+    O: GroupOperation<V>,
+{
+    for v in values {
+        let inverse = O::inverse(v).map_err(Violation::Operation)?;
+        for a in values {
+            let va = O::associate(v, a).map_err(Violation::Operation)?;
+            let av = O::associate(a, v).map_err(Violation::Operation)?;
+            let lhs = O::associate(&inverse, &va).map_err(Violation::Operation)?;
+            let rhs = O::associate(&av, &inverse).map_err(Violation::Operation)?;
+            if lhs != *a || rhs != *a {
+                return Err(Violation::Inverse(v.clone(), a.clone()));
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::operation::*;
+
+    crate::define_operation!(Sub, i64, |lhs, rhs| lhs - rhs);
+
+    #[test]
+    fn add_laws() {
+        let values = [-3i64, 0, 1, 7, 12];
+        assert_eq!(Ok(()), check_associativity::<_, CloneAdd>(&values));
+        assert_eq!(Ok(()), check_commutativity::<_, CloneAdd>(&values));
+        assert_eq!(Ok(()), check_identity::<_, CloneAdd>(&0, &values));
+        assert_eq!(Ok(()), check_inverse::<_, CloneAdd>(&values));
+        assert_eq!(
+            Err(Violation::Identity(-3)),
+            check_identity::<_, CloneAdd>(&1, &values)
+        );
+    }
+
+    #[test]
+    fn min_max_laws() {
+        let values = [5u8, 0, 3, 3, 255];
+        assert_eq!(Ok(()), check_associativity::<_, CloneMin>(&values));
+        assert_eq!(Ok(()), check_commutativity::<_, CloneMin>(&values));
+        assert_eq!(Ok(()), check_identity::<_, CloneMin>(&255, &values));
+        assert_eq!(Ok(()), check_associativity::<_, CloneMax>(&values));
+        assert_eq!(Ok(()), check_identity::<_, CloneMax>(&0, &values));
+    }
+
+    #[test]
+    fn gcd_lcm_laws() {
+        let values = [0u32, 1, 4, 6, 9, 12];
+        assert_eq!(Ok(()), check_associativity::<_, CloneGcd>(&values));
+        assert_eq!(Ok(()), check_identity::<_, CloneGcd>(&0, &values));
+        assert_eq!(Ok(()), check_associativity::<_, CloneLcm>(&values));
+        assert_eq!(Ok(()), check_commutativity::<_, CloneLcm>(&values));
+        assert_eq!(Ok(()), check_identity::<_, CloneLcm>(&1, &values));
+        assert_eq!(Ok(36), CloneLcm::associate(&12u8, &9));
+        assert_eq!(
+            Err(Violation::Operation(Overflow)),
+            check_associativity::<_, CloneLcm>(&[16u8, 17])
+        );
+    }
+
+    #[test]
+    fn checked_laws() {
+        assert_eq!(
+            Ok(()),
+            check_associativity::<_, CheckedAdd>(&[-5i8, 0, 3, 40])
+        );
+        assert_eq!(
+            Err(Violation::Operation(Overflow)),
+            check_associativity::<_, CheckedAdd>(&[100i8, 30])
+        );
+        assert_eq!(
+            Ok(()),
+            check_identity::<_, CheckedMul>(&1, &[0u64, 7, u64::MAX])
+        );
+        assert_eq!(Err(Overflow), CheckedMul::associate(&i32::MIN, &-1));
+    }
+
+    #[test]
+    fn saturating_wrapping_laws() {
+        let values = [0u8, 1, 2, 100, 200, 255];
+        assert_eq!(Ok(()), check_associativity::<_, SaturatingAdd>(&values));
+        assert_eq!(Ok(()), check_associativity::<_, SaturatingMul>(&values));
+        assert_eq!(Ok(()), check_associativity::<_, WrappingAdd>(&values));
+        assert_eq!(Ok(()), check_associativity::<_, WrappingMul>(&values));
+        assert_eq!(
+            Ok(()),
+            check_associativity::<_, WrappingMul>(&[i8::MIN, -3, -1, 0, 5, i8::MAX])
+        );
+        assert_eq!(Ok(255), SaturatingAdd::associate(&200u8, &100));
+        assert_eq!(Ok(44), WrappingAdd::associate(&200u8, &100));
+    }
+
+    #[test]
+    fn affine_laws() {
+        let values = [(1i64, 0), (2, 0), (1, 3), (-1, 2), (5, -1)];
+        assert_eq!(Ok(()), check_associativity::<_, Affine>(&values));
+        assert_eq!(Ok(()), check_identity::<_, Affine>(&(1, 0), &values));
+        assert_eq!(
+            Err(Violation::Commutativity((2, 0), (1, 3))),
+            check_commutativity::<_, Affine>(&values)
+        );
+    }
+
+    #[test]
+    fn set_laws() {
+        use std::collections::{BTreeSet, HashSet};
+
+        let values: Vec<BTreeSet<u8>> =
+            vec![BTreeSet::new(), [1].into(), [1, 2].into(), [2, 3, 4].into()];
+        assert_eq!(Ok(()), check_associativity::<_, CloneUnion>(&values));
+        assert_eq!(Ok(()), check_commutativity::<_, CloneUnion>(&values));
+        assert_eq!(
+            Ok(()),
+            check_identity::<_, CloneUnion>(&BTreeSet::new(), &values)
+        );
+        assert_eq!(Ok(()), check_associativity::<_, CloneIntersection>(&values));
+        assert_eq!(Ok(()), check_commutativity::<_, CloneIntersection>(&values));
+
+        let values: Vec<HashSet<u8>> = values
+            .into_iter()
+            .map(|set| set.into_iter().collect())
+            .collect();
+        assert_eq!(Ok(()), check_associativity::<_, CloneUnion>(&values));
+        assert_eq!(Ok(()), check_associativity::<_, CloneIntersection>(&values));
+        assert_eq!(
+            Ok(HashSet::from([2])),
+            CloneIntersection::associate(&values[2], &values[3])
+        );
+    }
+
+    #[test]
+    fn pair_laws() {
+        let values = [(0u8, 4u8), (1, 0), (200, 9), (100, 4)];
+        assert_eq!(
+            Ok(()),
+            check_associativity::<_, Pair<CloneGcd, CloneMax>>(&values)
+        );
+        assert_eq!(
+            Ok(()),
+            check_identity::<_, Pair<CloneGcd, CloneMax>>(&(0, 0), &values)
+        );
+        assert_eq!(
+            Ok((44, 4)),
+            Pair::<WrappingAdd, CloneMin>::associate(&values[2], &values[3])
+        );
+        assert_eq!(
+            Err(Overflow),
+            Pair::<CheckedAdd, CloneMin>::associate(&values[2], &values[3])
+        );
+    }
+
+    #[test]
+    fn flip_laws() {
+        let values = [(1i64, 0), (2, 0), (1, 3), (-1, 2)];
+        assert_eq!(Ok(()), check_associativity::<_, Flip<Affine>>(&values));
+        assert_eq!(Ok((2, 6)), Flip::<Affine>::associate(&(2, 0), &(1, 3)));
+        assert_eq!(Ok(()), check_inverse::<_, Flip<CloneAdd>>(&[1i32, -4, 9]));
+        assert_eq!(Ok(false), Flip::<KeepLhs>::selects_lhs(&1, &2));
+        assert_eq!(Ok(false), Flip::<CloneMin>::selects_lhs(&3, &3));
+    }
+
+    #[test]
+    fn lift_laws() {
+        let values = [None, Some(2i64), Some(-1), Some(5)];
+        assert_eq!(Ok(()), check_associativity::<_, Lift<CloneMul>>(&values));
+        assert_eq!(Ok(()), check_identity::<_, Lift<CloneMul>>(&None, &values));
+        assert_eq!(Ok(()), check_identity::<_, Lift<CloneMin>>(&None, &values));
+        assert_eq!(Ok(false), Lift::<CloneMin>::selects_lhs(&None, &Some(3)));
+        assert_eq!(Ok(true), Lift::<CloneMax>::selects_lhs(&Some(3), &None));
+        assert_eq!(
+            Err(Violation::Operation(Overflow)),
+            check_associativity::<_, Lift<CheckedAdd>>(&[Some(i8::MAX), None])
+        );
+    }
+
+    #[test]
+    fn monoid_laws() {
+        assert_eq!(Ok(()), check_monoid::<_, CheckedAdd>(&[-5i8, 0, 3, 40]));
+        assert_eq!(Ok(()), check_monoid::<_, WrappingMul>(&[0u16, 7, u16::MAX]));
+        assert_eq!(Ok(()), check_monoid::<_, CloneLcm>(&[0u32, 1, 4, 6]));
+        assert_eq!(Ok(()), check_monoid::<_, Lift<CloneMin>>(&[None, Some(3)]));
+        assert_eq!(
+            Ok(()),
+            check_monoid::<_, Pair<CloneGcd, Flip<SaturatingAdd>>>(&[(0u8, 4u8), (6, 255)])
+        );
+        assert_eq!(
+            Ok(()),
+            check_monoid::<_, CloneUnion>(&[std::collections::BTreeSet::from([1, 2])])
+        );
+    }
+
+    #[test]
+    fn idempotence_laws() {
+        let values = [0u8, 1, 6, 9, 255];
+        assert_eq!(Ok(()), check_idempotence::<_, CloneMin>(&values));
+        assert_eq!(Ok(()), check_idempotence::<_, CloneBitAnd>(&values));
+        assert_eq!(Ok(()), check_idempotence::<_, CloneBitOr>(&values));
+        assert_eq!(Ok(()), check_idempotence::<_, CloneGcd>(&values));
+        assert_eq!(Ok(()), check_idempotence::<_, CloneLcm>(&values));
+        assert_eq!(
+            Ok(()),
+            check_idempotence::<_, Pair<KeepRhs, Flip<CloneMax>>>(&[(1u8, 2u8), (3, 0)])
+        );
+        assert_eq!(
+            Ok(()),
+            check_idempotence::<_, Lift<CloneIntersection>>(&[
+                None,
+                Some(std::collections::BTreeSet::from([1, 2]))
+            ])
+        );
+    }
+
+    #[test]
+    fn commutative_laws() {
+        fn check<V, O>(values: &[V]) -> Result<(), Violation<V, O::Error>>
+        where
+            V: Clone + PartialEq,
+            O: CommutativeOperation<V>,
+        {
+            check_commutativity::<V, O>(values)
+        }
+
+        let values = [0u16, 3, 10, 12, u16::MAX];
+        assert_eq!(Ok(()), check::<_, CloneAdd>(&[-2i64, 5, 9]));
+        assert_eq!(Ok(()), check::<_, CloneBitXor>(&values));
+        assert_eq!(Ok(()), check::<_, CloneMax>(&values));
+        assert_eq!(Ok(()), check::<_, WrappingMul>(&values));
+        assert_eq!(Ok(()), check::<_, SaturatingAdd>(&values));
+        assert_eq!(Ok(()), check::<_, CloneGcd>(&values));
+        assert_eq!(
+            Ok(()),
+            check::<_, Lift<Flip<CloneMin>>>(&[None, Some(4), Some(1)])
+        );
+        assert_eq!(
+            Ok(()),
+            check::<_, Pair<CloneUnion, CloneBitOr>>(&[
+                (std::collections::BTreeSet::from([1]), 4u8),
+                (std::collections::BTreeSet::from([2, 3]), 1)
+            ])
+        );
+    }
+
+    #[test]
+    fn permutation_laws() {
+        let values = [[0, 1, 2], [1, 2, 0], [1, 0, 2], [2, 1, 0]];
+        assert_eq!(Ok(()), check_associativity::<_, Permutation>(&values));
+        assert_eq!(Ok(()), check_monoid::<_, Permutation>(&values));
+        assert_eq!(Ok(()), check_inverse::<_, Permutation>(&values));
+        assert_eq!(
+            Err(Violation::Commutativity([1, 2, 0], [1, 0, 2])),
+            check_commutativity::<_, Permutation>(&values)
+        );
+
+        let values: Vec<Vec<usize>> = values.iter().map(|p| p.to_vec()).collect();
+        assert_eq!(Ok(()), check_associativity::<_, Permutation>(&values));
+        assert_eq!(Ok(()), check_inverse::<_, Permutation>(&values));
+        assert_eq!(
+            Err(Violation::Operation(LengthMismatch)),
+            check_associativity::<_, Permutation>(&[vec![0], vec![1, 0]])
+        );
+    }
+
+    #[test]
+    fn modular_laws() {
+        const P: u64 = 1_000_000_007;
+        let values = [0, 1, 2, 12345, P - 1];
+        assert_eq!(Ok(()), check_associativity::<_, ModAdd<P>>(&values));
+        assert_eq!(Ok(()), check_monoid::<_, ModAdd<P>>(&values));
+        assert_eq!(Ok(()), check_inverse::<_, ModAdd<P>>(&values));
+        assert_eq!(Ok(()), check_associativity::<_, ModMul<P>>(&values));
+        assert_eq!(Ok(()), check_monoid::<_, ModMul<P>>(&values));
+        assert_eq!(Ok(()), check_commutativity::<_, ModMul<P>>(&values));
+        assert_eq!(Ok(P - 2), ModAdd::<P>::associate(&(P - 1), &(P - 1)));
+        assert_eq!(Ok(1), ModMul::<P>::associate(&(P - 1), &(P - 1)));
+        assert_eq!(
+            Ok((u64::MAX % 1000) * (u64::MAX % 1000) % 1000),
+            ModMul::<1000>::associate(&u64::MAX, &u64::MAX)
+        );
+    }
+
+    #[test]
+    fn finite_laws() {
+        // exactly representable values so that rounding does not break associativity
+        let values = [0.0f64, 0.5, -2.0, 8.25];
+        assert_eq!(Ok(()), check_associativity::<_, FiniteAdd>(&values));
+        assert_eq!(Ok(()), check_monoid::<_, FiniteAdd>(&values));
+        assert_eq!(Ok(()), check_associativity::<_, FiniteMul>(&values));
+        assert_eq!(Ok(()), check_monoid::<_, FiniteMul>(&values));
+        assert_eq!(Err(NonFinite), FiniteAdd::associate(&f32::MAX, &f32::MAX));
+        assert_eq!(Err(NonFinite), FiniteMul::associate(&f64::INFINITY, &0.0));
+        assert_eq!(Err(NonFinite), FiniteAdd::associate(&f64::NAN, &1.0));
+    }
+
+    #[test]
+    fn interval_laws() {
+        let values = [(0, 10), (2, 7), (5, 12), (-3, 6)];
+        assert_eq!(
+            Ok(()),
+            check_associativity::<_, IntervalIntersection>(&values)
+        );
+        assert_eq!(
+            Ok(()),
+            check_commutativity::<_, IntervalIntersection>(&values)
+        );
+        assert_eq!(
+            Ok(()),
+            check_idempotence::<_, IntervalIntersection>(&values)
+        );
+        assert_eq!(Ok(()), check_associativity::<_, IntervalHull>(&values));
+        assert_eq!(Ok(()), check_idempotence::<_, IntervalHull>(&values));
+        assert_eq!(
+            Ok((5, 6)),
+            IntervalIntersection::associate(&(-3, 6), &(5, 12))
+        );
+        assert_eq!(Ok((-3, 12)), IntervalHull::associate(&(-3, 6), &(5, 12)));
+        assert_eq!(
+            Err(Violation::Operation(EmptyInterval)),
+            check_associativity::<_, IntervalIntersection>(&[(0, 1), (2, 3)])
+        );
+    }
+
+    #[test]
+    fn violations() {
+        let values = [0i64, 1, 2];
+        assert_eq!(
+            Err(Violation::Associativity(0, 0, 1)),
+            check_associativity::<_, Sub>(&values)
+        );
+        assert_eq!(
+            Err(Violation::Commutativity(0, 1)),
+            check_commutativity::<_, Sub>(&values)
+        );
+    }
+}
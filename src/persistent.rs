@@ -0,0 +1,421 @@
+use std::marker::PhantomData;
+use std::rc::Rc;
+
+use crate::index::Index;
+use crate::node::Node;
+use crate::operation::{AssociativeOperation, DefaultOperation};
+use crate::ForestError;
+
+// A binary trie storing nodes at its leaves, shared between versions.
+#[derive(Debug)]
+enum Trie<V> {
+    Leaf(Node<V>),
+    Branch([Option<Rc<Trie<V>>>; 2]),
+}
+
+impl<V: Clone> Trie<V> {
+    fn get(mut trie: &Rc<Self>, depth: u32, key: usize) -> &Node<V> {
+        for level in (0..depth).rev() {
+            trie = match trie.as_ref() {
+                Self::Branch(children) => children[(key >> level) & 1].as_ref().unwrap(),
+                Self::Leaf(_) => unreachable!(),
+            };
+        }
+        match trie.as_ref() {
+            Self::Leaf(node) => node,
+            Self::Branch(_) => unreachable!(),
+        }
+    }
+
+    // Path copying: only the branches on the path to `key` are duplicated.
+    fn set(trie: Option<&Rc<Self>>, depth: u32, key: usize, node: Node<V>) -> Rc<Self> {
+        if depth == 0 {
+            return Rc::new(Self::Leaf(node));
+        }
+        let mut children = match trie.map(Rc::as_ref) {
+            Some(Self::Branch(children)) => children.clone(),
+            Some(Self::Leaf(_)) => unreachable!(),
+            None => [None, None],
+        };
+        let bit = (key >> (depth - 1)) & 1;
+        children[bit] = Some(Self::set(children[bit].as_ref(), depth - 1, key, node));
+        Rc::new(Self::Branch(children))
+    }
+}
+
+#[derive(Debug)]
+struct Snapshot<V> {
+    trie: Option<Rc<Trie<V>>>,
+    depth: u32,
+    len: usize,
+}
+
+impl<V> Clone for Snapshot<V> {
+    fn clone(&self) -> Self {
+        Self {
+            trie: self.trie.clone(),
+            depth: self.depth,
+            len: self.len,
+        }
+    }
+}
+
+/// A handle identifying a version of a [`PersistentForest`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Version(usize);
+
+/// A fully persistent EVAL-LINK-UPDATE forest structure.
+///
+/// Every operation that modifies the forest creates a new [`Version`] and leaves the previous ones untouched, so that any
+/// version can still be evaluated or modified later on. Versions share their unmodified nodes with path copying.
+///
+/// Evaluations are read-only, hence no path compression is performed: they walk the whole path from the root,
+/// each step accessing a node in `O(log n)` time.
+///
+/// `V` is the value type associated to nodes in the forest and `O` is the associative operation applied when evaluating.
+#[derive(Debug)]
+pub struct PersistentForest<V, O = DefaultOperation>
+where
+    O: 'static,
+{
+    versions: Vec<Snapshot<V>>,
+    _op: PhantomData<O>,
+}
+
+impl<V, O> Clone for PersistentForest<V, O> {
+    fn clone(&self) -> Self {
+        Self {
+            versions: self.versions.clone(),
+            _op: PhantomData,
+        }
+    }
+}
+
+impl<V, O> Default for PersistentForest<V, O>
+where
+    V: Clone,
+    O: AssociativeOperation<V>,
+{
+    #[inline]
+    fn default() -> Self {
+        Self {
+            versions: vec![Snapshot {
+                trie: None,
+                depth: 0,
+                len: 0,
+            }],
+            _op: PhantomData,
+        }
+    }
+}
+
+impl<V, O> PersistentForest<V, O>
+where
+    V: Clone,
+    O: AssociativeOperation<V>,
+{
+    /// Creates a new forest whose only version is empty.
+    #[inline]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the empty version the forest was created with.
+    #[inline]
+    #[must_use]
+    pub fn initial_version(&self) -> Version {
+        Version(0)
+    }
+
+    /// Returns the most recently created version.
+    #[inline]
+    #[must_use]
+    pub fn latest_version(&self) -> Version {
+        Version(self.versions.len() - 1)
+    }
+
+    /// Returns the number of versions of the forest.
+    #[inline]
+    #[must_use]
+    pub fn version_count(&self) -> usize {
+        self.versions.len()
+    }
+
+    /// Creates a new version with a new tree root with the given value in the given version.
+    ///
+    /// # Errors
+    /// Will return [`ForestError::InvalidId`] if the version does not belong to the forest.
+    pub fn try_new_root(
+        &mut self,
+        version: Version,
+        value: V,
+    ) -> Result<(Version, Index<Self>), ForestError<O::Error>> {
+        let mut snapshot = self.snapshot(version)?.clone();
+        let key = snapshot.len;
+        if key == 1 << snapshot.depth {
+            // the trie is full, add a level above it
+            snapshot.trie = Some(Rc::new(Trie::Branch([snapshot.trie.take(), None])));
+            snapshot.depth += 1;
+        }
+        snapshot.trie = Some(Trie::set(
+            snapshot.trie.as_ref(),
+            snapshot.depth,
+            key,
+            Node::new_root(value),
+        ));
+        snapshot.len += 1;
+        Ok((self.push(snapshot), Index::new(key)))
+    }
+
+    /// Infallible version of [`try_new_root`](PersistentForest::try_new_root).
+    ///
+    /// # Panics
+    /// Panics if the version does not belong to the forest.
+    #[inline]
+    pub fn new_root(&mut self, version: Version, value: V) -> (Version, Index<Self>) {
+        match self.try_new_root(version, value) {
+            Ok(created) => created,
+            Err(_) => panic!("version does not belong to this forest"),
+        }
+    }
+
+    /// Finds the root of the tree that contains the node identified by `id` in the given version.
+    ///
+    /// # Errors
+    /// Will return [`ForestError::InvalidId`] if the version or the id does not belong to the forest.
+    pub fn try_find_root(
+        &self,
+        version: Version,
+        id: Index<Self>,
+    ) -> Result<Index<Self>, ForestError<O::Error>> {
+        let snapshot = self.snapshot(version)?;
+        let key = Self::check(snapshot, id)?;
+        Ok(Index::new(Self::root_key(snapshot, key)))
+    }
+
+    /// Infallible version of [`try_find_root`](PersistentForest::try_find_root).
+    ///
+    /// # Panics
+    /// Panics if the version or the id does not belong to the forest.
+    #[inline]
+    pub fn find_root(&self, version: Version, id: Index<Self>) -> Index<Self> {
+        match self.try_find_root(version, id) {
+            Ok(root) => root,
+            Err(_) => panic!("version or id does not belong to this forest"),
+        }
+    }
+
+    /// Computes the value of the node identified by `id` in the given version.
+    ///
+    /// # Errors
+    /// Will return [`ForestError::InvalidId`] if the version or the id does not belong to the forest
+    /// and [`ForestError::Operation`] if [`O::associate`](AssociativeOperation::associate) fails.
+    pub fn try_eval(&self, version: Version, id: Index<Self>) -> Result<V, ForestError<O::Error>> {
+        let snapshot = self.snapshot(version)?;
+        let mut key = Self::check(snapshot, id)?;
+
+        let mut path = vec![];
+        loop {
+            let node = Self::get(snapshot, key);
+            path.push(node);
+            match node.parent() {
+                None => break,
+                Some(parent_key) => key = parent_key,
+            }
+        }
+
+        let root = path.pop().unwrap();
+        path.iter()
+            .rev()
+            .try_fold(root.value().clone(), |value, node| {
+                O::associate(&value, node.value()).map_err(ForestError::Operation)
+            })
+    }
+
+    /// Creates a new version in which the root of the tree that contains the node identified by `id_b` is linked to the
+    /// node identified by `id_a`, in the given version.
+    ///
+    /// # Errors
+    /// Will return [`ForestError::InvalidId`] if the version or an id does not belong to the forest
+    /// and [`ForestError::WouldCreateCycle`] if both nodes already belong to the same tree.
+    pub fn try_link(
+        &mut self,
+        version: Version,
+        id_a: Index<Self>,
+        id_b: Index<Self>,
+    ) -> Result<Version, ForestError<O::Error>> {
+        let snapshot = self.snapshot(version)?;
+        let id_a = Self::check(snapshot, id_a)?;
+        let id_b = Self::check(snapshot, id_b)?;
+
+        let root_b_key = Self::root_key(snapshot, id_b);
+        if Self::root_key(snapshot, id_a) == root_b_key {
+            return Err(ForestError::WouldCreateCycle);
+        }
+
+        let mut root_b = Self::get(snapshot, root_b_key).clone();
+        root_b.set_parent(id_a);
+        let snapshot = Self::set(snapshot, root_b_key, root_b);
+        Ok(self.push(snapshot))
+    }
+
+    /// Creates a new version in which the value of the root of the tree that contains the node identified by `id` is
+    /// replaced, in the given version.
+    ///
+    /// # Errors
+    /// Will return [`ForestError::InvalidId`] if the version or the id does not belong to the forest.
+    pub fn try_update(
+        &mut self,
+        version: Version,
+        id: Index<Self>,
+        value: V,
+    ) -> Result<Version, ForestError<O::Error>> {
+        let snapshot = self.snapshot(version)?;
+        let key = Self::check(snapshot, id)?;
+
+        let root_key = Self::root_key(snapshot, key);
+        let mut root = Self::get(snapshot, root_key).clone();
+        root.set_value(value);
+        let snapshot = Self::set(snapshot, root_key, root);
+        Ok(self.push(snapshot))
+    }
+
+    fn snapshot(&self, version: Version) -> Result<&Snapshot<V>, ForestError<O::Error>> {
+        self.versions.get(version.0).ok_or(ForestError::InvalidId)
+    }
+
+    fn push(&mut self, snapshot: Snapshot<V>) -> Version {
+        self.versions.push(snapshot);
+        self.latest_version()
+    }
+
+    fn check(snapshot: &Snapshot<V>, id: Index<Self>) -> Result<usize, ForestError<O::Error>> {
+        let key: usize = id.into();
+        if key < snapshot.len {
+            Ok(key)
+        } else {
+            Err(ForestError::InvalidId)
+        }
+    }
+
+    fn get(snapshot: &Snapshot<V>, key: usize) -> &Node<V> {
+        // a valid key implies a non-empty trie
+        Trie::get(snapshot.trie.as_ref().unwrap(), snapshot.depth, key)
+    }
+
+    fn set(snapshot: &Snapshot<V>, key: usize, node: Node<V>) -> Snapshot<V> {
+        Snapshot {
+            trie: Some(Trie::set(snapshot.trie.as_ref(), snapshot.depth, key, node)),
+            depth: snapshot.depth,
+            len: snapshot.len,
+        }
+    }
+
+    fn root_key(snapshot: &Snapshot<V>, mut key: usize) -> usize {
+        while let Some(parent_key) = Self::get(snapshot, key).parent() {
+            key = parent_key;
+        }
+        key
+    }
+}
+
+impl<V, O> PersistentForest<V, O>
+where
+    V: Clone,
+    O: AssociativeOperation<V, Error = std::convert::Infallible>,
+{
+    /// Infallible version of [`try_eval`](PersistentForest::try_eval).
+    ///
+    /// # Panics
+    /// Panics if the version or the id does not belong to the forest.
+    #[inline]
+    pub fn eval(&self, version: Version, id: Index<Self>) -> V {
+        self.try_eval(version, id).unwrap()
+    }
+
+    /// Infallible version of [`try_link`](PersistentForest::try_link).
+    ///
+    /// # Panics
+    /// Panics if the version or an id does not belong to the forest or if both nodes already belong to the same tree.
+    #[inline]
+    pub fn link(&mut self, version: Version, id_a: Index<Self>, id_b: Index<Self>) -> Version {
+        self.try_link(version, id_a, id_b).unwrap()
+    }
+
+    /// Infallible version of [`try_update`](PersistentForest::try_update).
+    ///
+    /// # Panics
+    /// Panics if the version or the id does not belong to the forest.
+    #[inline]
+    pub fn update(&mut self, version: Version, id: Index<Self>, value: V) -> Version {
+        self.try_update(version, id, value).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::operation::*;
+
+    #[test]
+    fn add_forest() {
+        let mut forest: PersistentForest<usize, CloneAdd> = PersistentForest::new();
+        let mut version = forest.initial_version();
+        let mut ids = vec![];
+        for value in 2..6 {
+            let (new_version, id) = forest.new_root(version, value);
+            version = new_version;
+            ids.push(id);
+        }
+        let (v0, v1, v2, v3) = (ids[0], ids[1], ids[2], ids[3]);
+
+        let linked = forest.link(version, v0, v1);
+        let linked = forest.link(linked, v2, v3);
+        let merged = forest.link(linked, v3, v0);
+        let updated = forest.update(merged, v1, 1);
+        let branched = forest.link(linked, v1, v3);
+
+        assert_eq!(3, forest.eval(version, v1));
+        assert_eq!(5, forest.eval(linked, v1));
+        assert_eq!(14, forest.eval(merged, v1));
+        assert_eq!(11, forest.eval(updated, v1));
+        assert_eq!(14, forest.eval(branched, v3));
+        assert_eq!(v2, forest.find_root(merged, v1));
+        assert_eq!(v0, forest.find_root(branched, v2));
+        assert_eq!(
+            Err(ForestError::InvalidId),
+            forest.try_eval(forest.initial_version(), v0)
+        );
+        assert_eq!(
+            Err(ForestError::WouldCreateCycle),
+            forest.try_link(merged, v0, v2)
+        );
+    }
+
+    #[test]
+    fn many_versions() {
+        let mut forest: PersistentForest<usize, CloneAdd> = PersistentForest::new();
+        let mut versions = vec![forest.initial_version()];
+        let mut ids = vec![];
+        for value in 0..100 {
+            let (version, id) = forest.new_root(*versions.last().unwrap(), value);
+            versions.push(version);
+            ids.push(id);
+        }
+        for i in 1..100 {
+            let version = forest.link(*versions.last().unwrap(), ids[i - 1], ids[i]);
+            versions.push(version);
+        }
+
+        // after linking the first i + 1 nodes, the chain is evaluated up to node i
+        for i in 0..100 {
+            let version = versions[100 + i];
+            assert_eq!((0..=i).sum::<usize>(), forest.eval(version, ids[i]));
+            if i < 99 {
+                assert_eq!(99, forest.eval(version, ids[99]));
+            }
+        }
+        assert_eq!(200, forest.version_count());
+    }
+}
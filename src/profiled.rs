@@ -0,0 +1,212 @@
+use std::time::{Duration, Instant};
+
+use crate::{EvalLinkUpdate, ForestError, NewRoot, OperationError};
+
+/// The number of calls of an operation and the wall-clock time they took.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct OperationProfile {
+    /// Number of calls, failed calls included.
+    pub calls: u64,
+    /// Total time spent in these calls.
+    pub time: Duration,
+}
+
+impl OperationProfile {
+    /// Returns the mean time of a call, or `None` if there was no call.
+    #[inline]
+    #[must_use]
+    pub fn mean_time(&self) -> Option<Duration> {
+        (self.calls > 0).then(|| self.time.div_f64(self.calls as f64))
+    }
+}
+
+/// The profile of every operation of a [`ProfiledForest`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Profile {
+    /// Profile of [`new_root`](NewRoot::new_root).
+    pub new_root: OperationProfile,
+    /// Profile of [`try_find_root`](EvalLinkUpdate::try_find_root).
+    pub find_root: OperationProfile,
+    /// Profile of [`try_eval`](EvalLinkUpdate::try_eval), every node evaluated by
+    /// [`try_eval_many`](EvalLinkUpdate::try_eval_many) counts as a call.
+    pub eval: OperationProfile,
+    /// Profile of [`try_link`](EvalLinkUpdate::try_link).
+    pub link: OperationProfile,
+    /// Profile of [`try_update`](EvalLinkUpdate::try_update).
+    pub update: OperationProfile,
+}
+
+/// A wrapper that measures the number of calls and the wall-clock time of every operation of an EVAL-LINK-UPDATE structure.
+///
+/// Operations are forwarded to the wrapped forest, so that different structures can be compared on the same workload
+/// (see [`profile`](ProfiledForest::profile)). Measuring time has a small cost of its own, which is included.
+///
+/// `F` is the wrapped EVAL-LINK-UPDATE structure.
+#[derive(Debug, Clone, Default)]
+pub struct ProfiledForest<F> {
+    forest: F,
+    profile: Profile,
+}
+
+impl<F> ProfiledForest<F>
+where
+    F: EvalLinkUpdate,
+{
+    /// Wraps a forest.
+    #[inline]
+    #[must_use]
+    pub fn new(forest: F) -> Self {
+        Self {
+            forest,
+            profile: Profile::default(),
+        }
+    }
+
+    /// Returns the wrapped forest.
+    #[inline]
+    pub fn into_inner(self) -> F {
+        self.forest
+    }
+
+    /// Returns a reference to the wrapped forest.
+    #[inline]
+    #[must_use]
+    pub fn inner(&self) -> &F {
+        &self.forest
+    }
+
+    /// Returns the profile of the operations called since the forest was wrapped or since the last reset.
+    #[inline]
+    #[must_use]
+    pub fn profile(&self) -> &Profile {
+        &self.profile
+    }
+
+    /// Resets the profile of every operation.
+    #[inline]
+    pub fn reset_profile(&mut self) {
+        self.profile = Profile::default();
+    }
+}
+
+// Calls `f` on the forest and adds `calls` calls and the time it took to the profile.
+fn measure<F, T>(
+    forest: &mut F,
+    profile: &mut OperationProfile,
+    calls: u64,
+    f: impl FnOnce(&mut F) -> T,
+) -> T {
+    let start = Instant::now();
+    let result = f(forest);
+    profile.time += start.elapsed();
+    profile.calls += calls;
+    result
+}
+
+impl<F> EvalLinkUpdate for ProfiledForest<F>
+where
+    F: EvalLinkUpdate,
+{
+    type Id = F::Id;
+    type Value = F::Value;
+    type Operation = F::Operation;
+    type Error = F::Error;
+
+    #[inline]
+    fn node_count(&self) -> Option<usize> {
+        self.forest.node_count()
+    }
+
+    fn try_find_root(&mut self, id: F::Id) -> Result<F::Id, ForestError<OperationError<F>>> {
+        measure(&mut self.forest, &mut self.profile.find_root, 1, |forest| {
+            forest.try_find_root(id)
+        })
+    }
+
+    fn try_link(&mut self, id_a: F::Id, id_b: F::Id) -> Result<(), ForestError<OperationError<F>>> {
+        measure(&mut self.forest, &mut self.profile.link, 1, |forest| {
+            forest.try_link(id_a, id_b)
+        })
+    }
+
+    fn try_update(
+        &mut self,
+        id: F::Id,
+        value: F::Value,
+    ) -> Result<(), ForestError<OperationError<F>>> {
+        measure(&mut self.forest, &mut self.profile.update, 1, |forest| {
+            forest.try_update(id, value)
+        })
+    }
+
+    fn try_eval(&mut self, id: F::Id) -> Result<F::Value, ForestError<OperationError<F>>> {
+        measure(&mut self.forest, &mut self.profile.eval, 1, |forest| {
+            forest.try_eval(id)
+        })
+    }
+
+    fn try_eval_many(
+        &mut self,
+        ids: &[F::Id],
+    ) -> Result<Vec<F::Value>, ForestError<OperationError<F>>>
+    where
+        F::Id: Clone,
+    {
+        measure(
+            &mut self.forest,
+            &mut self.profile.eval,
+            ids.len() as u64,
+            |forest| forest.try_eval_many(ids),
+        )
+    }
+}
+
+impl<F> NewRoot for ProfiledForest<F>
+where
+    F: NewRoot,
+{
+    fn new_root(&mut self, value: F::Value) -> F::Id {
+        measure(&mut self.forest, &mut self.profile.new_root, 1, |forest| {
+            forest.new_root(value)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::operation::*;
+    use crate::CompressedForest;
+
+    #[test]
+    fn add_forest() {
+        let mut forest: ProfiledForest<CompressedForest<usize, CloneAdd>> =
+            ProfiledForest::default();
+        let v0 = forest.new_root(2);
+        let v1 = forest.new_root(3);
+        let v2 = forest.new_root(4);
+        let v3 = forest.new_root(5);
+
+        forest.link(v0, v1);
+        forest.link(v2, v3);
+        forest.link(v3, v0);
+
+        assert_eq!(14, forest.eval(v1));
+        assert_eq!(vec![11, 14], forest.eval_many(&[v0, v1]));
+        forest.update(v1, 1);
+        assert_eq!(Err(ForestError::WouldCreateCycle), forest.try_link(v1, v2));
+
+        let profile = *forest.profile();
+        assert_eq!(4, profile.new_root.calls);
+        assert_eq!(4, profile.link.calls);
+        assert_eq!(3, profile.eval.calls);
+        assert_eq!(1, profile.update.calls);
+        assert_eq!(0, profile.find_root.calls);
+        assert!(profile.find_root.mean_time().is_none());
+        assert!(profile.eval.mean_time().is_some());
+
+        forest.reset_profile();
+        assert_eq!(Profile::default(), *forest.profile());
+        assert_eq!(11, forest.into_inner().eval(v1));
+    }
+}
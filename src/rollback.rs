@@ -0,0 +1,316 @@
+use std::marker::PhantomData;
+
+use crate::balanced::BalancedNode;
+use crate::index::Index;
+use crate::operation::GroupOperation;
+use crate::{EvalLinkUpdate, ForestError, NewRoot};
+
+#[derive(Debug, Clone)]
+enum Undo<V> {
+    NewRoot,
+    Restore(usize, BalancedNode<V>),
+}
+
+/// A state of a [`RollbackForest`] that can be restored with [`rollback_to`](RollbackForest::rollback_to).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Checkpoint(usize);
+
+/// An EVAL-LINK-UPDATE forest structure that links by rank and can undo its most recent operations.
+///
+/// Every modification is recorded in a journal, so that the forest can be rolled back to any earlier [`Checkpoint`].
+/// Path compression would make undoing expensive, so paths are only kept short by linking by rank: operations run in
+/// `O(log n)` time. As for [`BalancedForest`](crate::BalancedForest), this requires `O` to be a [`GroupOperation`].
+///
+/// `V` is the value type associated to nodes in the forest and `O` is the associative operation applied when evaluating.
+#[derive(Debug, Clone)]
+pub struct RollbackForest<V, O>
+where
+    O: 'static,
+{
+    nodes: Vec<BalancedNode<V>>,
+    journal: Vec<Undo<V>>,
+    _op: PhantomData<O>,
+}
+
+impl<V, O> Default for RollbackForest<V, O>
+where
+    V: Clone,
+    O: GroupOperation<V>,
+{
+    #[inline]
+    fn default() -> Self {
+        Self {
+            nodes: vec![],
+            journal: vec![],
+            _op: PhantomData,
+        }
+    }
+}
+
+impl<V, O> RollbackForest<V, O>
+where
+    V: Clone,
+    O: GroupOperation<V>,
+{
+    /// Creates a new empty forest.
+    #[inline]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new empty forest with a given capacity.
+    #[inline]
+    #[must_use]
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            nodes: Vec::with_capacity(capacity),
+            journal: vec![],
+            _op: PhantomData,
+        }
+    }
+
+    /// Reserve enough space for a given number of nodes.
+    #[inline]
+    pub fn reserve(&mut self, additional: usize) {
+        self.nodes.reserve(additional);
+    }
+
+    /// Returns a checkpoint of the current state of the forest.
+    #[inline]
+    #[must_use]
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint(self.journal.len())
+    }
+
+    /// Restores the state of the forest at the given checkpoint, undoing every operation performed since then.
+    ///
+    /// Nodes created after the checkpoint are removed and their ids must no longer be used.
+    /// Checkpoints taken after the given one are invalidated.
+    ///
+    /// # Panics
+    /// Panics if the checkpoint is more recent than the current state of the forest.
+    pub fn rollback_to(&mut self, checkpoint: Checkpoint) {
+        assert!(
+            checkpoint.0 <= self.journal.len(),
+            "checkpoint is more recent than the current state of the forest"
+        );
+        for undo in self.journal.drain(checkpoint.0..).rev() {
+            match undo {
+                Undo::NewRoot => {
+                    self.nodes.pop();
+                }
+                Undo::Restore(key, node) => self.nodes[key] = node,
+            }
+        }
+    }
+
+    /// Clears the journal, making the current state the oldest one that can be restored.
+    ///
+    /// Every checkpoint is invalidated.
+    #[inline]
+    pub fn forget(&mut self) {
+        self.journal.clear();
+    }
+
+    fn check(&self, id: Index<Self>) -> Result<usize, ForestError<O::Error>> {
+        let key: usize = id.into();
+        if key < self.nodes.len() {
+            Ok(key)
+        } else {
+            Err(ForestError::InvalidId)
+        }
+    }
+
+    fn find(&self, mut key: usize) -> usize {
+        while let Some(parent_key) = self.nodes[key].parent {
+            key = parent_key;
+        }
+        key
+    }
+
+    fn eval_key(&self, mut key: usize) -> Result<V, ForestError<O::Error>> {
+        let mut value = self.nodes[key].value.clone();
+        while let Some(parent_key) = self.nodes[key].parent {
+            value = O::associate(&self.nodes[parent_key].value, &value)
+                .map_err(ForestError::Operation)?;
+            key = parent_key;
+        }
+        Ok(value)
+    }
+
+    fn record(&mut self, key: usize) {
+        self.journal
+            .push(Undo::Restore(key, self.nodes[key].clone()));
+    }
+}
+
+impl<V, O> EvalLinkUpdate for RollbackForest<V, O>
+where
+    V: Clone,
+    O: 'static + GroupOperation<V>,
+{
+    type Id = Index<Self>;
+    type Value = V;
+    type Operation = O;
+    type Error = O::Error;
+
+    #[inline]
+    fn node_count(&self) -> Option<usize> {
+        Some(self.nodes.len())
+    }
+
+    fn try_find_root(&mut self, id: Index<Self>) -> Result<Index<Self>, ForestError<O::Error>> {
+        let key = self.check(id)?;
+        Ok(Index::new(self.nodes[self.find(key)].root))
+    }
+
+    fn try_link(
+        &mut self,
+        id_a: Index<Self>,
+        id_b: Index<Self>,
+    ) -> Result<(), ForestError<O::Error>> {
+        let id_a = self.check(id_a)?;
+        let id_b = self.check(id_b)?;
+
+        let repr_a_key = self.find(id_a);
+        let repr_b_key = self.find(id_b);
+        if repr_a_key == repr_b_key {
+            return Err(ForestError::WouldCreateCycle);
+        }
+
+        let rank_a = self.nodes[repr_a_key].rank;
+        let rank_b = self.nodes[repr_b_key].rank;
+        let value_a = self.eval_key(id_a)?;
+
+        if rank_b <= rank_a {
+            // the representative of "tree b" becomes a child of the representative of "tree a"
+            let inverse =
+                O::inverse(&self.nodes[repr_a_key].value).map_err(ForestError::Operation)?;
+            let relative_a = O::associate(&inverse, &value_a).map_err(ForestError::Operation)?;
+            let new_value = O::associate(&relative_a, &self.nodes[repr_b_key].value)
+                .map_err(ForestError::Operation)?;
+
+            self.record(repr_a_key);
+            self.record(repr_b_key);
+            let repr_b = &mut self.nodes[repr_b_key];
+            repr_b.value = new_value;
+            repr_b.parent = Some(repr_a_key);
+            if rank_a == rank_b {
+                self.nodes[repr_a_key].rank += 1;
+            }
+        } else {
+            // the representative of "tree a" becomes a child of the representative of "tree b"
+            // but "tree b" is still linked below "node a", so values of both representatives must be fixed
+            let new_value_b = O::associate(&value_a, &self.nodes[repr_b_key].value)
+                .map_err(ForestError::Operation)?;
+            let inverse_b = O::inverse(&new_value_b).map_err(ForestError::Operation)?;
+            let new_value_a = O::associate(&inverse_b, &self.nodes[repr_a_key].value)
+                .map_err(ForestError::Operation)?;
+
+            self.record(repr_a_key);
+            self.record(repr_b_key);
+            let root_a_key = self.nodes[repr_a_key].root;
+            let repr_a = &mut self.nodes[repr_a_key];
+            repr_a.value = new_value_a;
+            repr_a.parent = Some(repr_b_key);
+            let repr_b = &mut self.nodes[repr_b_key];
+            repr_b.value = new_value_b;
+            repr_b.root = root_a_key;
+        }
+
+        Ok(())
+    }
+
+    fn try_update(&mut self, id: Index<Self>, value: V) -> Result<(), ForestError<O::Error>> {
+        let key = self.check(id)?;
+        let repr_key = self.find(key);
+        let root_key = self.nodes[repr_key].root;
+
+        let new_value = if root_key == repr_key {
+            value
+        } else {
+            // every value of the tree is prefixed by the value of its root,
+            // replace this prefix in the value of the representative
+            let old_value = self.eval_key(root_key)?;
+            let inverse = O::inverse(&old_value).map_err(ForestError::Operation)?;
+            let delta = O::associate(&value, &inverse).map_err(ForestError::Operation)?;
+            O::associate(&delta, &self.nodes[repr_key].value).map_err(ForestError::Operation)?
+        };
+
+        self.record(repr_key);
+        self.nodes[repr_key].value = new_value;
+        Ok(())
+    }
+
+    fn try_eval(&mut self, id: Index<Self>) -> Result<V, ForestError<O::Error>> {
+        let key = self.check(id)?;
+        self.eval_key(key)
+    }
+}
+
+impl<V, O> NewRoot for RollbackForest<V, O>
+where
+    V: Clone,
+    O: 'static + GroupOperation<V>,
+{
+    fn new_root(&mut self, value: V) -> Index<Self> {
+        let index = self.nodes.len();
+        self.nodes.push(BalancedNode::new_root(index, value));
+        self.journal.push(Undo::NewRoot);
+        Index::new(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::operation::*;
+
+    #[test]
+    fn add_forest() {
+        let mut forest: RollbackForest<i64, CloneAdd> = RollbackForest::with_capacity(4);
+        let v0 = forest.new_root(2);
+        let v1 = forest.new_root(3);
+        let v2 = forest.new_root(4);
+        let v3 = forest.new_root(5);
+
+        forest.link(v0, v1);
+        forest.link(v2, v3);
+        let checkpoint = forest.checkpoint();
+
+        forest.link(v3, v0);
+        forest.update(v1, 1);
+
+        assert_eq!(11, forest.eval(v1));
+        assert_eq!(v2, forest.find_root(v0));
+
+        forest.rollback_to(checkpoint);
+
+        assert_eq!(5, forest.eval(v1));
+        assert_eq!(9, forest.eval(v3));
+        assert_eq!(v0, forest.find_root(v1));
+        assert!(!forest.connected(v1, v3));
+    }
+
+    #[test]
+    fn rollback_new_roots() {
+        let mut forest: RollbackForest<i64, CloneAdd> = RollbackForest::new();
+        let v0 = forest.new_root(2);
+        let checkpoint = forest.checkpoint();
+
+        let mut root = forest.new_root(3);
+        forest.link(root, v0);
+        for i in 0..16 {
+            let new_root = forest.new_root(i);
+            forest.link(new_root, root);
+            root = new_root;
+        }
+        assert_eq!(2 + 3 + (0..16).sum::<i64>(), forest.eval(v0));
+
+        forest.rollback_to(checkpoint);
+
+        assert_eq!(2, forest.eval(v0));
+        assert_eq!(Err(ForestError::InvalidId), forest.try_eval(root));
+    }
+}
@@ -0,0 +1,423 @@
+use std::marker::PhantomData;
+
+use crate::node::Node;
+use crate::operation::{AssociativeOperation, DefaultOperation};
+use crate::{EvalLinkUpdate, ForestError, NewRoot};
+
+/// A generation-tagged index type for identifying nodes in a [`SlotForest`].
+///
+/// Slots are reused once their tree is removed, the generation tells apart the nodes that occupied the same slot.
+#[derive(Debug)]
+pub struct SlotIndex<F> {
+    key: usize,
+    generation: u32,
+    _forest: PhantomData<F>,
+}
+
+impl<F> SlotIndex<F> {
+    #[inline]
+    fn new(key: usize, generation: u32) -> Self {
+        Self {
+            key,
+            generation,
+            _forest: PhantomData,
+        }
+    }
+}
+
+impl<F> Clone for SlotIndex<F> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<F> Copy for SlotIndex<F> {}
+
+impl<F> PartialEq for SlotIndex<F> {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key && self.generation == other.generation
+    }
+}
+impl<F> Eq for SlotIndex<F> {}
+
+#[derive(Debug, Clone)]
+struct SlotNode<V> {
+    node: Node<V>,
+    // next node of the same tree, trees are kept as lists of their nodes starting from their root
+    next: Option<usize>,
+    // last node of the list, only meaningful for roots
+    last: usize,
+}
+
+#[derive(Debug, Clone)]
+struct Slot<V> {
+    generation: u32,
+    node: Option<SlotNode<V>>,
+}
+
+/// An EVAL-LINK-UPDATE forest structure with path compression that supports removing whole trees.
+///
+/// Slots of removed nodes are reused by new nodes, ids are tagged with a generation so that ids of removed nodes
+/// are reported as invalid instead of silently pointing to new nodes.
+///
+/// `V` is the value type associated to nodes in the forest and `O` is the associative operation applied when evaluating.
+#[derive(Debug, Clone)]
+pub struct SlotForest<V, O = DefaultOperation>
+where
+    O: 'static,
+{
+    slots: Vec<Slot<V>>,
+    free: Vec<usize>,
+    _op: PhantomData<O>,
+}
+
+impl<V, O> Default for SlotForest<V, O>
+where
+    V: Clone,
+    O: AssociativeOperation<V>,
+{
+    #[inline]
+    fn default() -> Self {
+        Self {
+            slots: vec![],
+            free: vec![],
+            _op: PhantomData,
+        }
+    }
+}
+
+impl<V, O> SlotForest<V, O>
+where
+    V: Clone,
+    O: AssociativeOperation<V>,
+{
+    /// Creates a new empty forest.
+    #[inline]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new empty forest with a given capacity.
+    #[inline]
+    #[must_use]
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            slots: Vec::with_capacity(capacity),
+            free: vec![],
+            _op: PhantomData,
+        }
+    }
+
+    /// Reserve enough space for a given number of nodes.
+    #[inline]
+    pub fn reserve(&mut self, additional: usize) {
+        self.slots
+            .reserve(additional.saturating_sub(self.free.len()));
+    }
+
+    /// Removes the tree that contains the node identified by `id`, its slots are reused by new nodes.
+    ///
+    /// # Errors
+    /// Will return [`ForestError::InvalidId`] if the id does not belong to the forest
+    /// and [`ForestError::Operation`] if [`O::associate`](AssociativeOperation::associate) fails.
+    pub fn try_remove_tree(&mut self, id: SlotIndex<Self>) -> Result<(), ForestError<O::Error>> {
+        let key = self.check(id)?;
+        let mut current = Some(self.find_root_key(key)?);
+        while let Some(key) = current {
+            let slot = &mut self.slots[key];
+            current = slot.node.take().and_then(|node| node.next);
+            slot.generation = slot.generation.wrapping_add(1);
+            self.free.push(key);
+        }
+        Ok(())
+    }
+
+    /// Removes the node identified by `id` if it is the root of a tree with no other node, its slot is reused by new nodes.
+    ///
+    /// Returns `false` and leaves the forest unchanged if the node is not alone in its tree,
+    /// see [`try_remove_tree`](SlotForest::try_remove_tree) to remove the whole tree.
+    ///
+    /// # Errors
+    /// Will return [`ForestError::InvalidId`] if the id does not belong to the forest.
+    pub fn try_remove_root(&mut self, id: SlotIndex<Self>) -> Result<bool, ForestError<O::Error>> {
+        let key = self.check(id)?;
+        let node = self.node(key);
+        if !node.node.is_root() || node.next.is_some() {
+            return Ok(false);
+        }
+        let slot = &mut self.slots[key];
+        slot.node = None;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free.push(key);
+        Ok(true)
+    }
+
+    fn check(&self, id: SlotIndex<Self>) -> Result<usize, ForestError<O::Error>> {
+        match self.slots.get(id.key) {
+            Some(slot) if slot.generation == id.generation && slot.node.is_some() => Ok(id.key),
+            _ => Err(ForestError::InvalidId),
+        }
+    }
+
+    // Assumes the slot is occupied.
+    fn node(&self, key: usize) -> &SlotNode<V> {
+        self.slots[key].node.as_ref().unwrap()
+    }
+
+    fn node_mut(&mut self, key: usize) -> &mut SlotNode<V> {
+        self.slots[key].node.as_mut().unwrap()
+    }
+
+    fn compress(&mut self, key: usize) -> Result<(), ForestError<O::Error>> {
+        let mut path = vec![key];
+        let mut current = key;
+        while let Some(parent_key) = self.node(current).node.parent() {
+            path.push(parent_key);
+            current = parent_key;
+        }
+
+        // nodes are compressed from the top, each of them is linked to the parent of its parent
+        // (i.e the root) once its parent has been compressed
+        for i in (0..path.len().saturating_sub(2)).rev() {
+            let parent = &self.node(path[i + 1]).node;
+            let root_key = parent.parent().unwrap();
+            let merged_values = O::associate(parent.value(), self.node(path[i]).node.value())
+                .map_err(ForestError::Operation)?;
+            let node = &mut self.node_mut(path[i]).node;
+            node.set_value(merged_values);
+            node.set_parent(root_key);
+        }
+
+        Ok(())
+    }
+
+    fn find_root_key(&mut self, key: usize) -> Result<usize, ForestError<O::Error>> {
+        match self.node(key).node.parent() {
+            None => Ok(key),
+            Some(_) => {
+                self.compress(key)?;
+                Ok(self.node(key).node.parent().unwrap())
+            }
+        }
+    }
+
+    fn index(&self, key: usize) -> SlotIndex<Self> {
+        SlotIndex::new(key, self.slots[key].generation)
+    }
+}
+
+impl<V, O> SlotForest<V, O>
+where
+    V: Clone,
+    O: AssociativeOperation<V, Error = std::convert::Infallible>,
+{
+    /// Infallible version of [`try_remove_tree`](SlotForest::try_remove_tree).
+    ///
+    /// # Panics
+    /// Panics if the id does not belong to the forest.
+    #[inline]
+    pub fn remove_tree(&mut self, id: SlotIndex<Self>) {
+        self.try_remove_tree(id).unwrap();
+    }
+
+    /// Infallible version of [`try_remove_root`](SlotForest::try_remove_root).
+    ///
+    /// # Panics
+    /// Panics if the id does not belong to the forest.
+    #[inline]
+    pub fn remove_root(&mut self, id: SlotIndex<Self>) -> bool {
+        self.try_remove_root(id).unwrap()
+    }
+}
+
+impl<V, O> EvalLinkUpdate for SlotForest<V, O>
+where
+    V: Clone,
+    O: 'static + AssociativeOperation<V>,
+{
+    type Id = SlotIndex<Self>;
+    type Value = V;
+    type Operation = O;
+    type Error = O::Error;
+
+    #[inline]
+    fn node_count(&self) -> Option<usize> {
+        Some(self.slots.len() - self.free.len())
+    }
+
+    fn try_find_root(
+        &mut self,
+        id: SlotIndex<Self>,
+    ) -> Result<SlotIndex<Self>, ForestError<O::Error>> {
+        let key = self.check(id)?;
+        let root_key = self.find_root_key(key)?;
+        Ok(self.index(root_key))
+    }
+
+    fn try_link(
+        &mut self,
+        id_a: SlotIndex<Self>,
+        id_b: SlotIndex<Self>,
+    ) -> Result<(), ForestError<O::Error>> {
+        let id_a = self.check(id_a)?;
+        let id_b = self.check(id_b)?;
+
+        let root_a_key = self.find_root_key(id_a)?;
+        let root_b_key = self.find_root_key(id_b)?;
+        if root_a_key == root_b_key {
+            return Err(ForestError::WouldCreateCycle);
+        }
+
+        // if "node a" is not the root of it's tree
+        // need to update the value of "node b"
+        if root_a_key != id_a {
+            let new_value = O::associate(
+                self.node(id_a).node.value(),
+                self.node(root_b_key).node.value(),
+            )
+            .map_err(ForestError::Operation)?;
+            self.node_mut(root_b_key).node.set_value(new_value);
+        }
+        self.node_mut(root_b_key).node.set_parent(root_a_key);
+
+        // append the nodes of "tree b" to the nodes of "tree a"
+        let last_a_key = self.node(root_a_key).last;
+        let last_b_key = self.node(root_b_key).last;
+        self.node_mut(last_a_key).next = Some(root_b_key);
+        self.node_mut(root_a_key).last = last_b_key;
+
+        Ok(())
+    }
+
+    fn try_update(&mut self, id: SlotIndex<Self>, value: V) -> Result<(), ForestError<O::Error>> {
+        let key = self.check(id)?;
+        let root_key = self.find_root_key(key)?;
+        self.node_mut(root_key).node.set_value(value);
+        Ok(())
+    }
+
+    fn try_eval(&mut self, id: SlotIndex<Self>) -> Result<V, ForestError<O::Error>> {
+        let key = self.check(id)?;
+        self.compress(key)?;
+
+        let node = &self.node(key).node;
+        match node.parent() {
+            None => Ok(node.value().clone()),
+            Some(parent_key) => {
+                let parent = &self.node(parent_key).node;
+                O::associate(parent.value(), node.value()).map_err(ForestError::Operation)
+            }
+        }
+    }
+}
+
+impl<V, O> NewRoot for SlotForest<V, O>
+where
+    V: Clone,
+    O: 'static + AssociativeOperation<V>,
+{
+    fn new_root(&mut self, value: V) -> SlotIndex<Self> {
+        let key = self.free.pop().unwrap_or_else(|| {
+            self.slots.push(Slot {
+                generation: 0,
+                node: None,
+            });
+            self.slots.len() - 1
+        });
+        self.slots[key].node = Some(SlotNode {
+            node: Node::new_root(value),
+            next: None,
+            last: key,
+        });
+        self.index(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::operation::*;
+
+    #[test]
+    fn add_forest() {
+        let mut forest: SlotForest<usize, CloneAdd> = SlotForest::with_capacity(4);
+        let v0 = forest.new_root(2);
+        let v1 = forest.new_root(3);
+        let v2 = forest.new_root(4);
+        let v3 = forest.new_root(5);
+
+        forest.link(v0, v1);
+        forest.link(v2, v3);
+
+        assert_eq!(5, forest.eval(v1));
+        assert_eq!(9, forest.eval(v3));
+
+        forest.link(v3, v0);
+
+        assert_eq!(11, forest.eval(v0));
+        assert_eq!(14, forest.eval(v1));
+        assert_eq!(v2, forest.find_root(v1));
+
+        forest.update(v1, 1);
+
+        assert_eq!(11, forest.eval(v1));
+        assert_eq!(Err(ForestError::WouldCreateCycle), forest.try_link(v1, v2));
+    }
+
+    #[test]
+    fn remove_tree() {
+        let mut forest: SlotForest<usize, CloneAdd> = SlotForest::new();
+        let v0 = forest.new_root(2);
+        let v1 = forest.new_root(3);
+        let v2 = forest.new_root(4);
+        let v3 = forest.new_root(5);
+
+        forest.link(v0, v1);
+        forest.link(v1, v2);
+        forest.remove_tree(v2);
+
+        for id in [v0, v1, v2] {
+            assert_eq!(Err(ForestError::InvalidId), forest.try_eval(id));
+        }
+        assert_eq!(5, forest.eval(v3));
+
+        // the three free slots are reused but old ids stay invalid
+        let w0 = forest.new_root(6);
+        let w1 = forest.new_root(7);
+        let w2 = forest.new_root(8);
+        let w3 = forest.new_root(9);
+        assert_eq!(5, forest.slots.len());
+        assert_eq!(Err(ForestError::InvalidId), forest.try_eval(v0));
+
+        forest.link(w0, w1);
+        forest.link(v3, w0);
+        forest.link(w3, w2);
+        assert_eq!(18, forest.eval(w1));
+
+        forest.remove_tree(w1);
+        assert_eq!(17, forest.eval(w2));
+        assert_eq!(3, forest.free.len());
+        assert_eq!(Some(2), forest.node_count());
+    }
+
+    #[test]
+    fn remove_root() {
+        let mut forest: SlotForest<usize, CloneAdd> = SlotForest::new();
+        let v0 = forest.new_root(2);
+        let v1 = forest.new_root(3);
+        let v2 = forest.new_root(4);
+
+        forest.link(v0, v1);
+
+        assert!(!forest.remove_root(v0));
+        assert!(!forest.remove_root(v1));
+        assert!(forest.remove_root(v2));
+        assert_eq!(Err(ForestError::InvalidId), forest.try_remove_root(v2));
+
+        let w2 = forest.new_root(5);
+        assert_eq!(3, forest.slots.len());
+        forest.link(v1, w2);
+        assert_eq!(10, forest.eval(w2));
+        assert_eq!(Some(3), forest.node_count());
+    }
+}
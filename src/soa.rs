@@ -0,0 +1,280 @@
+use std::marker::PhantomData;
+
+use crate::index::{Index, IndexType};
+use crate::operation::{AssociativeOperation, DefaultOperation};
+use crate::{EvalLinkUpdate, ForestError, NewRoot};
+
+/// An EVAL-LINK-UPDATE forest structure with path compression that stores parents and values in separate arrays.
+///
+/// This is the same structure as [`CompressedForest`](crate::CompressedForest), but parents are stored apart from values:
+/// once a path is compressed, its root is found by reading parents only, so large values do not get in the way of the
+/// cache. Values are still read and written when a path is compressed.
+///
+/// `V` is the value type associated to nodes in the forest and `O` is the associative operation applied when evaluating.
+/// `Ix` is the integer type used to store parent indices (see [`IndexType`]), it bounds the number of nodes in the forest.
+#[derive(Debug, Clone)]
+pub struct SoaForest<V, O = DefaultOperation, Ix = usize>
+where
+    O: 'static,
+{
+    // the parent of a root is encoded as `Ix::MAX`
+    parents: Vec<Ix>,
+    values: Vec<V>,
+    _op: PhantomData<O>,
+}
+
+impl<V, O, Ix> Default for SoaForest<V, O, Ix>
+where
+    V: Clone,
+    O: AssociativeOperation<V>,
+    Ix: IndexType,
+{
+    #[inline]
+    fn default() -> Self {
+        Self {
+            parents: vec![],
+            values: vec![],
+            _op: PhantomData,
+        }
+    }
+}
+
+impl<V, O, Ix> SoaForest<V, O, Ix>
+where
+    V: Clone,
+    O: AssociativeOperation<V>,
+    Ix: IndexType,
+{
+    /// Creates a new empty forest.
+    #[inline]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new empty forest with a given capacity.
+    #[inline]
+    #[must_use]
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            parents: Vec::with_capacity(capacity),
+            values: Vec::with_capacity(capacity),
+            _op: PhantomData,
+        }
+    }
+
+    /// Reserve enough space for a given number of nodes.
+    #[inline]
+    pub fn reserve(&mut self, additional: usize) {
+        self.parents.reserve(additional);
+        self.values.reserve(additional);
+    }
+
+    fn check(&self, id: Index<Self>) -> Result<usize, ForestError<O::Error>> {
+        let key: usize = id.into();
+        if key < self.parents.len() {
+            Ok(key)
+        } else {
+            Err(ForestError::InvalidId)
+        }
+    }
+
+    fn parent(&self, key: usize) -> Option<usize> {
+        let parent = self.parents[key].index();
+        (parent != Ix::MAX).then_some(parent)
+    }
+
+    // Links every node of the path to the root, so that the node becomes a root or a child of its root,
+    // and returns the root.
+    fn find_root_key(&mut self, key: usize) -> Result<usize, ForestError<O::Error>> {
+        let mut path = vec![];
+        let mut current = key;
+        let root_key = loop {
+            match self.parent(current) {
+                None => break current,
+                Some(parent_key) if self.parent(parent_key).is_none() => break parent_key,
+                Some(parent_key) => {
+                    path.push(current);
+                    current = parent_key;
+                }
+            }
+        };
+
+        // from the top, so that each parent is already compressed
+        for &key in path.iter().rev() {
+            let parent_key = self.parents[key].index();
+            let merged_values = O::associate(&self.values[parent_key], &self.values[key])
+                .map_err(ForestError::Operation)?;
+            self.values[key] = merged_values;
+            self.parents[key] = Ix::new(root_key);
+        }
+        Ok(root_key)
+    }
+}
+
+impl<V, O, Ix> EvalLinkUpdate for SoaForest<V, O, Ix>
+where
+    V: Clone,
+    O: 'static + AssociativeOperation<V>,
+    Ix: IndexType,
+{
+    type Id = Index<Self>;
+    type Value = V;
+    type Operation = O;
+    type Error = O::Error;
+
+    #[inline]
+    fn node_count(&self) -> Option<usize> {
+        Some(self.parents.len())
+    }
+
+    fn try_find_root(&mut self, id: Index<Self>) -> Result<Index<Self>, ForestError<O::Error>> {
+        let key = self.check(id)?;
+        Ok(Index::new(self.find_root_key(key)?))
+    }
+
+    fn try_link(
+        &mut self,
+        id_a: Index<Self>,
+        id_b: Index<Self>,
+    ) -> Result<(), ForestError<O::Error>> {
+        let id_a = self.check(id_a)?;
+        let id_b = self.check(id_b)?;
+
+        let root_a_key = self.find_root_key(id_a)?;
+        let root_b_key = self.find_root_key(id_b)?;
+        if root_a_key == root_b_key {
+            return Err(ForestError::WouldCreateCycle);
+        }
+
+        // if "node a" is not the root of it's tree
+        // need to update the value of "node b"
+        if root_a_key != id_a {
+            let new_value = O::associate(&self.values[id_a], &self.values[root_b_key])
+                .map_err(ForestError::Operation)?;
+            self.values[root_b_key] = new_value;
+        }
+        self.parents[root_b_key] = Ix::new(root_a_key);
+
+        Ok(())
+    }
+
+    fn try_update(&mut self, id: Index<Self>, value: V) -> Result<(), ForestError<O::Error>> {
+        let key = self.check(id)?;
+        let root_key = self.find_root_key(key)?;
+        self.values[root_key] = value;
+        Ok(())
+    }
+
+    fn try_eval(&mut self, id: Index<Self>) -> Result<V, ForestError<O::Error>> {
+        let key = self.check(id)?;
+        let root_key = self.find_root_key(key)?;
+        if root_key == key {
+            Ok(self.values[key].clone())
+        } else {
+            O::associate(&self.values[root_key], &self.values[key]).map_err(ForestError::Operation)
+        }
+    }
+}
+
+impl<V, O, Ix> NewRoot for SoaForest<V, O, Ix>
+where
+    V: Clone,
+    O: 'static + AssociativeOperation<V>,
+    Ix: IndexType,
+{
+    /// # Panics
+    /// Panics if the forest already holds `Ix::MAX` nodes.
+    fn new_root(&mut self, value: V) -> Index<Self> {
+        let index = self.parents.len();
+        // `Ix::MAX` is reserved for roots
+        assert!(index < Ix::MAX, "the forest is full");
+        self.parents.push(Ix::new(Ix::MAX));
+        self.values.push(value);
+        Index::new(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::operation::*;
+    use crate::CompressedForest;
+
+    #[test]
+    fn add_forest() {
+        let mut forest: SoaForest<usize, CloneAdd> = SoaForest::with_capacity(4);
+        let v0 = forest.new_root(2);
+        let v1 = forest.new_root(3);
+        let v2 = forest.new_root(4);
+        let v3 = forest.new_root(5);
+
+        forest.link(v0, v1);
+        forest.link(v2, v3);
+
+        assert_eq!(5, forest.eval(v1));
+        assert_eq!(9, forest.eval(v3));
+
+        forest.link(v3, v0);
+
+        assert_eq!(11, forest.eval(v0));
+        assert_eq!(14, forest.eval(v1));
+        assert_eq!(v2, forest.find_root(v1));
+
+        forest.update(v1, 1);
+
+        assert_eq!(11, forest.eval(v1));
+        assert_eq!(Err(ForestError::WouldCreateCycle), forest.try_link(v1, v2));
+    }
+
+    #[test]
+    fn deep_chain() {
+        let n = 1_000_000;
+        let mut forest: SoaForest<u64, CloneAdd, u32> = SoaForest::with_capacity(n);
+        let ids: Vec<_> = (0..n).map(|i| forest.new_root(i as u64)).collect();
+        for i in 1..n {
+            forest.link(ids[i], ids[i - 1]);
+        }
+
+        assert_eq!(499_999_500_000, forest.eval(ids[0]));
+        assert_eq!(ids[n - 1], forest.find_root(ids[0]));
+    }
+
+    #[test]
+    fn same_as_compressed() {
+        let n = 256;
+        let mut soa: SoaForest<u64, CloneAdd, u32> = SoaForest::new();
+        let mut compressed: CompressedForest<u64, CloneAdd> = CompressedForest::new();
+        let soa_ids: Vec<_> = (0..n).map(|i| soa.new_root(i as u64)).collect();
+        let compressed_ids: Vec<_> = (0..n).map(|i| compressed.new_root(i as u64)).collect();
+
+        let mut state: usize = 7;
+        let mut next = |bound: usize| {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 33) % bound
+        };
+
+        for _ in 0..2048 {
+            let a = next(n);
+            let b = next(n);
+            match next(3) {
+                0 => assert_eq!(
+                    compressed.try_link(compressed_ids[a], compressed_ids[b]),
+                    soa.try_link(soa_ids[a], soa_ids[b])
+                ),
+                1 => {
+                    compressed.update(compressed_ids[a], b as u64);
+                    soa.update(soa_ids[a], b as u64);
+                }
+                _ => {}
+            }
+            assert_eq!(compressed.eval(compressed_ids[a]), soa.eval(soa_ids[a]));
+            assert_eq!(
+                usize::from(compressed.find_root(compressed_ids[b])),
+                usize::from(soa.find_root(soa_ids[b]))
+            );
+        }
+    }
+}
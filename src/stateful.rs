@@ -0,0 +1,264 @@
+use crate::index::Index;
+use crate::node::{compress_path, Node};
+use crate::operation::AssociativeOperationRef;
+use crate::{EvalLinkUpdate, ForestError, NewRoot};
+
+/// An EVAL-LINK-UPDATE forest structure with path compression whose associative operation is an instance,
+/// so that it can depend on runtime parameters (see [`AssociativeOperationRef`]).
+///
+/// Paths are fully compressed and trees are linked as in [`CompressedForest`](crate::CompressedForest). The
+/// [`Operation`](EvalLinkUpdate::Operation) of the forest is an [`AssociativeOperationRef`], so that it can be wrapped
+/// by [`SyncForest`](crate::SyncForest) or [`ProfiledForest`](crate::ProfiledForest) and replayed with
+/// [`offline::apply`](crate::offline::apply) like any other forest.
+///
+/// `V` is the value type associated to nodes in the forest and `O` is the type of the operation applied when evaluating.
+#[derive(Debug, Clone)]
+pub struct StatefulForest<V, O> {
+    nodes: Vec<Node<V>>,
+    operation: O,
+}
+
+impl<V, O> StatefulForest<V, O>
+where
+    V: Clone,
+    O: AssociativeOperationRef<V>,
+{
+    /// Creates a new empty forest that evaluates with `operation`.
+    #[inline]
+    #[must_use]
+    pub fn new(operation: O) -> Self {
+        Self {
+            nodes: vec![],
+            operation,
+        }
+    }
+
+    /// Creates a new empty forest with a given capacity, that evaluates with `operation`.
+    #[inline]
+    #[must_use]
+    pub fn with_capacity(operation: O, capacity: usize) -> Self {
+        Self {
+            nodes: Vec::with_capacity(capacity),
+            operation,
+        }
+    }
+
+    /// Reserve enough space for a given number of nodes.
+    #[inline]
+    pub fn reserve(&mut self, additional: usize) {
+        self.nodes.reserve(additional);
+    }
+
+    /// Returns the operation of the forest.
+    #[inline]
+    #[must_use]
+    pub fn operation(&self) -> &O {
+        &self.operation
+    }
+
+    /// Returns the number of nodes in the forest.
+    #[inline]
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Returns `true` if the forest contains no nodes.
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    fn check(&self, id: Index<Self>) -> Result<usize, ForestError<O::Error>> {
+        let key: usize = id.into();
+        if key < self.nodes.len() {
+            Ok(key)
+        } else {
+            Err(ForestError::InvalidId)
+        }
+    }
+
+    #[inline]
+    fn associate(&self, lhs: &V, rhs: &V) -> Result<V, ForestError<O::Error>> {
+        self.operation
+            .associate_ref(lhs, rhs)
+            .map_err(ForestError::Operation)
+    }
+
+    // Links every node of the path to the root, so that the node becomes a root or a child of its root.
+    fn compress(&mut self, key: usize) -> Result<(), ForestError<O::Error>> {
+        let operation = &self.operation;
+        compress_path(
+            &mut self.nodes,
+            key,
+            |lhs, rhs| operation.associate_ref(lhs, rhs),
+            |_, _| {},
+        )
+        .map_err(ForestError::Operation)?;
+        Ok(())
+    }
+
+    fn find_root_key(&mut self, key: usize) -> Result<usize, ForestError<O::Error>> {
+        self.compress(key)?;
+        Ok(self.nodes[key].parent().unwrap_or(key))
+    }
+}
+
+impl<V, O> EvalLinkUpdate for StatefulForest<V, O>
+where
+    V: Clone,
+    O: AssociativeOperationRef<V>,
+{
+    type Id = Index<Self>;
+    type Value = V;
+    type Operation = O;
+    type Error = O::Error;
+
+    #[inline]
+    fn node_count(&self) -> Option<usize> {
+        Some(self.len())
+    }
+
+    fn try_find_root(&mut self, id: Index<Self>) -> Result<Index<Self>, ForestError<O::Error>> {
+        let key = self.check(id)?;
+        Ok(Index::new(self.find_root_key(key)?))
+    }
+
+    fn try_eval(&mut self, id: Index<Self>) -> Result<V, ForestError<O::Error>> {
+        let key = self.check(id)?;
+        self.compress(key)?;
+        let node = &self.nodes[key];
+        match node.parent() {
+            None => Ok(node.value().clone()),
+            Some(root_key) => self.associate(self.nodes[root_key].value(), node.value()),
+        }
+    }
+
+    fn try_link(
+        &mut self,
+        id_a: Index<Self>,
+        id_b: Index<Self>,
+    ) -> Result<(), ForestError<O::Error>> {
+        let id_a = self.check(id_a)?;
+        let id_b = self.check(id_b)?;
+
+        let root_a_key = self.find_root_key(id_a)?;
+        let root_b_key = self.find_root_key(id_b)?;
+        if root_a_key == root_b_key {
+            return Err(ForestError::WouldCreateCycle);
+        }
+
+        // "node a" is a child of its root after compression
+        if root_a_key != id_a {
+            let new_value =
+                self.associate(self.nodes[id_a].value(), self.nodes[root_b_key].value())?;
+            self.nodes[root_b_key].set_value(new_value);
+        }
+        self.nodes[root_b_key].set_parent(root_a_key);
+        Ok(())
+    }
+
+    fn try_update(&mut self, id: Index<Self>, value: V) -> Result<(), ForestError<O::Error>> {
+        let key = self.check(id)?;
+        let root_key = self.find_root_key(key)?;
+        self.nodes[root_key].set_value(value);
+        Ok(())
+    }
+}
+
+impl<V, O> NewRoot for StatefulForest<V, O>
+where
+    V: Clone,
+    O: AssociativeOperationRef<V>,
+{
+    fn new_root(&mut self, value: V) -> Index<Self> {
+        let index = self.nodes.len();
+        self.nodes.push(Node::new_root(value));
+        Index::new(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::offline::{self, Op};
+    use crate::operation::FnOperation;
+    use crate::{ProfiledForest, SyncForest};
+
+    #[derive(Debug)]
+    struct ModAdd {
+        modulus: u64,
+    }
+
+    impl AssociativeOperationRef<u64> for ModAdd {
+        type Error = std::convert::Infallible;
+
+        fn associate_ref(&self, lhs: &u64, rhs: &u64) -> Result<u64, Self::Error> {
+            Ok((lhs + rhs) % self.modulus)
+        }
+    }
+
+    #[test]
+    fn mod_forest() {
+        let mut forest = StatefulForest::new(ModAdd { modulus: 7 });
+        let v0 = forest.new_root(2);
+        let v1 = forest.new_root(3);
+        let v2 = forest.new_root(4);
+        let v3 = forest.new_root(5);
+
+        forest.link(v0, v1);
+        forest.link(v2, v3);
+
+        assert_eq!(5, forest.eval(v1));
+        assert_eq!(2, forest.eval(v3));
+
+        forest.link(v3, v0);
+
+        assert_eq!(4, forest.eval(v0));
+        assert_eq!(0, forest.eval(v1));
+        assert_eq!(v2, forest.find_root(v1));
+
+        forest.update(v1, 1);
+
+        assert_eq!(4, forest.eval(v1));
+        assert_eq!(7, forest.operation().modulus);
+        assert_eq!(Err(ForestError::WouldCreateCycle), forest.try_link(v1, v2));
+    }
+
+    #[test]
+    fn closure_forest() {
+        let table = [[0, 1, 2], [1, 2, 0], [2, 0, 1]];
+        let mut forest = StatefulForest::with_capacity(
+            FnOperation(|lhs: &usize, rhs: &usize| table[*lhs][*rhs]),
+            3,
+        );
+        let ids: Vec<_> = (0..3).map(|i| forest.new_root(i)).collect();
+        forest.link(ids[1], ids[2]);
+        forest.link(ids[0], ids[1]);
+
+        assert_eq!(0, forest.eval(ids[2]));
+        assert_eq!(3, forest.len());
+    }
+
+    #[test]
+    fn wrapped_forest() {
+        let forest = SyncForest::new(StatefulForest::new(ModAdd { modulus: 5 }));
+        let v0 = forest.new_root(3);
+        let v1 = forest.new_root(4);
+        forest.link(v0, v1);
+        assert_eq!(v0, forest.find_root(v1));
+
+        let mut forest = ProfiledForest::new(forest.into_inner());
+        let operations = [
+            Op::NewRoot(2),
+            Op::NewRoot(4),
+            Op::Link(0, 1),
+            Op::Eval(1),
+            Op::Eval(0),
+        ];
+        assert_eq!(Ok(vec![1, 2]), offline::apply(&mut forest, &operations));
+        assert_eq!(2, forest.profile().eval.calls);
+        assert_eq!(Some(4), forest.node_count());
+    }
+}
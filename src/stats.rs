@@ -0,0 +1,78 @@
+/// Counters of the operations performed by a [`CompressedForest`](crate::CompressedForest), see
+/// [`CompressedForest::stats`](crate::CompressedForest::stats).
+#[cfg(feature = "stats")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Stats {
+    /// Number of evaluated nodes, including failed evaluations.
+    pub evals: u64,
+    /// Number of links, including links that would have created a cycle.
+    pub links: u64,
+    /// Number of updates.
+    pub updates: u64,
+    /// Number of parent pointers followed, while compressing paths and while walking them.
+    pub hops: u64,
+    /// Number of nodes linked to another ancestor by path compression.
+    pub compressions: u64,
+}
+
+// Counters that compile to nothing without the `stats` feature.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Counters {
+    #[cfg(feature = "stats")]
+    stats: Stats,
+}
+
+#[cfg_attr(not(feature = "stats"), allow(unused_variables))]
+impl Counters {
+    #[inline]
+    pub(crate) fn evals(&mut self, n: usize) {
+        #[cfg(feature = "stats")]
+        {
+            self.stats.evals += n as u64;
+        }
+    }
+
+    #[inline]
+    pub(crate) fn link(&mut self) {
+        #[cfg(feature = "stats")]
+        {
+            self.stats.links += 1;
+        }
+    }
+
+    #[inline]
+    pub(crate) fn update(&mut self) {
+        #[cfg(feature = "stats")]
+        {
+            self.stats.updates += 1;
+        }
+    }
+
+    #[inline]
+    pub(crate) fn hops(&mut self, n: usize) {
+        #[cfg(feature = "stats")]
+        {
+            self.stats.hops += n as u64;
+        }
+    }
+
+    #[inline]
+    pub(crate) fn compressions(&mut self, n: usize) {
+        #[cfg(feature = "stats")]
+        {
+            self.stats.compressions += n as u64;
+        }
+    }
+
+    #[cfg(feature = "stats")]
+    #[inline]
+    pub(crate) fn get(&self) -> Stats {
+        self.stats
+    }
+
+    #[cfg(feature = "stats")]
+    #[inline]
+    pub(crate) fn reset(&mut self) {
+        self.stats = Stats::default();
+    }
+}
@@ -0,0 +1,329 @@
+use std::marker::PhantomData;
+
+use crate::index::Index;
+use crate::node::Node;
+use crate::operation::{AssociativeOperation, DefaultOperation};
+use crate::{EvalLinkUpdate, ForestError, NewRoot};
+
+#[derive(Debug, Clone)]
+struct SubtreeNode<V> {
+    node: Node<V>,
+    // children in link order
+    children: Vec<usize>,
+    // value of the node associated with the aggregates of its children
+    aggregate: V,
+}
+
+/// An EVAL-LINK-UPDATE forest structure that also maintains the aggregate of the values of every subtree.
+///
+/// The aggregate of a node `n` with children `c1, ..., ck` (in link order) is `value(n)` ⊕ `aggregate(c1)` ⊕ ... ⊕ `aggregate(ck)`,
+/// i.e. the product of the values of the subtree in pre-order. See [`try_eval_subtree`](SubtreeForest::try_eval_subtree).
+///
+/// Nodes keep their actual parent so that aggregates can be maintained: linking recomputes the aggregates of the
+/// ancestors of the new parent, and evaluating walks the whole path from the root as in [`NaiveForest`](crate::NaiveForest).
+///
+/// `V` is the value type associated to nodes in the forest and `O` is the associative operation applied when evaluating.
+#[derive(Debug, Clone)]
+pub struct SubtreeForest<V, O = DefaultOperation>
+where
+    O: 'static,
+{
+    nodes: Vec<SubtreeNode<V>>,
+    _op: PhantomData<O>,
+}
+
+impl<V, O> Default for SubtreeForest<V, O>
+where
+    V: Clone,
+    O: AssociativeOperation<V>,
+{
+    #[inline]
+    fn default() -> Self {
+        Self {
+            nodes: vec![],
+            _op: PhantomData,
+        }
+    }
+}
+
+impl<V, O> SubtreeForest<V, O>
+where
+    V: Clone,
+    O: AssociativeOperation<V>,
+{
+    /// Creates a new empty forest.
+    #[inline]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new empty forest with a given capacity.
+    #[inline]
+    #[must_use]
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            nodes: Vec::with_capacity(capacity),
+            _op: PhantomData,
+        }
+    }
+
+    /// Reserve enough space for a given number of nodes.
+    #[inline]
+    pub fn reserve(&mut self, additional: usize) {
+        self.nodes.reserve(additional);
+    }
+
+    /// Computes the aggregate of the values of the subtree rooted at the node identified by `id`.
+    ///
+    /// # Errors
+    /// Will return [`ForestError::InvalidId`] if the id does not belong to the forest.
+    pub fn try_eval_subtree(&self, id: Index<Self>) -> Result<V, ForestError<O::Error>> {
+        let key = self.check(id)?;
+        Ok(self.nodes[key].aggregate.clone())
+    }
+
+    fn check(&self, id: Index<Self>) -> Result<usize, ForestError<O::Error>> {
+        let key: usize = id.into();
+        if key < self.nodes.len() {
+            Ok(key)
+        } else {
+            Err(ForestError::InvalidId)
+        }
+    }
+
+    fn root_key(&self, mut key: usize) -> usize {
+        while let Some(parent_key) = self.nodes[key].node.parent() {
+            key = parent_key;
+        }
+        key
+    }
+
+    // Computes the aggregate of a node with the given value from the aggregates of its children, without modifying it.
+    // The given child aggregate replaces the aggregate of this child, or is appended if it is not a child yet.
+    fn aggregate(
+        &self,
+        key: usize,
+        value: &V,
+        child: Option<(usize, &V)>,
+    ) -> Result<V, ForestError<O::Error>> {
+        let mut child = child;
+        let mut aggregate = value.clone();
+        for &child_key in &self.nodes[key].children {
+            let child_aggregate = match child {
+                Some((key, child_aggregate)) if key == child_key => {
+                    child = None;
+                    child_aggregate
+                }
+                _ => &self.nodes[child_key].aggregate,
+            };
+            aggregate =
+                O::associate(&aggregate, child_aggregate).map_err(ForestError::Operation)?;
+        }
+        if let Some((_, child_aggregate)) = child {
+            aggregate =
+                O::associate(&aggregate, child_aggregate).map_err(ForestError::Operation)?;
+        }
+        Ok(aggregate)
+    }
+}
+
+impl<V, O> SubtreeForest<V, O>
+where
+    V: Clone,
+    O: AssociativeOperation<V, Error = std::convert::Infallible>,
+{
+    /// Infallible version of [`try_eval_subtree`](SubtreeForest::try_eval_subtree).
+    ///
+    /// # Panics
+    /// Panics if the id does not belong to the forest.
+    #[inline]
+    pub fn eval_subtree(&self, id: Index<Self>) -> V {
+        self.try_eval_subtree(id).unwrap()
+    }
+}
+
+impl<V, O> EvalLinkUpdate for SubtreeForest<V, O>
+where
+    V: Clone,
+    O: 'static + AssociativeOperation<V>,
+{
+    type Id = Index<Self>;
+    type Value = V;
+    type Operation = O;
+    type Error = O::Error;
+
+    #[inline]
+    fn node_count(&self) -> Option<usize> {
+        Some(self.nodes.len())
+    }
+
+    fn try_find_root(&mut self, id: Index<Self>) -> Result<Index<Self>, ForestError<O::Error>> {
+        let key = self.check(id)?;
+        Ok(Index::new(self.root_key(key)))
+    }
+
+    fn try_link(
+        &mut self,
+        id_a: Index<Self>,
+        id_b: Index<Self>,
+    ) -> Result<(), ForestError<O::Error>> {
+        let id_a = self.check(id_a)?;
+        let id_b = self.check(id_b)?;
+
+        let root_b_key = self.root_key(id_b);
+        if self.root_key(id_a) == root_b_key {
+            return Err(ForestError::WouldCreateCycle);
+        }
+
+        // the aggregates of "node a" and of all its ancestors now include "tree b",
+        // they are all computed before any change so that a failed link leaves the forest untouched
+        let mut aggregates: Vec<(usize, V)> = vec![];
+        let mut current = Some(id_a);
+        while let Some(key) = current {
+            let child = match aggregates.last() {
+                None => (root_b_key, &self.nodes[root_b_key].aggregate),
+                Some((child_key, aggregate)) => (*child_key, aggregate),
+            };
+            let aggregate = self.aggregate(key, self.nodes[key].node.value(), Some(child))?;
+            aggregates.push((key, aggregate));
+            current = self.nodes[key].node.parent();
+        }
+
+        self.nodes[root_b_key].node.set_parent(id_a);
+        self.nodes[id_a].children.push(root_b_key);
+        for (key, aggregate) in aggregates {
+            self.nodes[key].aggregate = aggregate;
+        }
+        Ok(())
+    }
+
+    fn try_update(&mut self, id: Index<Self>, value: V) -> Result<(), ForestError<O::Error>> {
+        let key = self.check(id)?;
+        let root_key = self.root_key(key);
+        let aggregate = self.aggregate(root_key, &value, None)?;
+        self.nodes[root_key].node.set_value(value);
+        self.nodes[root_key].aggregate = aggregate;
+        Ok(())
+    }
+
+    fn try_eval(&mut self, id: Index<Self>) -> Result<V, ForestError<O::Error>> {
+        let mut key = self.check(id)?;
+
+        let mut path = vec![];
+        while let Some(parent_key) = self.nodes[key].node.parent() {
+            path.push(key);
+            key = parent_key;
+        }
+
+        path.iter()
+            .rev()
+            .try_fold(self.nodes[key].node.value().clone(), |value, &child_key| {
+                O::associate(&value, self.nodes[child_key].node.value())
+                    .map_err(ForestError::Operation)
+            })
+    }
+}
+
+impl<V, O> NewRoot for SubtreeForest<V, O>
+where
+    V: Clone,
+    O: 'static + AssociativeOperation<V>,
+{
+    fn new_root(&mut self, value: V) -> Index<Self> {
+        let index = self.nodes.len();
+        self.nodes.push(SubtreeNode {
+            aggregate: value.clone(),
+            node: Node::new_root(value),
+            children: vec![],
+        });
+        Index::new(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::operation::*;
+
+    #[test]
+    fn add_forest() {
+        let mut forest: SubtreeForest<usize, CloneAdd> = SubtreeForest::with_capacity(4);
+        let v0 = forest.new_root(2);
+        let v1 = forest.new_root(3);
+        let v2 = forest.new_root(4);
+        let v3 = forest.new_root(5);
+
+        forest.link(v0, v1);
+        forest.link(v2, v3);
+
+        assert_eq!(5, forest.eval(v1));
+        assert_eq!(5, forest.eval_subtree(v0));
+        assert_eq!(9, forest.eval_subtree(v2));
+
+        forest.link(v3, v0);
+
+        assert_eq!(14, forest.eval(v1));
+        assert_eq!(14, forest.eval_subtree(v2));
+        assert_eq!(10, forest.eval_subtree(v3));
+        assert_eq!(3, forest.eval_subtree(v1));
+
+        forest.update(v1, 1);
+
+        assert_eq!(11, forest.eval_subtree(v2));
+        assert_eq!(10, forest.eval_subtree(v3));
+        assert_eq!(Err(ForestError::WouldCreateCycle), forest.try_link(v1, v2));
+    }
+
+    #[test]
+    fn failed_link() {
+        let mut forest: SubtreeForest<u8, CheckedAdd> = SubtreeForest::new();
+        let v0 = forest.new_root(200);
+        let v1 = forest.new_root(10);
+        let v2 = forest.new_root(50);
+        forest.try_link(v0, v1).unwrap();
+
+        // the aggregate of "v1" can be computed, not the one of "v0"
+        assert_eq!(
+            Err(ForestError::Operation(Overflow)),
+            forest.try_link(v1, v2)
+        );
+        assert_eq!(Ok(210), forest.try_eval_subtree(v0));
+        assert_eq!(Ok(10), forest.try_eval_subtree(v1));
+        assert_eq!(Ok(v2), forest.try_find_root(v2));
+        assert_eq!(
+            Err(ForestError::Operation(Overflow)),
+            forest.try_update(v1, 250)
+        );
+        assert_eq!(Ok(210), forest.try_eval(v1));
+        assert_eq!(Ok(210), forest.try_eval_subtree(v0));
+    }
+
+    #[derive(Debug)]
+    struct Concat;
+
+    impl AssociativeOperation<String> for Concat {
+        type Error = std::convert::Infallible;
+
+        fn associate(lhs: &String, rhs: &String) -> Result<String, Self::Error> {
+            Ok(format!("{lhs}{rhs}"))
+        }
+    }
+
+    #[test]
+    fn pre_order() {
+        let mut forest: SubtreeForest<String, Concat> = SubtreeForest::new();
+        let ids: Vec<_> = ["a", "b", "c", "d"]
+            .iter()
+            .map(|s| forest.new_root(s.to_string()))
+            .collect();
+
+        forest.link(ids[0], ids[1]);
+        forest.link(ids[2], ids[3]);
+        forest.link(ids[1], ids[2]);
+
+        assert_eq!("abcd", forest.eval_subtree(ids[0]));
+        assert_eq!("bcd", forest.eval_subtree(ids[1]));
+        assert_eq!("abcd", forest.eval(ids[3]));
+    }
+}
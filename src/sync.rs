@@ -0,0 +1,264 @@
+use std::convert::Infallible;
+use std::sync::{PoisonError, RwLock, RwLockWriteGuard};
+
+use crate::{EvalLinkUpdate, ForestError, NewRoot, OperationError, SharedEval};
+
+/// A wrapper that shares an EVAL-LINK-UPDATE structure across threads behind a read-write lock.
+///
+/// Every operation takes `&self`. Evaluations that do not modify the forest (see [`SharedEval`]) only take the read lock,
+/// every other operation takes the write lock. Poisoned locks are ignored: a panic in the middle of an operation is assumed
+/// to leave the wrapped forest usable.
+///
+/// `F` is the wrapped EVAL-LINK-UPDATE structure.
+#[derive(Debug, Default)]
+pub struct SyncForest<F> {
+    forest: RwLock<F>,
+}
+
+impl<F> SyncForest<F>
+where
+    F: EvalLinkUpdate,
+{
+    /// Wraps a forest.
+    #[inline]
+    #[must_use]
+    pub fn new(forest: F) -> Self {
+        Self {
+            forest: RwLock::new(forest),
+        }
+    }
+
+    /// Returns the wrapped forest.
+    #[inline]
+    pub fn into_inner(self) -> F {
+        self.forest
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Creates a new tree root in the forest with the given value.
+    #[inline]
+    #[must_use]
+    pub fn new_root(&self, value: F::Value) -> F::Id
+    where
+        F: NewRoot,
+    {
+        self.write().new_root(value)
+    }
+
+    /// Finds the root of the tree that contains the node identified by `id`.
+    ///
+    /// # Errors
+    /// See [`EvalLinkUpdate::try_find_root`].
+    #[inline]
+    pub fn try_find_root(&self, id: F::Id) -> Result<F::Id, ForestError<OperationError<F>>> {
+        self.write().try_find_root(id)
+    }
+
+    /// Computes the value of the node identified by `id`, under the read lock if the forest does not need to be modified.
+    ///
+    /// # Errors
+    /// See [`EvalLinkUpdate::try_eval`].
+    pub fn try_eval(&self, id: F::Id) -> Result<F::Value, ForestError<OperationError<F>>>
+    where
+        F: SharedEval,
+        F::Id: Clone,
+    {
+        let shared = self
+            .forest
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .try_eval_shared(id.clone());
+        match shared {
+            Some(value) => value,
+            None => self.write().try_eval(id),
+        }
+    }
+
+    /// Links the root of the tree that contains the node identified by `id_b` to the node identified by `id_a`.
+    ///
+    /// # Errors
+    /// See [`EvalLinkUpdate::try_link`].
+    #[inline]
+    pub fn try_link(&self, id_a: F::Id, id_b: F::Id) -> Result<(), ForestError<OperationError<F>>> {
+        self.write().try_link(id_a, id_b)
+    }
+
+    /// Updates the value of the root of the tree that contains the node identified by `id`.
+    ///
+    /// # Errors
+    /// See [`EvalLinkUpdate::try_update`].
+    #[inline]
+    pub fn try_update(
+        &self,
+        id: F::Id,
+        value: F::Value,
+    ) -> Result<(), ForestError<OperationError<F>>> {
+        self.write().try_update(id, value)
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, F> {
+        self.forest.write().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<F> SyncForest<F>
+where
+    F: EvalLinkUpdate<Error = Infallible>,
+{
+    /// Infallible version of [`try_find_root`](SyncForest::try_find_root).
+    ///
+    /// # Panics
+    /// Panics if the id does not belong to the forest.
+    #[inline]
+    pub fn find_root(&self, id: F::Id) -> F::Id {
+        self.try_find_root(id).unwrap()
+    }
+
+    /// Infallible version of [`try_eval`](SyncForest::try_eval).
+    ///
+    /// # Panics
+    /// Panics if the id does not belong to the forest.
+    #[inline]
+    pub fn eval(&self, id: F::Id) -> F::Value
+    where
+        F: SharedEval,
+        F::Id: Clone,
+    {
+        self.try_eval(id).unwrap()
+    }
+
+    /// Infallible version of [`try_link`](SyncForest::try_link).
+    ///
+    /// # Panics
+    /// Panics if an id does not belong to the forest or if both nodes already belong to the same tree.
+    #[inline]
+    pub fn link(&self, id_a: F::Id, id_b: F::Id) {
+        self.try_link(id_a, id_b).unwrap();
+    }
+
+    /// Infallible version of [`try_update`](SyncForest::try_update).
+    ///
+    /// # Panics
+    /// Panics if the id does not belong to the forest.
+    #[inline]
+    pub fn update(&self, id: F::Id, value: F::Value) {
+        self.try_update(id, value).unwrap();
+    }
+}
+
+impl<F> EvalLinkUpdate for SyncForest<F>
+where
+    F: EvalLinkUpdate,
+{
+    type Id = F::Id;
+    type Value = F::Value;
+    type Operation = F::Operation;
+    type Error = F::Error;
+
+    #[inline]
+    fn node_count(&self) -> Option<usize> {
+        self.forest
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .node_count()
+    }
+
+    // exclusive access does not need the lock
+    fn try_find_root(&mut self, id: F::Id) -> Result<F::Id, ForestError<OperationError<F>>> {
+        self.get_mut().try_find_root(id)
+    }
+
+    fn try_link(&mut self, id_a: F::Id, id_b: F::Id) -> Result<(), ForestError<OperationError<F>>> {
+        self.get_mut().try_link(id_a, id_b)
+    }
+
+    fn try_update(
+        &mut self,
+        id: F::Id,
+        value: F::Value,
+    ) -> Result<(), ForestError<OperationError<F>>> {
+        self.get_mut().try_update(id, value)
+    }
+
+    fn try_eval(&mut self, id: F::Id) -> Result<F::Value, ForestError<OperationError<F>>> {
+        self.get_mut().try_eval(id)
+    }
+}
+
+impl<F> NewRoot for SyncForest<F>
+where
+    F: NewRoot,
+{
+    fn new_root(&mut self, value: F::Value) -> F::Id {
+        self.get_mut().new_root(value)
+    }
+}
+
+impl<F> SyncForest<F> {
+    #[inline]
+    fn get_mut(&mut self) -> &mut F {
+        self.forest
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::operation::*;
+    use crate::{CompressedForest, NaiveForest};
+
+    #[test]
+    fn add_forest() {
+        let mut forest: SyncForest<CompressedForest<usize, CloneAdd>> = SyncForest::default();
+        let v0 = forest.new_root(2);
+        let v1 = forest.new_root(3);
+        let v2 = forest.new_root(4);
+        let v3 = forest.new_root(5);
+
+        forest.link(v0, v1);
+        forest.link(v2, v3);
+
+        assert_eq!(5, forest.eval(v1));
+        assert_eq!(9, forest.eval(v3));
+
+        forest.link(v3, v0);
+
+        assert_eq!(11, forest.eval(v0));
+        assert_eq!(14, forest.eval(v1));
+        assert_eq!(v2, forest.find_root(v1));
+
+        forest.update(v1, 1);
+
+        assert_eq!(11, EvalLinkUpdate::eval(&mut forest, v1));
+        assert_eq!(Some(4), forest.node_count());
+        assert_eq!(Err(ForestError::WouldCreateCycle), forest.try_link(v1, v2));
+    }
+
+    #[test]
+    fn shared_chain() {
+        let forest: SyncForest<NaiveForest<usize, CloneAdd>> = SyncForest::new(NaiveForest::new());
+        let ids: Vec<_> = (0..64).map(|i| forest.new_root(i)).collect();
+
+        std::thread::scope(|scope| {
+            for thread in 0..4 {
+                let (forest, ids) = (&forest, &ids);
+                scope.spawn(move || {
+                    for i in thread * 16 + 1..(thread + 1) * 16 {
+                        forest.link(ids[i - 1], ids[i]);
+                    }
+                });
+            }
+        });
+
+        let mut forest = SyncForest::new(forest.into_inner());
+        assert_eq!(Some(64), forest.node_count());
+        for thread in 1..4 {
+            forest.link(ids[thread * 16 - 1], ids[thread * 16]);
+        }
+        assert_eq!(63 * 64 / 2, forest.eval(ids[63]));
+        assert_eq!(ids[0], EvalLinkUpdate::find_root(&mut forest, ids[63]));
+    }
+}